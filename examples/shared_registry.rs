@@ -0,0 +1,34 @@
+use jsonschema_valid::{Registry, SharedRegistry};
+use serde_json::json;
+
+const URI: &str = "https://example.com/message.json";
+
+fn main() {
+    // A registry shared between threads, instead of a global `Config`. Each
+    // validation uses a snapshot of it, so the schemas can be replaced while
+    // the threads are running.
+    let mut registry = Registry::new();
+    registry
+        .add(json!({"$id": URI, "required": ["text"]}))
+        .unwrap();
+    let shared = SharedRegistry::new(registry);
+
+    let snapshot = shared.snapshot();
+    let worker = std::thread::spawn(move || {
+        let cfg = snapshot.config(URI, None).unwrap();
+        assert!(cfg.validate(&json!({"text": "hello"})).is_ok());
+    });
+
+    // Pick up a new version of the schema, without restarting.
+    let mut registry = Registry::new();
+    registry
+        .add(json!({"$id": URI, "required": ["text", "sender"]}))
+        .unwrap();
+    shared.replace(registry);
+
+    let snapshot = shared.snapshot();
+    let cfg = snapshot.config(URI, None).unwrap();
+    assert!(cfg.validate(&json!({"text": "hello"})).is_err());
+
+    worker.join().unwrap();
+}
@@ -1,9 +1,16 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
 use serde_json::Value;
 
 use crate::context::Context;
 use crate::error::{ErrorIterator, ValidationError};
 use crate::format::FormatChecker;
-use crate::resolver::Resolver;
+use crate::hyperschema::{self, Link};
+use crate::keywords::{self, IgnoredKeyword, IgnoredKeywords};
+use crate::policy::ResolutionPolicy;
+use crate::resolver::{self, Resolver};
+use crate::retriever::{FileRetriever, Retriever};
 use crate::schemas;
 use crate::validators;
 use crate::validators::Validator;
@@ -13,12 +20,57 @@ pub struct Config<'a> {
     schema: &'a Value,
     resolver: Resolver<'a>,
     pub(crate) draft: schemas::Draft,
+    /// The vocabularies enabled by the metaschema, found when first needed,
+    /// so that a metaschema that isn't known up front is retrieved with the
+    /// retriever and policy set by then.
+    vocabularies: OnceLock<Result<Option<Vec<schemas::Vocabulary>>, ValidationError>>,
+    report_ignored_keywords: bool,
+    ref_siblings: bool,
 }
 
 impl<'a> Config<'a> {
-    /// Get the validator object for the draft in use.
+    /// Get the validator object for the draft in use. If the metaschema
+    /// declares its vocabularies, only their keywords are validated.
     pub fn get_validator<'v>(&self, key: &'v str) -> Option<Validator<'v>> {
-        self.draft.get_validator(key)
+        match self.vocabularies() {
+            Some(vocabularies) => self.draft.get_override(key).unwrap_or_else(|| {
+                vocabularies
+                    .iter()
+                    .find_map(|vocabulary| vocabulary.get_validator(key))
+            }),
+            None => self.draft.get_validator(key),
+        }
+    }
+
+    /// Get the URLs of the vocabularies enabled by the metaschema, through
+    /// `$vocabulary`. This is empty before Draft 2019-09.
+    pub fn get_vocabularies(&self) -> Vec<&str> {
+        match self.vocabularies() {
+            Some(vocabularies) => vocabularies.iter().map(|x| x.url).collect(),
+            None => self
+                .draft
+                .get_vocabularies()
+                .iter()
+                .map(|x| x.url)
+                .collect(),
+        }
+    }
+
+    /// Get the vocabularies enabled by the metaschema, or `None` to use those
+    /// of the draft.
+    fn vocabularies(&self) -> Option<&Vec<schemas::Vocabulary>> {
+        self.vocabularies
+            .get_or_init(|| find_vocabularies(self.draft, self.schema, &self.resolver))
+            .as_ref()
+            .ok()?
+            .as_ref()
+    }
+
+    /// Get the error finding the vocabularies of a retrieved metaschema, if
+    /// it requires one that isn't known.
+    pub(crate) fn vocabulary_error(&self) -> Option<&ValidationError> {
+        self.vocabularies();
+        self.vocabularies.get()?.as_ref().err()
     }
 
     /// Get the string format checker for the draft in use.
@@ -41,6 +93,55 @@ impl<'a> Config<'a> {
         &self.resolver
     }
 
+    /// Get the keywords of the schema that the draft in use doesn't evaluate,
+    /// split into those known to other drafts, those unknown to all, and
+    /// those beside a `$ref` that overrides them.
+    pub fn get_ignored_keywords(&self) -> IgnoredKeywords {
+        keywords::find_ignored_keywords(self.draft, self.schema, self.ref_siblings)
+    }
+
+    /// Get the links of an instance, from the `links` of the Hyper-Schema
+    /// schemas that apply to it.
+    ///
+    /// The `href` of each link is expanded as a URI template with the data of
+    /// the instance, and resolved against the instance's base URI. This is
+    /// `instance_url`, or the URI of the schema if it isn't given, and can be
+    /// changed by the `base` of the schemas applying to each part of the
+    /// instance. Links whose `templateRequired` variables are missing are
+    /// left out. The instance should be valid against the schema, as the
+    /// links of subschemas that it fails, such as the branches of `anyOf` it
+    /// doesn't match, don't apply to it.
+    ///
+    /// ## Example:
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    /// # use serde_json::json;
+    /// # use jsonschema_valid::Config;
+    /// let schema = json!({
+    ///     "$id": "https://example.com/schemas/thing",
+    ///     "base": "https://api.example.com/",
+    ///     "links": [{"rel": "self", "href": "things/{id}"}]
+    /// });
+    /// let cfg = Config::from_schema(&schema, None)?;
+    /// let links = cfg.get_links(&json!({"id": 42}), None)?;
+    /// assert_eq!(links[0].target_uri, "https://api.example.com/things/42");
+    /// # Ok(()) }
+    /// ```
+    pub fn get_links(
+        &'a self,
+        instance: &'a Value,
+        instance_url: Option<&str>,
+    ) -> Result<Vec<Link>, ValidationError> {
+        let base = match instance_url {
+            Some(url) => url::Url::parse(url)?,
+            None => self
+                .resolver
+                .join_url(self.draft, "", &Context::new_from(self.schema))?,
+        };
+        hyperschema::find_links(self, instance, base)
+    }
+
     /// Get the schema currently being checked against.
     pub fn get_schema(&self) -> &Value {
         self.schema
@@ -52,6 +153,19 @@ impl<'a> Config<'a> {
     /// `None`, it will be automatically determined from the `$schema` entry in
     /// the given `schema`. If no `$schema` entry is present Draft 7 will be used
     /// by default.
+    ///
+    /// From Draft 2019-09, the metaschema named by `$schema` may restrict the
+    /// keywords in use through `$vocabulary`. It is an error for it to require
+    /// a vocabulary that isn't known. A metaschema that is neither built in
+    /// nor one of the schema's resources is retrieved with the retriever set
+    /// by [`Config::with_retriever`] when it is first needed, and then an
+    /// unknown required vocabulary is reported as the only error of each
+    /// validation.
+    ///
+    /// It is also an error for a subschema to apply itself to the same part of
+    /// an instance again through its `$ref`s, as in `{"$ref": "#"}`, since
+    /// validating against it would never end, and for two subschemas of the
+    /// same schema resource to define the same anchor.
     pub fn from_schema(
         schema: &'a Value,
         draft: Option<schemas::Draft>,
@@ -59,27 +173,262 @@ impl<'a> Config<'a> {
         let draft = draft.unwrap_or_else(|| {
             schemas::draft_from_schema(schema).unwrap_or(schemas::Draft::Draft7)
         });
+        Config::from_schema_and_draft(schema, draft, false, resolver::DOCUMENT_PROTOCOL)
+    }
+
+    /// Create a new Config object from a schema that was loaded from the given
+    /// base URI.
+    ///
+    /// The draft is chosen as with [`Config::from_schema`]. Relative
+    /// references in the schema, and its own id if it is relative, are
+    /// resolved against `base_uri`, rather than against a placeholder for a
+    /// document with no URI. The documents they refer to are loaded with a
+    /// [`FileRetriever`], so that a schema at a `file:` URI can refer to the
+    /// files beside it. Another retriever, such as a `FileRetriever` mapping
+    /// URL prefixes to directories, can be set with
+    /// [`Config::with_retriever`].
+    pub fn from_schema_with_base_uri(
+        schema: &'a Value,
+        draft: Option<schemas::Draft>,
+        base_uri: &str,
+    ) -> Result<Config<'a>, ValidationError> {
+        let draft = draft.unwrap_or_else(|| {
+            schemas::draft_from_schema(schema).unwrap_or(schemas::Draft::Draft7)
+        });
+        Ok(
+            Config::from_schema_and_draft(schema, draft, false, base_uri)?
+                .with_retriever(FileRetriever::new()),
+        )
+    }
+
+    /// Create a new Config object from a schema that was loaded from the file
+    /// at `path`, so that it can refer to other files by their relative path.
+    ///
+    /// This is [`Config::from_schema_with_base_uri`] with the `file:` URI of
+    /// `path`, which is taken relative to the current directory if it isn't
+    /// absolute.
+    ///
+    /// ## Example:
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    /// # use serde_json::Value;
+    /// # use jsonschema_valid::Config;
+    /// let path = std::path::Path::new("schemas/person.json");
+    /// let schema: Value = serde_json::from_reader(std::fs::File::open(path)?)?;
+    /// // A `{"$ref": "common/address.json#/definitions/zip"}` in the schema
+    /// // refers to `schemas/common/address.json`.
+    /// let cfg = Config::from_schema_at_path(&schema, None, path)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn from_schema_at_path(
+        schema: &'a Value,
+        draft: Option<schemas::Draft>,
+        path: &Path,
+    ) -> Result<Config<'a>, ValidationError> {
+        let invalid_path =
+            || ValidationError::new(&format!("Invalid path {}", path.display()), None, None);
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()
+                .map_err(|_| invalid_path())?
+                .join(path)
+        };
+        let base_uri = url::Url::from_file_path(&path).map_err(|_| invalid_path())?;
+        Config::from_schema_with_base_uri(schema, draft, base_uri.as_str())
+    }
+
+    /// Create a new Config object from a given schema, without falling back to
+    /// Draft 7 for an unrecognized `$schema`.
+    ///
+    /// As with [`Config::from_schema`], the draft is given by `draft` or by the
+    /// `$schema` entry, and Draft 7 is used when neither is present. However,
+    /// if `draft` is `None` and the `$schema` entry isn't the URL of a known
+    /// draft or of a registered [`schemas::Dialect`], an error naming it is
+    /// returned. The keywords that the draft ignores are also reported, as
+    /// with [`Config::with_ignored_keywords_reported`].
+    pub fn from_schema_strict(
+        schema: &'a Value,
+        draft: Option<schemas::Draft>,
+    ) -> Result<Config<'a>, ValidationError> {
+        let draft = match (draft, schema.get("$schema")) {
+            (Some(draft), _) => draft,
+            (None, None) => schemas::Draft::Draft7,
+            (None, Some(url)) => {
+                url.as_str()
+                    .and_then(schemas::draft_from_url)
+                    .ok_or_else(|| {
+                        ValidationError::new(&format!("Unknown $schema {}", url), None, Some(url))
+                            .schema_ctx("$schema".to_string())
+                    })?
+            }
+        };
+        Config::from_schema_and_draft(schema, draft, true, resolver::DOCUMENT_PROTOCOL)
+    }
+
+    pub(crate) fn from_schema_and_draft(
+        schema: &'a Value,
+        draft: schemas::Draft,
+        report_ignored_keywords: bool,
+        base_uri: &str,
+    ) -> Result<Config<'a>, ValidationError> {
+        let resolver = Resolver::from_schema_with_base(draft, schema, base_uri)?;
+        let cfg = Config::from_resolver(schema, draft, report_ignored_keywords, resolver)?;
+        validators::check_reference_cycles(&cfg)?;
+        Ok(cfg)
+    }
+
+    /// Create a new Config object with a resolver that knows the schema
+    /// already, without checking the schema for reference cycles.
+    pub(crate) fn from_resolver(
+        schema: &'a Value,
+        draft: schemas::Draft,
+        report_ignored_keywords: bool,
+        resolver: Resolver<'a>,
+    ) -> Result<Config<'a>, ValidationError> {
+        let vocabularies = OnceLock::new();
+        if let Some(metaschema) = find_metaschema(draft, schema, &resolver) {
+            let _ = vocabularies.set(Ok(schemas::vocabularies_from_metaschema(
+                draft, metaschema,
+            )?));
+        }
         Ok(Config {
             schema,
-            resolver: Resolver::from_schema(draft, schema)?,
+            resolver,
             draft,
+            vocabularies,
+            report_ignored_keywords,
+            ref_siblings: false,
         })
     }
 
+    /// Set whether the keywords beside a `$ref` are evaluated along with it,
+    /// and their errors reported together with those of the reference.
+    ///
+    /// Before Draft 2019-09, a `$ref` overrides all of its siblings, which are
+    /// ignored by default. Many schemas are written expecting them to apply
+    /// anyway, as they do in later drafts and OpenAPI 3.1. Ids beside a
+    /// `$ref` are still ignored, as the draft requires.
+    ///
+    /// ## Example:
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    /// # use serde_json::json;
+    /// # use jsonschema_valid::{Config, schemas};
+    /// let schema = json!({
+    ///     "definitions": {"name": {"type": "string"}},
+    ///     "$ref": "#/definitions/name",
+    ///     "maxLength": 3
+    /// });
+    /// let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft7))?;
+    /// assert!(cfg.validate(&json!("long")).is_ok());
+    ///
+    /// let cfg = cfg.with_ref_siblings(true);
+    /// assert!(cfg.validate(&json!("long")).is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn with_ref_siblings(mut self, evaluate: bool) -> Self {
+        self.ref_siblings = evaluate;
+        self
+    }
+
+    /// Set whether [`Config::validate_schema`] reports the keywords that the
+    /// draft ignores, as given by [`Config::get_ignored_keywords`], as errors.
+    /// This is set by [`Config::from_schema_strict`].
+    ///
+    /// ## Example:
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    /// # use serde_json::json;
+    /// # use jsonschema_valid::{Config, schemas};
+    /// let schema = json!({"properties": {"a": {"bogus": true}}});
+    /// let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft7))?;
+    /// assert!(cfg.validate_schema().is_ok());
+    ///
+    /// let cfg = cfg.with_ignored_keywords_reported(true);
+    /// let errors: Vec<_> = cfg.validate_schema().unwrap_err().collect();
+    /// assert_eq!(errors[0].msg, "Unknown keyword \"bogus\" is ignored");
+    /// # Ok(()) }
+    /// ```
+    pub fn with_ignored_keywords_reported(mut self, report: bool) -> Self {
+        self.report_ignored_keywords = report;
+        self
+    }
+
+    /// Set the retriever used to load the documents referred to by `$ref`
+    /// that aren't one of the schema's own resources or a known metaschema.
+    /// Without one, such references fail to resolve.
+    ///
+    /// See [`Retriever`] for an example.
+    pub fn with_retriever(mut self, retriever: impl Retriever + 'a) -> Self {
+        self.resolver.set_retriever(Box::new(retriever));
+        self.forget_retrieved_vocabularies();
+        self
+    }
+
+    /// Set the policy that the documents loaded by the retriever must meet,
+    /// such as the hosts they may come from. Without one, any document may be
+    /// loaded.
+    ///
+    /// See [`ResolutionPolicy`] for an example.
+    pub fn with_policy(mut self, policy: ResolutionPolicy) -> Self {
+        self.resolver.set_policy(policy);
+        self.forget_retrieved_vocabularies();
+        self
+    }
+
+    /// Look for the vocabularies again when next needed, unless the
+    /// metaschema is known without retrieving it.
+    fn forget_retrieved_vocabularies(&mut self) {
+        if find_metaschema(self.draft, self.schema, &self.resolver).is_none() {
+            self.vocabularies = OnceLock::new();
+        }
+    }
+
+    /// Whether `$ref` overrides its siblings for the given draft, which is
+    /// the draft in use by the schema being evaluated.
+    pub(crate) fn ref_overrides_siblings(&self, draft: schemas::Draft) -> bool {
+        draft.ref_overrides_siblings() && !self.ref_siblings
+    }
+
     /// Validate the given JSON instance against the schema.
     pub fn validate(&'a self, instance: &'a Value) -> Result<(), ErrorIterator<'a>> {
         crate::validate(self, instance)
     }
 
     /// Validate the schema in this Config object against the metaschema.
+    ///
+    /// With [`Config::with_ignored_keywords_reported`], or if the Config was
+    /// created with [`Config::from_schema_strict`], the keywords that the
+    /// draft ignores are reported as errors as well.
     pub fn validate_schema(&'a self) -> Result<(), ErrorIterator<'a>> {
+        if let Some(err) = self.vocabulary_error() {
+            return Err(Box::new(std::iter::once(err.clone())));
+        }
+        let ignored: Vec<ValidationError> = if self.report_ignored_keywords {
+            self.get_ignored_keywords()
+                .iter()
+                .map(IgnoredKeyword::to_error)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        // The metaschema is written in the draft of its own `$schema`, which
+        // may not be the draft it describes, as for MongoDB's, which has no
+        // `$ref`.
+        let metaschema = self.get_metaschema();
+        let draft = resolver::draft_of(self.draft, metaschema);
         let mut errors = validators::descend(
             self,
             self.get_schema(),
-            self.get_metaschema(),
+            metaschema,
             None,
-            Context::new_from(self.get_metaschema()),
+            Context::new_from(metaschema).with_draft(Some(draft).filter(|x| *x != self.draft)),
         )
+        .chain(ignored)
         .peekable();
 
         if errors.peek().is_none() {
@@ -89,3 +438,37 @@ impl<'a> Config<'a> {
         }
     }
 }
+
+/// Get the URL of the metaschema named by the `$schema` of a schema, if the
+/// draft has vocabularies for it to choose from.
+fn metaschema_url(draft: schemas::Draft, schema: &Value) -> Option<url::Url> {
+    if draft.get_vocabularies().is_empty() {
+        return None;
+    }
+    let url = schema.get("$schema").and_then(Value::as_str)?;
+    url::Url::parse(url.trim_end_matches('#')).ok()
+}
+
+/// Find the metaschema named by the `$schema` of a schema, among the built-in
+/// metaschemas, registered dialects and the schema's own resources.
+fn find_metaschema<'a>(
+    draft: schemas::Draft,
+    schema: &'a Value,
+    resolver: &Resolver<'a>,
+) -> Option<&'a Value> {
+    resolver.resolve_known_url(&metaschema_url(draft, schema)?, schema)
+}
+
+/// Get the vocabularies enabled by the metaschema named by the `$schema` of a
+/// schema, retrieving it if it isn't known. A metaschema that can't be found
+/// enables the vocabularies of the draft.
+fn find_vocabularies(
+    draft: schemas::Draft,
+    schema: &Value,
+    resolver: &Resolver,
+) -> Result<Option<Vec<schemas::Vocabulary>>, ValidationError> {
+    match metaschema_url(draft, schema).map(|url| resolver.resolve_url(&url, schema)) {
+        Some(Ok(metaschema)) => schemas::vocabularies_from_metaschema(draft, metaschema),
+        _ => Ok(None),
+    }
+}
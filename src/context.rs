@@ -2,9 +2,33 @@
 
 use serde_json::Value;
 
+use crate::schemas::Draft;
+
+/// A reference that was followed, with the schema it resolved to and the
+/// instance that schema was applied to.
+#[derive(Copy, Clone)]
+pub struct Reference<'a> {
+    pub reference: &'a str,
+    pub schema: &'a Value,
+    pub instance: &'a Value,
+}
+
 #[derive(Copy, Clone)]
 pub struct Context<'a> {
     pub x: &'a Value,
+    /// The schema resource that was entered to create this frame, if it isn't
+    /// `x` itself. This is used when `x` is only a placeholder carrying the
+    /// base URI of a resource that was reached through a reference.
+    pub resource: Option<&'a Value>,
+    /// The draft of the innermost schema resource that declares its own
+    /// `$schema`, if it differs from the one of the `Config`.
+    pub draft: Option<Draft>,
+    /// The reference that was followed to create this frame, if any.
+    pub reference: Option<Reference<'a>>,
+    /// The absolute base URL of this frame, if it was known when the frame
+    /// was pushed, so that it needn't be worked out again from the ids of
+    /// the frames below.
+    pub base_url: Option<&'a str>,
     pub parent: Option<&'a Context<'a>>,
 }
 
@@ -12,17 +36,46 @@ impl<'a> Context<'a> {
     pub fn new() -> Context<'static> {
         Context {
             x: &Value::Null,
+            resource: None,
+            draft: None,
+            reference: None,
+            base_url: None,
             parent: None,
         }
     }
 
     pub fn new_from(x: &'a Value) -> Context<'a> {
-        Context { x, parent: None }
+        Context {
+            x,
+            resource: None,
+            draft: None,
+            reference: None,
+            base_url: None,
+            parent: None,
+        }
     }
 
     pub fn push(&'a self, x: &'a Value) -> Context<'a> {
         Context {
             x,
+            resource: None,
+            draft: self.draft,
+            reference: None,
+            base_url: None,
+            parent: Some(self),
+        }
+    }
+
+    /// Push a frame for a schema resource reached through a reference, where
+    /// `x` is a placeholder whose `$id` is the absolute base URL of the
+    /// resource.
+    pub fn push_resource(&'a self, x: &'a Value, resource: &'a Value) -> Context<'a> {
+        Context {
+            x,
+            resource: Some(resource),
+            draft: self.draft,
+            reference: None,
+            base_url: x.get("$id").and_then(Value::as_str),
             parent: Some(self),
         }
     }
@@ -30,10 +83,64 @@ impl<'a> Context<'a> {
     pub fn replace(&'a self, x: &'a Value) -> Context<'a> {
         Context {
             x,
+            resource: None,
+            draft: self.draft,
+            reference: self.reference,
+            base_url: None,
             parent: self.parent,
         }
     }
 
+    /// Set the draft that this frame, and the frames pushed on top of it, are
+    /// evaluated with.
+    pub fn with_draft(mut self, draft: Option<Draft>) -> Context<'a> {
+        self.draft = draft;
+        self
+    }
+
+    /// Set the absolute base URL of this frame.
+    pub fn with_base_url(mut self, base_url: Option<&'a str>) -> Context<'a> {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Set the reference that was followed to create this frame.
+    pub fn with_reference(mut self, reference: Reference<'a>) -> Context<'a> {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// If `schema` is already being applied to `instance` through the
+    /// references followed to reach this frame, get the references followed
+    /// since, which applying it again would repeat forever.
+    pub fn reference_chain(&self, schema: &Value, instance: &Value) -> Option<Vec<&'a str>> {
+        let mut chain = Vec::new();
+        let mut ptr = Some(self);
+        while let Some(frame) = ptr {
+            if let Some(reference) = frame.reference {
+                if std::ptr::eq(reference.schema, schema)
+                    && std::ptr::eq(reference.instance, instance)
+                {
+                    chain.reverse();
+                    return Some(chain);
+                }
+                chain.push(reference.reference);
+            }
+            ptr = frame.parent;
+        }
+        None
+    }
+
+    /// Get the draft that this frame is evaluated with.
+    pub fn get_draft(&self, default: Draft) -> Draft {
+        self.draft.unwrap_or(default)
+    }
+
+    /// The schema resource that this frame represents.
+    pub fn get_resource(&self) -> &'a Value {
+        self.resource.unwrap_or(self.x)
+    }
+
     pub fn flatten(&'a self) -> Vec<Value> {
         let mut result = Vec::new();
         let mut ptr = self;
@@ -5,6 +5,8 @@ use std::iter::{empty, once};
 use itertools::Itertools;
 use serde_json::Value;
 
+use crate::policy::PolicyViolation;
+
 /// An error that can occur during validation.
 #[derive(Default, Debug, Clone)]
 pub struct ValidationError {
@@ -22,6 +24,10 @@ pub struct ValidationError {
 
     /// The path to the JSON schema fragment within the entire schema.
     pub schema_path: Vec<String>,
+
+    /// The reason that a [`crate::ResolutionPolicy`] refused to load a
+    /// document, if the error is due to that.
+    pub policy_violation: Option<PolicyViolation>,
 }
 
 impl StdError for ValidationError {}
@@ -3,11 +3,14 @@ use std::net::Ipv6Addr;
 use std::str::FromStr;
 
 use chrono::DateTime;
+use lazy_static::lazy_static;
 use regex::Regex;
 use url::{Host, Url};
 
 use crate::config::Config;
+use crate::uritemplate::UriTemplate;
 
+/// A function checking whether a string conforms to a `format`.
 pub type FormatChecker = fn(cfg: &Config, value: &str) -> bool;
 
 pub fn email(_cfg: &Config, value: &str) -> bool {
@@ -64,7 +67,28 @@ pub fn json_pointer(_cfg: &Config, value: &str) -> bool {
     value.parse::<json_pointer::JsonPointer<_, _>>().is_ok()
 }
 
-pub fn uri_template(_cfg: &Config, _value: &str) -> bool {
-    // It seems like pretty much anything can be a URI template
-    true
+/// A CSS 2.1 color: one of the 17 named colors, or a hex triplet.
+pub fn color(_cfg: &Config, value: &str) -> bool {
+    lazy_static! {
+        static ref HEX_COLOR: Regex = Regex::new(r"^#([0-9a-fA-F]{3}|[0-9a-fA-F]{6})$").unwrap();
+    }
+    const NAMED_COLORS: &[&str] = &[
+        "aqua", "black", "blue", "fuchsia", "gray", "green", "lime", "maroon", "navy", "olive",
+        "orange", "purple", "red", "silver", "teal", "white", "yellow",
+    ];
+    HEX_COLOR.is_match(value) || NAMED_COLORS.contains(&value.to_lowercase().as_str())
+}
+
+/// Base64 encoded data, as used by OpenAPI.
+pub fn byte(_cfg: &Config, value: &str) -> bool {
+    lazy_static! {
+        static ref BASE64: Regex =
+            Regex::new(r"^(?:[A-Za-z0-9+/]{4})*(?:[A-Za-z0-9+/]{2}==|[A-Za-z0-9+/]{3}=)?$")
+                .unwrap();
+    }
+    BASE64.is_match(value)
+}
+
+pub fn uri_template(_cfg: &Config, value: &str) -> bool {
+    UriTemplate::parse(value).is_ok()
 }
@@ -0,0 +1,453 @@
+//! Retrieving the documents of remote `$ref`s over HTTP(S), with an on-disk
+//! cache. This module is only built with the `http` feature.
+
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::error::ValidationError;
+use crate::policy::{PolicyViolation, ResolutionPolicy};
+use crate::retriever::Retriever;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 5;
+
+/// A [`Retriever`] that loads `http:` and `https:` documents from the
+/// network.
+///
+/// Requests time out after 30 seconds, or after 10 seconds if no connection
+/// can be made. With a cache directory, each document is kept there along
+/// with its `ETag` and `Last-Modified` headers, and later requests for it are
+/// conditional, so that an unchanged document isn't downloaded again. In
+/// offline mode, documents are only served from the cache, and no requests
+/// are made.
+///
+/// Up to 5 redirects are followed, and the URL of each is checked against
+/// the [`crate::ResolutionPolicy`] before it is requested. Documents larger
+/// than the policy's maximum size are refused without being downloaded in
+/// full.
+///
+/// ## Example:
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::json;
+/// # use jsonschema_valid::{Config, HttpRetriever};
+/// let schema = json!({"$ref": "https://json.schemastore.org/package.json"});
+/// let cfg = Config::from_schema(&schema, None)?.with_retriever(
+///     HttpRetriever::new()
+///         .with_timeout(std::time::Duration::from_secs(5))
+///         .with_cache_dir("schema-cache"),
+/// );
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpRetriever {
+    connect_timeout: Duration,
+    timeout: Duration,
+    cache_dir: Option<PathBuf>,
+    offline: bool,
+}
+
+impl Default for HttpRetriever {
+    fn default() -> Self {
+        HttpRetriever::new()
+    }
+}
+
+impl HttpRetriever {
+    /// Create a retriever with the default timeouts and no cache.
+    pub fn new() -> HttpRetriever {
+        HttpRetriever {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+            cache_dir: None,
+            offline: false,
+        }
+    }
+
+    /// Set the time allowed for connecting to a server.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Set the time allowed for a whole request, from connecting to reading
+    /// all of the response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Keep the retrieved documents in the given directory, which is created
+    /// if needed, and revalidate them with the server when they are needed
+    /// again.
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Set whether documents are only served from the cache, without making
+    /// any requests. Documents that aren't in the cache fail to load.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Get the path of the cache entry for a URL, if there is a cache.
+    fn cache_path(&self, url: &str) -> Option<PathBuf> {
+        self.cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}.json", fnv1a(url.as_bytes()))))
+    }
+
+    /// Get the cache entry for a URL, if it has one.
+    fn read_cache(&self, url: &str) -> Option<CacheEntry> {
+        let text = fs::read_to_string(self.cache_path(url)?).ok()?;
+        let entry: Value = serde_json::from_str(&text).ok()?;
+        // Different URLs may share a file name, so the entry is only used if
+        // it is for the same URL.
+        if entry.get("url").and_then(Value::as_str) != Some(url) {
+            return None;
+        }
+        let header = |name| entry.get(name).and_then(Value::as_str).map(String::from);
+        Some(CacheEntry {
+            etag: header("etag"),
+            last_modified: header("last_modified"),
+            document: entry.get("document")?.clone(),
+        })
+    }
+
+    /// Store the cache entry for a URL. The cache is only an optimization,
+    /// so failing to write it isn't an error.
+    fn write_cache(&self, url: &str, entry: &CacheEntry) {
+        let path = match self.cache_path(url) {
+            Some(path) => path,
+            None => return,
+        };
+        let text = json!({
+            "url": url,
+            "etag": entry.etag,
+            "last_modified": entry.last_modified,
+            "document": entry.document,
+        })
+        .to_string();
+        // The entry is written to a temporary file and then renamed, so that
+        // a concurrent reader never sees it half-written.
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&partial, text))
+            .and_then(|_| fs::rename(&partial, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+
+    fn fetch(
+        &self,
+        url: &str,
+        mut cached: Option<CacheEntry>,
+        policy: &ResolutionPolicy,
+    ) -> Result<Value, ValidationError> {
+        // Redirects are followed here rather than by the agent, so that the
+        // policy is checked for each of them.
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(self.connect_timeout)
+            .timeout(self.timeout)
+            .redirects(0)
+            .build();
+        let mut location = url.to_string();
+        let mut redirects = 0;
+        let response = loop {
+            let mut request = agent
+                .get(&location)
+                .set("Accept", "application/schema+json, application/json");
+            if let Some(cached) = &cached {
+                if let Some(etag) = &cached.etag {
+                    request = request.set("If-None-Match", etag);
+                }
+                if let Some(last_modified) = &cached.last_modified {
+                    request = request.set("If-Modified-Since", last_modified);
+                }
+            }
+            let response = request.call().map_err(|err| match err {
+                ureq::Error::Status(status, _) => error(&format!(
+                    "Can't retrieve {}: HTTP status {}",
+                    location, status
+                )),
+                ureq::Error::Transport(err) => {
+                    error(&format!("Can't retrieve {}: {}", location, err))
+                }
+            })?;
+            if response.status() == 304 {
+                if let Some(cached) = cached.take() {
+                    return Ok(cached.document);
+                }
+            }
+            match response.status() {
+                301 | 302 | 303 | 307 | 308 => (),
+                200..=299 => break response,
+                status => {
+                    return Err(error(&format!(
+                        "Can't retrieve {}: HTTP status {}",
+                        location, status
+                    )))
+                }
+            }
+            if redirects == MAX_REDIRECTS {
+                return Err(error(&format!(
+                    "Can't retrieve {}: more than {} redirects",
+                    url, MAX_REDIRECTS
+                )));
+            }
+            let next = response
+                .header("Location")
+                .and_then(|x| url::Url::parse(&location).ok()?.join(x).ok())
+                .ok_or_else(|| {
+                    error(&format!(
+                        "Can't retrieve {}: redirected without a valid Location",
+                        location
+                    ))
+                })?;
+            if !matches!(next.scheme(), "http" | "https") {
+                return Err(error(&format!(
+                    "Can't retrieve {}: redirected to {}, which isn't an HTTP URL",
+                    url, next
+                )));
+            }
+            policy
+                .check_location(&next)
+                .map_err(|violation| violation.to_error(next.as_str()))?;
+            location = next.into();
+            redirects += 1;
+        };
+        let header = |name| response.header(name).map(String::from);
+        let (etag, last_modified) = (header("ETag"), header("Last-Modified"));
+        let text = match policy.max_document_size() {
+            Some(max) => {
+                let length = header("Content-Length").and_then(|x| x.parse::<usize>().ok());
+                if let Some(size) = length.filter(|size| *size > max) {
+                    return Err(PolicyViolation::DocumentTooLarge { size, max }.to_error(url));
+                }
+                // Without a length, or with a wrong one, only one byte more
+                // than the maximum is read, to tell that it is too large.
+                let mut text = String::new();
+                response
+                    .into_reader()
+                    .take(max as u64 + 1)
+                    .read_to_string(&mut text)
+                    .map_err(|err| error(&format!("Can't read {}: {}", url, err)))?;
+                if text.len() > max {
+                    let size = text.len();
+                    return Err(PolicyViolation::DocumentTooLarge { size, max }.to_error(url));
+                }
+                text
+            }
+            None => response
+                .into_string()
+                .map_err(|err| error(&format!("Can't read {}: {}", url, err)))?,
+        };
+        let document: Value = serde_json::from_str(&text)
+            .map_err(|err| error(&format!("Invalid JSON at {}: {}", url, err)))?;
+        self.write_cache(
+            url,
+            &CacheEntry {
+                etag,
+                last_modified,
+                document: document.clone(),
+            },
+        );
+        Ok(document)
+    }
+}
+
+impl Retriever for HttpRetriever {
+    fn retrieve(&self, uri: &str) -> Result<Value, ValidationError> {
+        self.retrieve_with_policy(uri, &ResolutionPolicy::new())
+    }
+
+    fn retrieve_with_policy(
+        &self,
+        uri: &str,
+        policy: &ResolutionPolicy,
+    ) -> Result<Value, ValidationError> {
+        if !(uri.starts_with("http://") || uri.starts_with("https://")) {
+            return Err(error(&format!("Not an HTTP URL: {}", uri)));
+        }
+        let cached = self.read_cache(uri);
+        if self.offline {
+            return cached.map(|x| x.document).ok_or_else(|| {
+                error(&format!(
+                    "{} isn't in the cache, and retrieval is offline",
+                    uri
+                ))
+            });
+        }
+        self.fetch(uri, cached, policy)
+    }
+}
+
+/// A cached document, with the headers used to revalidate it.
+struct CacheEntry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    document: Value,
+}
+
+fn error(msg: &str) -> ValidationError {
+    ValidationError::new(msg, None, None)
+}
+
+/// The 64-bit FNV-1a hash, used to name cache entries. Unlike the standard
+/// library's hashers, it is the same across releases and platforms, so that
+/// the cache stays valid.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// A stand-in HTTP server, which serves a document with an `ETag` and
+    /// answers a matching `If-None-Match` with `304 Not Modified`. It
+    /// redirects to the document from another path, to itself from a third,
+    /// and to another host from a fourth. It records the status of each
+    /// response.
+    fn serve(document: Value) -> (String, Arc<Mutex<Vec<u16>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}/", listener.local_addr().unwrap());
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let recorded = statuses.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut if_none_match = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(": ") {
+                        if name.eq_ignore_ascii_case("If-None-Match") {
+                            if_none_match = Some(value.to_string());
+                        }
+                    }
+                }
+                let body = document.to_string();
+                let path = request_line.split(' ').nth(1).unwrap_or("");
+                let (status, location, body) = match path {
+                    "/redirect.json" => (301, "/schema.json", ""),
+                    "/loop.json" => (302, "/loop.json", ""),
+                    "/away.json" => (307, "http://denied.example.com/schema.json", ""),
+                    "/schema.json" if if_none_match.as_deref() == Some("\"v1\"") => (304, "", ""),
+                    "/schema.json" => (200, "", body.as_str()),
+                    _ => (404, "", ""),
+                };
+                recorded.lock().unwrap().push(status);
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nETag: \"v1\"\r\nLocation: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    location,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (base, statuses)
+    }
+
+    #[test]
+    fn test_http_retriever() {
+        let document = json!({"type": "integer"});
+        let (base, statuses) = serve(document.clone());
+        let url = format!("{}schema.json", base);
+        let cache_dir =
+            std::env::temp_dir().join(format!("jsonschema-valid-http-{}", std::process::id()));
+
+        let retriever = HttpRetriever::new()
+            .with_timeout(Duration::from_secs(5))
+            .with_cache_dir(&cache_dir);
+        assert!(retriever.clone().offline(true).retrieve(&url).is_err());
+        assert_eq!(retriever.retrieve(&url).unwrap(), document);
+        // The second request is revalidated, and served from the cache.
+        assert_eq!(retriever.retrieve(&url).unwrap(), document);
+        assert_eq!(
+            retriever.clone().offline(true).retrieve(&url).unwrap(),
+            document
+        );
+        assert_eq!(*statuses.lock().unwrap(), vec![200, 304]);
+
+        let err = retriever
+            .retrieve(&format!("{}missing.json", base))
+            .unwrap_err();
+        assert!(err.msg.contains("HTTP status 404"));
+
+        // Redirects are followed, up to a limit, unless the policy refuses
+        // where they lead.
+        let retriever = HttpRetriever::new().with_timeout(Duration::from_secs(5));
+        assert_eq!(
+            retriever
+                .retrieve(&format!("{}redirect.json", base))
+                .unwrap(),
+            document
+        );
+        let err = retriever
+            .retrieve(&format!("{}loop.json", base))
+            .unwrap_err();
+        assert!(err.msg.contains("more than 5 redirects"));
+        let err = retriever
+            .retrieve_with_policy(
+                &format!("{}away.json", base),
+                &ResolutionPolicy::new().deny_host("denied.example.com"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.policy_violation,
+            Some(PolicyViolation::HostDenied(
+                "denied.example.com".to_string()
+            ))
+        );
+
+        let err = retriever
+            .retrieve_with_policy(&url, &ResolutionPolicy::new().with_max_document_size(5))
+            .unwrap_err();
+        assert_eq!(
+            err.policy_violation,
+            Some(PolicyViolation::DocumentTooLarge { size: 18, max: 5 })
+        );
+        assert!(retriever.retrieve("ftp://example.com/x.json").is_err());
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_http_ref() {
+        let (base, _) = serve(json!({"type": "integer"}));
+        let schema = json!({"$ref": format!("{}schema.json", base)});
+        let cfg = crate::Config::from_schema(&schema, None)
+            .unwrap()
+            .with_retriever(HttpRetriever::new());
+        assert!(cfg.validate(&json!(1)).is_ok());
+        assert!(cfg.validate(&json!("one")).is_err());
+    }
+}
@@ -0,0 +1,424 @@
+//! Extracting the links of an instance described by
+//! [JSON Hyper-Schema](https://json-schema.org/draft/2019-09/json-schema-hypermedia).
+
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::ValidationError;
+use crate::resolver::DOCUMENT_PROTOCOL;
+use crate::uritemplate::UriTemplate;
+use crate::validators;
+
+/// A link of an instance, resolved from a Link Description Object in the
+/// `links` of a schema that applies to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// A JSON pointer to the part of the instance that the link is attached
+    /// to, which is its context.
+    pub context_pointer: String,
+    /// The relation types of the link, from `rel`.
+    pub rel: Vec<String>,
+    /// The target of the link: its `href` template expanded with the data of
+    /// the instance, and resolved against the base URI of the instance.
+    pub target_uri: String,
+    /// The `targetSchema` describing the target of the link, if any.
+    pub target_schema: Option<Value>,
+    /// The Link Description Object itself, for its other keywords, such as
+    /// `title` or `submissionSchema`.
+    pub description: Value,
+}
+
+/// The state of finding the links of an instance.
+struct LinkFinder<'i> {
+    root: &'i Value,
+    path: Vec<String>,
+    links: Vec<Link>,
+    error: Option<ValidationError>,
+}
+
+/// Find the links of an instance, whose initial base URI is `base`.
+pub(crate) fn find_links<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    base: url::Url,
+) -> Result<Vec<Link>, ValidationError> {
+    let mut finder = LinkFinder {
+        root: instance,
+        path: Vec::new(),
+        links: Vec::new(),
+        error: None,
+    };
+    visit(
+        cfg,
+        instance,
+        cfg.get_schema(),
+        Context::new_from(cfg.get_schema()),
+        &base,
+        &mut finder,
+    );
+    match finder.error {
+        Some(err) => Err(err),
+        None => Ok(finder.links),
+    }
+}
+
+/// Find the links of the instance at the finder's path, from the schemas that
+/// apply to it, and then those of its children.
+fn visit<'s, 'i>(
+    cfg: &'s Config<'s>,
+    instance: &'i Value,
+    schema: &'s Value,
+    ref_context: Context<'s>,
+    base: &url::Url,
+    finder: &mut LinkFinder<'i>,
+) {
+    // A `base` in any of the schemas applying to the instance sets the base
+    // URI of the instance, for all of their links and subschemas.
+    let mut base = base.clone();
+    validators::visit_evaluating_schemas(
+        cfg,
+        instance,
+        schema,
+        ref_context,
+        &mut |_, object, _| {
+            if let Some(Value::String(template)) = object.get("base") {
+                match finder
+                    .expand(template, None, instance)
+                    .and_then(|x| x.ok_or_else(|| missing(template)))
+                    .and_then(|x| Ok(base.join(&x)?))
+                {
+                    Ok(url) => base = url,
+                    Err(err) => finder.fail(err),
+                }
+            }
+        },
+    );
+
+    validators::visit_evaluating_schemas(
+        cfg,
+        instance,
+        schema,
+        ref_context,
+        &mut |cfg, object, ref_context| {
+            if let Some(Value::Array(links)) = object.get("links") {
+                for ldo in links {
+                    match finder.resolve_link(ldo, instance, &base) {
+                        Ok(Some(link)) => finder.links.push(link),
+                        Ok(None) => {}
+                        Err(err) => finder.fail(err),
+                    }
+                }
+            }
+            let draft = ref_context.get_draft(cfg.draft);
+            for (segment, child, subschema) in
+                child_schemas(draft.get_draft_number(), object, instance)
+            {
+                finder.path.push(segment);
+                visit(cfg, child, subschema, ref_context, &base, finder);
+                finder.path.pop();
+            }
+        },
+    );
+}
+
+fn missing(template: &str) -> ValidationError {
+    ValidationError::new(
+        &format!(
+            "Missing a required variable of the URI template \"{}\"",
+            template
+        ),
+        None,
+        None,
+    )
+}
+
+/// Find the subschemas that apply to the children of an instance, along with
+/// the path segment of each child.
+fn child_schemas<'s, 'i>(
+    draft_number: u8,
+    object: &'s Map<String, Value>,
+    instance: &'i Value,
+) -> Vec<(String, &'i Value, &'s Value)> {
+    let mut children = Vec::new();
+    match instance {
+        Value::Object(instance_object) => {
+            let properties = object.get("properties").and_then(Value::as_object);
+            let patterns: Vec<(regex::Regex, &Value)> = object
+                .get("patternProperties")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .filter_map(|(k, v)| Some((regex::Regex::new(k).ok()?, v)))
+                .collect();
+            for (key, value) in instance_object {
+                let mut additional = true;
+                if let Some(subschema) = properties.and_then(|x| x.get(key)) {
+                    children.push((key.clone(), value, subschema));
+                    additional = false;
+                }
+                for (re, subschema) in &patterns {
+                    if re.is_match(key) {
+                        children.push((key.clone(), value, *subschema));
+                        additional = false;
+                    }
+                }
+                if let (true, Some(subschema)) = (additional, object.get("additionalProperties")) {
+                    children.push((key.clone(), value, subschema));
+                }
+            }
+        }
+        Value::Array(items) => {
+            let (prefix, rest): (&[Value], Option<&Value>) = if draft_number >= 9 {
+                match object.get("prefixItems") {
+                    Some(Value::Array(prefix)) => (prefix, object.get("items")),
+                    _ => (&[], object.get("items")),
+                }
+            } else {
+                match object.get("items") {
+                    Some(Value::Array(prefix)) => (prefix, object.get("additionalItems")),
+                    items => (&[], items),
+                }
+            };
+            for (i, item) in items.iter().enumerate() {
+                if let Some(subschema) = prefix.get(i).or(rest) {
+                    children.push((i.to_string(), item, subschema));
+                }
+            }
+        }
+        _ => {}
+    }
+    children
+}
+
+impl<'i> LinkFinder<'i> {
+    fn fail(&mut self, err: ValidationError) {
+        if self.error.is_none() {
+            self.error = Some(err);
+        }
+    }
+
+    /// Resolve a Link Description Object, or return `None` if the link can't
+    /// be used, as one of its `templateRequired` variables is missing.
+    fn resolve_link(
+        &self,
+        ldo: &Value,
+        instance: &'i Value,
+        base: &url::Url,
+    ) -> Result<Option<Link>, ValidationError> {
+        let href = match ldo.get("href") {
+            Some(Value::String(href)) => href,
+            _ => {
+                return Err(ValidationError::new(
+                    "A link description must have an \"href\"",
+                    None,
+                    Some(ldo),
+                ))
+            }
+        };
+        let target = match self.expand(href, Some(ldo), instance)? {
+            Some(target) => base.join(&target)?,
+            None => return Ok(None),
+        };
+        let rel = match ldo.get("rel") {
+            Some(Value::String(rel)) => vec![rel.clone()],
+            Some(Value::Array(rels)) => rels
+                .iter()
+                .filter_map(|x| x.as_str().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(Some(Link {
+            context_pointer: self
+                .path
+                .iter()
+                .map(|x| format!("/{}", x.replace('~', "~0").replace('/', "~1")))
+                .collect(),
+            rel,
+            target_uri: without_document_protocol(&target),
+            target_schema: ldo.get("targetSchema").cloned(),
+            description: ldo.clone(),
+        }))
+    }
+
+    /// Expand a URI template with the data of the instance. Each variable is
+    /// looked up with the link's `templatePointers`, or else as a property of
+    /// the instance. Returns `None` if any of the link's `templateRequired`
+    /// variables is missing.
+    fn expand(
+        &self,
+        template: &str,
+        ldo: Option<&Value>,
+        instance: &'i Value,
+    ) -> Result<Option<String>, ValidationError> {
+        let template = UriTemplate::parse(template).map_err(|msg| {
+            ValidationError::new(&msg, None, Some(&Value::String(template.to_string())))
+        })?;
+        let pointers = ldo
+            .and_then(|x| x.get("templatePointers"))
+            .and_then(Value::as_object);
+        let lookup = |name: &str| -> Option<&'i Value> {
+            let name = percent_encoding::percent_decode_str(name).decode_utf8_lossy();
+            match pointers
+                .and_then(|x| x.get(name.as_ref()))
+                .and_then(Value::as_str)
+            {
+                Some(pointer) => self.resolve_pointer(pointer, instance),
+                None => instance.get(name.as_ref()),
+            }
+            .filter(|x| !x.is_null())
+        };
+        let required = ldo
+            .and_then(|x| x.get("templateRequired"))
+            .and_then(Value::as_array);
+        if required
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .any(|name| lookup(name).is_none())
+        {
+            return Ok(None);
+        }
+        Ok(Some(template.expand(|name| lookup(name).cloned())))
+    }
+
+    /// Resolve a JSON pointer against the whole instance, or a relative JSON
+    /// pointer against the part of the instance the link is attached to.
+    fn resolve_pointer(&self, pointer: &str, instance: &'i Value) -> Option<&'i Value> {
+        if pointer.is_empty() || pointer.starts_with('/') {
+            return self.root.pointer(pointer);
+        }
+        let digits = pointer
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(pointer.len());
+        let up: usize = pointer[..digits].parse().ok()?;
+        let rest = &pointer[digits..];
+        if up == 0 {
+            return instance.pointer(rest);
+        }
+        let depth = self.path.len().checked_sub(up)?;
+        let ancestor: String = self.path[..depth]
+            .iter()
+            .map(|x| format!("/{}", x.replace('~', "~0").replace('/', "~1")))
+            .collect();
+        self.root.pointer(&ancestor)?.pointer(rest)
+    }
+}
+
+/// Write a URL resolved against the placeholder base of a document without a
+/// URI as an absolute path.
+fn without_document_protocol(url: &url::Url) -> String {
+    let url = url.to_string();
+    match url.strip_prefix(DOCUMENT_PROTOCOL) {
+        Some(path) => format!("/{}", path),
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn links(schema: &Value, instance: &Value, url: Option<&str>) -> Vec<(String, String)> {
+        let cfg = Config::from_schema(schema, None).unwrap();
+        cfg.get_links(instance, url)
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.context_pointer, x.target_uri))
+            .collect()
+    }
+
+    #[test]
+    fn test_links() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2019-09/hyper-schema",
+            "$id": "https://example.com/schemas/order",
+            "base": "https://api.example.com/orders/{id}/",
+            "links": [
+                {"rel": ["self", "canonical"], "href": ""},
+                {"rel": "customer", "href": "/customers/{customerId}",
+                 "templateRequired": ["customerId"]},
+            ],
+            "properties": {
+                "items": {
+                    "items": {"$ref": "#/$defs/item"}
+                }
+            },
+            "$defs": {
+                "item": {
+                    "allOf": [{
+                        "links": [{
+                            "rel": "product",
+                            "href": "products/{order,sku}",
+                            "templatePointers": {"order": "2/id"}
+                        }]
+                    }]
+                }
+            }
+        });
+        let instance = json!({
+            "id": 7,
+            "items": [{"sku": "a b"}, {"sku": "c"}]
+        });
+        assert_eq!(
+            links(&schema, &instance, None),
+            vec![
+                (
+                    "".to_string(),
+                    "https://api.example.com/orders/7/".to_string()
+                ),
+                (
+                    "/items/0".to_string(),
+                    "https://api.example.com/orders/7/products/7,a%20b".to_string()
+                ),
+                (
+                    "/items/1".to_string(),
+                    "https://api.example.com/orders/7/products/7,c".to_string()
+                ),
+            ]
+        );
+
+        let instance = json!({"id": 7, "customerId": "x/y"});
+        assert_eq!(
+            links(&schema, &instance, None)[1],
+            (
+                "".to_string(),
+                "https://api.example.com/customers/x%2Fy".to_string()
+            )
+        );
+
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        let link = &cfg.get_links(&instance, None).unwrap()[0];
+        assert_eq!(link.rel, vec!["self", "canonical"]);
+        assert_eq!(link.target_schema, None);
+    }
+
+    #[test]
+    fn test_links_relative_to_instance() {
+        let schema = json!({
+            "links": [{"rel": "self", "href": "things/{id}", "targetSchema": {"type": "object"}}]
+        });
+        assert_eq!(
+            links(&schema, &json!({"id": 1}), Some("https://example.com/api/")),
+            vec![(
+                "".to_string(),
+                "https://example.com/api/things/1".to_string()
+            )]
+        );
+        assert_eq!(
+            links(&schema, &json!({"id": 1}), None),
+            vec![("".to_string(), "/things/1".to_string())]
+        );
+
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        assert!(cfg.get_links(&json!({}), Some("not a url")).is_err());
+        let link = &cfg.get_links(&json!({}), None).unwrap()[0];
+        assert_eq!(link.target_uri, "/things/");
+        assert_eq!(link.target_schema, Some(json!({"type": "object"})));
+
+        let schema = json!({"links": [{"rel": "self", "href": "{"}]});
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        assert!(cfg.get_links(&json!({}), None).is_err());
+    }
+}
@@ -0,0 +1,790 @@
+//! Validation against [JSON Type Definition](https://www.rfc-editor.org/rfc/rfc8927)
+//! schemas.
+
+use std::collections::HashSet;
+
+use chrono::DateTime;
+use serde_json::{Map, Value};
+
+use crate::error::{ErrorIterator, ValidationError};
+
+/// The maximum number of references followed while validating, unless set
+/// with [`Jtd::with_max_depth`].
+const DEFAULT_MAX_DEPTH: usize = 64;
+
+const TYPES: &[&str] = &[
+    "boolean",
+    "float32",
+    "float64",
+    "int8",
+    "uint8",
+    "int16",
+    "uint16",
+    "int32",
+    "uint32",
+    "string",
+    "timestamp",
+];
+
+/// The keywords that make up each form of schema.
+const FORMS: &[&[&str]] = &[
+    &["ref"],
+    &["type"],
+    &["enum"],
+    &["elements"],
+    &["properties", "optionalProperties", "additionalProperties"],
+    &["values"],
+    &["discriminator", "mapping"],
+];
+
+/// A [JSON Type Definition](https://www.rfc-editor.org/rfc/rfc8927) schema,
+/// against which JSON instances can be validated.
+///
+/// Errors are reported as [`ValidationError`]s, whose `instance_path` and
+/// `schema_path` give the RFC's standard error indicators. These can also be
+/// converted to an [`ErrorIndicator`].
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::json;
+/// use jsonschema_valid::{ErrorIndicator, Jtd};
+///
+/// let schema = json!({
+///     "properties": {
+///         "name": { "type": "string" },
+///         "age": { "type": "uint8" }
+///     }
+/// });
+/// let jtd = Jtd::from_schema(&schema)?;
+/// assert!(jtd.validate(&json!({"name": "Rex", "age": 3})).is_ok());
+///
+/// let instance = json!({"name": "Rex", "age": -3});
+/// let errors: Vec<ErrorIndicator> = jtd
+///     .validate(&instance)
+///     .unwrap_err()
+///     .map(|x| ErrorIndicator::from(&x))
+///     .collect();
+/// assert_eq!(errors[0].instance_path, "/age");
+/// assert_eq!(errors[0].schema_path, "/properties/age/type");
+/// # Ok(()) }
+/// ```
+pub struct Jtd<'a> {
+    schema: &'a Value,
+    max_depth: usize,
+}
+
+impl<'a> Jtd<'a> {
+    /// Create a new Jtd object from a schema, which must be a correct JSON
+    /// Type Definition schema.
+    pub fn from_schema(schema: &'a Value) -> Result<Jtd<'a>, ValidationError> {
+        let definitions = match schema.get("definitions") {
+            Some(Value::Object(definitions)) => Some(definitions),
+            Some(definitions) => {
+                return Err(schema_error(
+                    "\"definitions\" must be an object",
+                    definitions,
+                    &["definitions".to_string()],
+                ))
+            }
+            None => None,
+        };
+        let mut path = Vec::new();
+        check_schema(schema, definitions, true, &mut path)?;
+        for (name, definition) in definitions.into_iter().flatten() {
+            let mut path = vec!["definitions".to_string(), name.clone()];
+            check_schema(definition, definitions, false, &mut path)?;
+        }
+        Ok(Jtd {
+            schema,
+            max_depth: DEFAULT_MAX_DEPTH,
+        })
+    }
+
+    /// Set the maximum number of references to follow while validating an
+    /// instance, beyond which validation fails. This stops schemas that refer
+    /// to themselves without bound, such as `{"ref": "loop"}` where the
+    /// definition of `loop` is the same, from recursing forever.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Validate a JSON instance against the schema.
+    pub fn validate(&self, instance: &'a Value) -> Result<(), ErrorIterator<'a>> {
+        let mut validation = Validation {
+            definitions: self.schema.get("definitions"),
+            max_depth: self.max_depth,
+            instance_path: Vec::new(),
+            schema_path: Vec::new(),
+            errors: Vec::new(),
+        };
+        validation.validate(instance, self.schema, None, 0);
+        if validation.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(validation.errors.into_iter()))
+        }
+    }
+}
+
+/// An error indicator, as defined by RFC 8927: a pair of JSON pointers to the
+/// part of the instance that was rejected, and to the part of the schema that
+/// rejected it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ErrorIndicator {
+    /// A JSON pointer to the rejected part of the instance.
+    pub instance_path: String,
+    /// A JSON pointer to the part of the schema that rejected it.
+    pub schema_path: String,
+}
+
+impl From<&ValidationError> for ErrorIndicator {
+    fn from(err: &ValidationError) -> ErrorIndicator {
+        ErrorIndicator {
+            instance_path: to_pointer(&err.instance_path),
+            schema_path: to_pointer(&err.schema_path),
+        }
+    }
+}
+
+/// Convert a path, innermost segment first, to a JSON pointer.
+fn to_pointer(path: &[String]) -> String {
+    path.iter()
+        .rev()
+        .map(|x| format!("/{}", x.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn schema_error(msg: &str, schema: &Value, path: &[String]) -> ValidationError {
+    path.iter().rev().fold(
+        ValidationError::new(msg, None, Some(schema)),
+        |err, segment| err.schema_ctx(segment.clone()),
+    )
+}
+
+/// Check that a schema is correct, as described in section 2.2 of RFC 8927.
+fn check_schema(
+    schema: &Value,
+    definitions: Option<&Map<String, Value>>,
+    is_root: bool,
+    path: &mut Vec<String>,
+) -> Result<(), ValidationError> {
+    let object = match schema {
+        Value::Object(object) => object,
+        _ => return Err(schema_error("A schema must be an object", schema, path)),
+    };
+    let mut form: Option<usize> = None;
+    for key in object.keys() {
+        let key_form = FORMS.iter().position(|x| x.contains(&key.as_str()));
+        match (key.as_str(), key_form) {
+            ("definitions", _) if is_root => {}
+            ("nullable", _) | ("metadata", _) => {}
+            (_, Some(key_form)) => match form {
+                Some(form) if form != key_form => {
+                    return Err(schema_error(
+                        &format!(
+                            "\"{}\" can't be used along with \"{}\"",
+                            key, FORMS[form][0]
+                        ),
+                        schema,
+                        path,
+                    ))
+                }
+                _ => form = Some(key_form),
+            },
+            _ => {
+                return Err(schema_error(
+                    &format!("Unknown keyword \"{}\"", key),
+                    schema,
+                    path,
+                ))
+            }
+        }
+    }
+    if !matches!(object.get("nullable"), None | Some(Value::Bool(_))) {
+        return Err(schema_error("\"nullable\" must be a boolean", schema, path));
+    }
+    if !matches!(object.get("metadata"), None | Some(Value::Object(_))) {
+        return Err(schema_error("\"metadata\" must be an object", schema, path));
+    }
+
+    if let Some(reference) = object.get("ref") {
+        let name = reference.as_str().unwrap_or_default();
+        if definitions.and_then(|x| x.get(name)).is_none() {
+            return Err(schema_error(
+                &format!("\"ref\" refers to the unknown definition {}", reference),
+                schema,
+                path,
+            ));
+        }
+    }
+    if let Some(typename) = object.get("type") {
+        if !matches!(typename.as_str(), Some(x) if TYPES.contains(&x)) {
+            return Err(schema_error(
+                &format!("Unknown type {}", typename),
+                schema,
+                path,
+            ));
+        }
+    }
+    if let Some(values) = object.get("enum") {
+        let mut seen = HashSet::new();
+        let valid = match values {
+            Value::Array(values) => {
+                !values.is_empty()
+                    && values
+                        .iter()
+                        .all(|x| matches!(x.as_str(), Some(x) if seen.insert(x)))
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(schema_error(
+                "\"enum\" must be a non-empty array of distinct strings",
+                schema,
+                path,
+            ));
+        }
+    }
+    for key in &["elements", "values"] {
+        if let Some(subschema) = object.get(*key) {
+            path.push(key.to_string());
+            check_schema(subschema, definitions, false, path)?;
+            path.pop();
+        }
+    }
+    match form.map(|x| FORMS[x][0]) {
+        Some("properties") => check_properties(schema, object, definitions, path),
+        Some("discriminator") => check_discriminator(schema, object, definitions, path),
+        _ => Ok(()),
+    }
+}
+
+fn check_properties(
+    schema: &Value,
+    object: &Map<String, Value>,
+    definitions: Option<&Map<String, Value>>,
+    path: &mut Vec<String>,
+) -> Result<(), ValidationError> {
+    if !object.contains_key("properties") && !object.contains_key("optionalProperties") {
+        return Err(schema_error(
+            "\"additionalProperties\" requires \"properties\" or \"optionalProperties\"",
+            schema,
+            path,
+        ));
+    }
+    if !matches!(
+        object.get("additionalProperties"),
+        None | Some(Value::Bool(_))
+    ) {
+        return Err(schema_error(
+            "\"additionalProperties\" must be a boolean",
+            schema,
+            path,
+        ));
+    }
+    let mut seen = HashSet::new();
+    for key in &["properties", "optionalProperties"] {
+        match object.get(*key) {
+            Some(Value::Object(properties)) => {
+                path.push(key.to_string());
+                for (name, subschema) in properties {
+                    if !seen.insert(name) {
+                        return Err(schema_error(
+                            &format!("\"{}\" can't be both required and optional", name),
+                            subschema,
+                            path,
+                        ));
+                    }
+                    path.push(name.clone());
+                    check_schema(subschema, definitions, false, path)?;
+                    path.pop();
+                }
+                path.pop();
+            }
+            Some(properties) => {
+                return Err(schema_error(
+                    &format!("\"{}\" must be an object", key),
+                    properties,
+                    path,
+                ))
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_discriminator(
+    schema: &Value,
+    object: &Map<String, Value>,
+    definitions: Option<&Map<String, Value>>,
+    path: &mut Vec<String>,
+) -> Result<(), ValidationError> {
+    let tag = match object.get("discriminator") {
+        Some(Value::String(tag)) => tag,
+        _ => {
+            return Err(schema_error(
+                "\"discriminator\" must be a string",
+                schema,
+                path,
+            ))
+        }
+    };
+    let mapping = match object.get("mapping") {
+        Some(Value::Object(mapping)) => mapping,
+        _ => {
+            return Err(schema_error(
+                "\"discriminator\" requires a \"mapping\" object",
+                schema,
+                path,
+            ))
+        }
+    };
+    path.push("mapping".to_string());
+    for (name, subschema) in mapping {
+        path.push(name.clone());
+        check_schema(subschema, definitions, false, path)?;
+        let is_properties = ["properties", "optionalProperties"]
+            .iter()
+            .any(|x| subschema.get(x).is_some());
+        if !is_properties {
+            return Err(schema_error(
+                "The schemas of a \"mapping\" must be of the properties form",
+                subschema,
+                path,
+            ));
+        }
+        if subschema.get("nullable") == Some(&Value::Bool(true)) {
+            return Err(schema_error(
+                "The schemas of a \"mapping\" can't be nullable",
+                subschema,
+                path,
+            ));
+        }
+        if ["properties", "optionalProperties"]
+            .iter()
+            .any(|x| subschema.get(x).and_then(|x| x.get(tag)).is_some())
+        {
+            return Err(schema_error(
+                &format!(
+                    "The schemas of a \"mapping\" can't have the discriminator \"{}\" as a property",
+                    tag
+                ),
+                subschema,
+                path,
+            ));
+        }
+        path.pop();
+    }
+    path.pop();
+    Ok(())
+}
+
+/// The state of validating an instance, as described in section 3.3 of RFC
+/// 8927.
+struct Validation<'a> {
+    definitions: Option<&'a Value>,
+    max_depth: usize,
+    instance_path: Vec<String>,
+    schema_path: Vec<String>,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> Validation<'a> {
+    /// Report an error at the current instance path, and the current schema
+    /// path followed by `keyword`.
+    fn error(&mut self, msg: String, instance: &Value, schema: &Value, keyword: &[&str]) {
+        let mut err = ValidationError::new(&msg, Some(instance), Some(schema));
+        err.instance_path = self.instance_path.iter().rev().cloned().collect();
+        err.schema_path = keyword
+            .iter()
+            .rev()
+            .map(|x| x.to_string())
+            .chain(self.schema_path.iter().rev().cloned())
+            .collect();
+        self.errors.push(err);
+    }
+
+    /// Validate an instance against a schema. If the schema is the mapping of
+    /// a discriminator, `tag` is its name, which is allowed as an additional
+    /// property.
+    fn validate(&mut self, instance: &Value, schema: &'a Value, tag: Option<&str>, depth: usize) {
+        if instance.is_null() && schema.get("nullable") == Some(&Value::Bool(true)) {
+            return;
+        }
+        if let Some(reference) = schema.get("ref").and_then(Value::as_str) {
+            if depth >= self.max_depth {
+                self.error(
+                    format!("Maximum depth of {} references exceeded", self.max_depth),
+                    instance,
+                    schema,
+                    &["ref"],
+                );
+                return;
+            }
+            let definition = &self.definitions.unwrap()[reference];
+            let schema_path = std::mem::replace(
+                &mut self.schema_path,
+                vec!["definitions".to_string(), reference.to_string()],
+            );
+            self.validate(instance, definition, None, depth + 1);
+            self.schema_path = schema_path;
+        } else if let Some(typename) = schema.get("type").and_then(Value::as_str) {
+            if !has_type(instance, typename) {
+                self.error(
+                    format!("Expected a value of type {}", typename),
+                    instance,
+                    schema,
+                    &["type"],
+                );
+            }
+        } else if let Some(Value::Array(values)) = schema.get("enum") {
+            if !values.contains(instance) {
+                self.error(
+                    "Value is not in enum.".to_string(),
+                    instance,
+                    schema,
+                    &["enum"],
+                );
+            }
+        } else if let Some(subschema) = schema.get("elements") {
+            match instance {
+                Value::Array(items) => {
+                    self.schema_path.push("elements".to_string());
+                    for (i, item) in items.iter().enumerate() {
+                        self.instance_path.push(i.to_string());
+                        self.validate(item, subschema, None, depth);
+                        self.instance_path.pop();
+                    }
+                    self.schema_path.pop();
+                }
+                _ => self.error(
+                    "Expected an array".to_string(),
+                    instance,
+                    schema,
+                    &["elements"],
+                ),
+            }
+        } else if schema.get("properties").is_some() || schema.get("optionalProperties").is_some() {
+            self.validate_properties(instance, schema, tag, depth);
+        } else if let Some(subschema) = schema.get("values") {
+            match instance {
+                Value::Object(object) => {
+                    self.schema_path.push("values".to_string());
+                    for (key, value) in object {
+                        self.instance_path.push(key.clone());
+                        self.validate(value, subschema, None, depth);
+                        self.instance_path.pop();
+                    }
+                    self.schema_path.pop();
+                }
+                _ => self.error(
+                    "Expected an object".to_string(),
+                    instance,
+                    schema,
+                    &["values"],
+                ),
+            }
+        } else if let Some(Value::String(tag)) = schema.get("discriminator") {
+            self.validate_discriminator(instance, schema, tag, depth);
+        }
+    }
+
+    fn validate_properties(
+        &mut self,
+        instance: &Value,
+        schema: &'a Value,
+        tag: Option<&str>,
+        depth: usize,
+    ) {
+        let object = match instance {
+            Value::Object(object) => object,
+            _ => {
+                let keyword = if schema.get("properties").is_some() {
+                    "properties"
+                } else {
+                    "optionalProperties"
+                };
+                self.error(
+                    "Expected an object".to_string(),
+                    instance,
+                    schema,
+                    &[keyword],
+                );
+                return;
+            }
+        };
+        if let Some(Value::Object(properties)) = schema.get("properties") {
+            for (key, subschema) in properties {
+                match object.get(key) {
+                    Some(value) => {
+                        self.schema_path.push("properties".to_string());
+                        self.schema_path.push(key.clone());
+                        self.instance_path.push(key.clone());
+                        self.validate(value, subschema, None, depth);
+                        self.instance_path.pop();
+                        self.schema_path.pop();
+                        self.schema_path.pop();
+                    }
+                    None => self.error(
+                        format!("Missing required property \"{}\"", key),
+                        instance,
+                        schema,
+                        &["properties", key.as_str()],
+                    ),
+                }
+            }
+        }
+        if let Some(Value::Object(properties)) = schema.get("optionalProperties") {
+            for (key, subschema) in properties {
+                if let Some(value) = object.get(key) {
+                    self.schema_path.push("optionalProperties".to_string());
+                    self.schema_path.push(key.clone());
+                    self.instance_path.push(key.clone());
+                    self.validate(value, subschema, None, depth);
+                    self.instance_path.pop();
+                    self.schema_path.pop();
+                    self.schema_path.pop();
+                }
+            }
+        }
+        if schema.get("additionalProperties") != Some(&Value::Bool(true)) {
+            for (key, value) in object {
+                let known = ["properties", "optionalProperties"]
+                    .iter()
+                    .any(|x| schema.get(x).and_then(|x| x.get(key)).is_some());
+                if !known && tag != Some(key.as_str()) {
+                    self.instance_path.push(key.clone());
+                    self.error(
+                        format!("Additional property \"{}\" is not allowed", key),
+                        value,
+                        schema,
+                        &[],
+                    );
+                    self.instance_path.pop();
+                }
+            }
+        }
+    }
+
+    fn validate_discriminator(
+        &mut self,
+        instance: &Value,
+        schema: &'a Value,
+        tag: &str,
+        depth: usize,
+    ) {
+        let object = match instance {
+            Value::Object(object) => object,
+            _ => {
+                self.error(
+                    "Expected an object".to_string(),
+                    instance,
+                    schema,
+                    &["discriminator"],
+                );
+                return;
+            }
+        };
+        let value = match object.get(tag) {
+            Some(Value::String(value)) => value,
+            Some(value) => {
+                self.instance_path.push(tag.to_string());
+                self.error(
+                    format!("Discriminator property \"{}\" must be a string", tag),
+                    value,
+                    schema,
+                    &["discriminator"],
+                );
+                self.instance_path.pop();
+                return;
+            }
+            None => {
+                self.error(
+                    format!("Discriminator property \"{}\" is missing", tag),
+                    instance,
+                    schema,
+                    &["discriminator"],
+                );
+                return;
+            }
+        };
+        match schema.get("mapping").and_then(|x| x.get(value)) {
+            Some(subschema) => {
+                self.schema_path.push("mapping".to_string());
+                self.schema_path.push(value.clone());
+                self.validate(instance, subschema, Some(tag), depth);
+                self.schema_path.pop();
+                self.schema_path.pop();
+            }
+            None => {
+                self.instance_path.push(tag.to_string());
+                self.error(
+                    format!("Discriminator value \"{}\" is not in the mapping", value),
+                    &object[tag],
+                    schema,
+                    &["mapping"],
+                );
+                self.instance_path.pop();
+            }
+        }
+    }
+}
+
+/// Whether an instance has one of the types of the `type` form.
+fn has_type(instance: &Value, typename: &str) -> bool {
+    let range = match typename {
+        "boolean" => return instance.is_boolean(),
+        "float32" | "float64" => return instance.is_number(),
+        "string" => return instance.is_string(),
+        "timestamp" => {
+            return matches!(instance.as_str(), Some(x) if DateTime::parse_from_rfc3339(x).is_ok())
+        }
+        "int8" => (i8::MIN as f64, i8::MAX as f64),
+        "uint8" => (u8::MIN as f64, u8::MAX as f64),
+        "int16" => (i16::MIN as f64, i16::MAX as f64),
+        "uint16" => (u16::MIN as f64, u16::MAX as f64),
+        "int32" => (i32::MIN as f64, i32::MAX as f64),
+        "uint32" => (u32::MIN as f64, u32::MAX as f64),
+        _ => return false,
+    };
+    // Integers may have a zero fractional part, as in `3.0`.
+    match instance.as_f64() {
+        Some(x) => x.fract() == 0.0 && range.0 <= x && x <= range.1,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn indicators(schema: &Value, instance: &Value) -> Vec<(String, String)> {
+        let jtd = Jtd::from_schema(schema).unwrap();
+        let mut indicators: Vec<(String, String)> = match jtd.validate(instance) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|x| {
+                    let indicator = ErrorIndicator::from(&x);
+                    (indicator.instance_path, indicator.schema_path)
+                })
+                .collect(),
+        };
+        indicators.sort();
+        indicators
+    }
+
+    fn pair(instance_path: &str, schema_path: &str) -> (String, String) {
+        (instance_path.to_string(), schema_path.to_string())
+    }
+
+    #[test]
+    fn test_types() {
+        let schema = json!({"elements": {"type": "uint8"}});
+        assert_eq!(indicators(&schema, &json!([0, 255, 3.0])), vec![]);
+        assert_eq!(
+            indicators(&schema, &json!([-1, 256, 1.5, "1"])),
+            vec![
+                pair("/0", "/elements/type"),
+                pair("/1", "/elements/type"),
+                pair("/2", "/elements/type"),
+                pair("/3", "/elements/type"),
+            ]
+        );
+        let schema = json!({"type": "timestamp", "nullable": true});
+        assert_eq!(indicators(&schema, &json!("1990-12-31T23:59:60Z")), vec![]);
+        assert_eq!(indicators(&schema, &json!(null)), vec![]);
+        assert_eq!(
+            indicators(&schema, &json!("1990-12-31")),
+            vec![pair("", "/type")]
+        );
+    }
+
+    #[test]
+    fn test_properties_and_refs() {
+        let schema = json!({
+            "definitions": {
+                "node": {
+                    "properties": { "value": { "type": "string" } },
+                    "optionalProperties": { "next": { "ref": "node" } }
+                }
+            },
+            "ref": "node"
+        });
+        assert_eq!(
+            indicators(
+                &schema,
+                &json!({"value": "a", "next": {"value": 1, "next": {"extra": true}}})
+            ),
+            vec![
+                pair("/next/next", "/definitions/node/properties/value"),
+                pair("/next/next/extra", "/definitions/node"),
+                pair("/next/value", "/definitions/node/properties/value/type"),
+            ]
+        );
+        assert_eq!(
+            indicators(&schema, &json!([])),
+            vec![pair("", "/definitions/node/properties")]
+        );
+    }
+
+    #[test]
+    fn test_discriminator() {
+        let schema = json!({
+            "discriminator": "kind",
+            "mapping": {
+                "cat": { "properties": { "meow": { "type": "boolean" } } },
+                "dog": { "optionalProperties": { "bark": { "type": "boolean" } } }
+            }
+        });
+        assert_eq!(indicators(&schema, &json!({"kind": "dog"})), vec![]);
+        assert_eq!(
+            indicators(&schema, &json!({"kind": "cat"})),
+            vec![pair("", "/mapping/cat/properties/meow")]
+        );
+        assert_eq!(
+            indicators(&schema, &json!({"kind": "cow"})),
+            vec![pair("/kind", "/mapping")]
+        );
+        assert_eq!(
+            indicators(&schema, &json!({"kind": 1})),
+            vec![pair("/kind", "/discriminator")]
+        );
+        assert_eq!(
+            indicators(&schema, &json!({})),
+            vec![pair("", "/discriminator")]
+        );
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let schema = json!({"definitions": {"loop": {"ref": "loop"}}, "ref": "loop"});
+        let jtd = Jtd::from_schema(&schema).unwrap().with_max_depth(8);
+        let errors: Vec<ValidationError> = jtd.validate(&json!(1)).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].msg.contains("Maximum depth"));
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        for schema in &[
+            json!({"type": "integer"}),
+            json!({"ref": "missing"}),
+            json!({"type": "string", "enum": ["a"]}),
+            json!({"enum": ["a", "a"]}),
+            json!({"elements": {"definitions": {}}}),
+            json!({"properties": {"a": {}}, "optionalProperties": {"a": {}}}),
+            json!({"discriminator": "k", "mapping": {"x": {"type": "string"}}}),
+            json!({"discriminator": "k", "mapping": {"x": {"properties": {"k": {}}}}}),
+            json!({"nullable": "yes"}),
+            json!({"bogus": true}),
+        ] {
+            assert!(Jtd::from_schema(schema).is_err(), "{}", schema);
+        }
+        assert!(Jtd::from_schema(&json!({"metadata": {"description": "x"}})).is_ok());
+    }
+}
@@ -0,0 +1,282 @@
+//! Finding the keywords of a schema that a draft doesn't evaluate.
+
+use serde_json::Value;
+
+use crate::error::ValidationError;
+use crate::resolver;
+use crate::schemas::Draft;
+
+/// The built-in drafts, which are checked for the keywords that a schema's
+/// draft doesn't know.
+const DRAFTS: &[Draft] = &[
+    Draft::Draft3,
+    Draft::Draft4,
+    Draft::Draft6,
+    Draft::Draft7,
+    Draft::Draft201909,
+    Draft::Draft202012,
+    Draft::OpenApi30,
+    Draft::Kubernetes,
+    Draft::MongoDb,
+];
+
+/// A keyword of a schema that isn't evaluated by its draft.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnoredKeyword {
+    /// A JSON pointer to the keyword within the schema.
+    pub pointer: String,
+    /// The keyword itself.
+    pub keyword: String,
+    /// The other drafts that know the keyword. This is empty for keywords that
+    /// aren't part of any draft. For a keyword beside a `$ref`, these are the
+    /// drafts that evaluate it there.
+    pub drafts: Vec<Draft>,
+    path: Vec<String>,
+    beside_ref: bool,
+}
+
+impl IgnoredKeyword {
+    /// Describe the keyword as a validation error.
+    pub(crate) fn to_error(&self) -> ValidationError {
+        let msg = if self.beside_ref {
+            format!(
+                "Keyword \"{}\" is ignored by this draft, as it is beside \"$ref\"",
+                self.keyword
+            )
+        } else if self.drafts.is_empty() {
+            format!("Unknown keyword \"{}\" is ignored", self.keyword)
+        } else {
+            format!(
+                "Keyword \"{}\" is ignored by this draft, but known to {}",
+                self.keyword,
+                self.drafts
+                    .iter()
+                    .map(|x| format!("{:?}", x))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        };
+        self.path
+            .iter()
+            .rev()
+            .fold(ValidationError::new(&msg, None, None), |err, segment| {
+                err.schema_ctx(segment.clone())
+            })
+    }
+}
+
+/// The keywords of a schema that aren't evaluated by its draft.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IgnoredKeywords {
+    /// The keywords known to other drafts, such as `const` in a Draft 4 schema.
+    pub from_other_drafts: Vec<IgnoredKeyword>,
+    /// The keywords that aren't part of any draft.
+    pub unknown: Vec<IgnoredKeyword>,
+    /// The keywords beside a `$ref`, which overrides them before Draft
+    /// 2019-09, unless [`crate::Config::with_ref_siblings`] is set.
+    pub beside_ref: Vec<IgnoredKeyword>,
+}
+
+impl IgnoredKeywords {
+    /// Whether no keyword is ignored.
+    pub fn is_empty(&self) -> bool {
+        self.from_other_drafts.is_empty() && self.unknown.is_empty() && self.beside_ref.is_empty()
+    }
+
+    /// Iterate over all of the ignored keywords, from other drafts, unknown
+    /// and beside a `$ref`.
+    pub fn iter(&self) -> impl Iterator<Item = &IgnoredKeyword> {
+        self.from_other_drafts
+            .iter()
+            .chain(self.unknown.iter())
+            .chain(self.beside_ref.iter())
+    }
+}
+
+/// Find the keywords of a schema, written in `draft`, that aren't evaluated.
+/// Embedded schema resources that declare their own `$schema` are checked
+/// against that draft. The keywords beside a `$ref` that overrides them are
+/// included, unless `ref_siblings` is set to evaluate them. The subschemas of
+/// ignored keywords aren't visited.
+pub(crate) fn find_ignored_keywords(
+    draft: Draft,
+    schema: &Value,
+    ref_siblings: bool,
+) -> IgnoredKeywords {
+    let mut ignored = IgnoredKeywords::default();
+    visit(draft, schema, ref_siblings, &mut Vec::new(), &mut ignored);
+    ignored
+}
+
+fn visit(
+    draft: Draft,
+    schema: &Value,
+    ref_siblings: bool,
+    path: &mut Vec<String>,
+    ignored: &mut IgnoredKeywords,
+) {
+    let object = match schema {
+        Value::Object(object) => object,
+        _ => return,
+    };
+    // The siblings that a `$ref` overrides are those that would be evaluated
+    // without it, and not the likes of `definitions` or `title`.
+    let overridden = |keyword: &str| {
+        !ref_siblings
+            && draft.ref_overrides_siblings()
+            && object.contains_key("$ref")
+            && keyword != "$ref"
+            && draft.get_validator(keyword).is_some()
+    };
+    for keyword in object.keys() {
+        let beside_ref = overridden(keyword);
+        if draft.knows_keyword(keyword) && !beside_ref {
+            continue;
+        }
+        let mut keyword_path = path.clone();
+        keyword_path.push(keyword.clone());
+        let drafts: Vec<Draft> = DRAFTS
+            .iter()
+            .filter(|x| x.knows_keyword(keyword) && !(beside_ref && x.ref_overrides_siblings()))
+            .cloned()
+            .collect();
+        let keyword = IgnoredKeyword {
+            pointer: keyword_path
+                .iter()
+                .map(|x| format!("/{}", x.replace('~', "~0").replace('/', "~1")))
+                .collect(),
+            keyword: keyword.clone(),
+            drafts,
+            path: keyword_path,
+            beside_ref,
+        };
+        if beside_ref {
+            ignored.beside_ref.push(keyword);
+        } else if keyword.drafts.is_empty() {
+            ignored.unknown.push(keyword);
+        } else {
+            ignored.from_other_drafts.push(keyword);
+        }
+    }
+    for (subpath, subschema) in resolver::subschemas_with_paths(object) {
+        if !draft.knows_keyword(&subpath[0]) || overridden(&subpath[0]) {
+            continue;
+        }
+        let length = path.len();
+        path.extend(subpath);
+        visit(
+            resolver::draft_of(draft, subschema),
+            subschema,
+            ref_siblings,
+            path,
+            ignored,
+        );
+        path.truncate(length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ignored_keywords() {
+        let schema = json!({
+            "$id": "http://example.com/root.json",
+            "$comment": "root",
+            "properties": {
+                "a": { "const": 1, "bogus": true },
+                "b": { "if": { "bogus": 1 }, "then": {} }
+            },
+            "definitions": {
+                "c": { "$comment": "known to Draft 7 only" }
+            }
+        });
+        let ignored = find_ignored_keywords(Draft::Draft4, &schema, false);
+        let from_other_drafts: Vec<(&str, &[Draft])> = ignored
+            .from_other_drafts
+            .iter()
+            .map(|x| (x.pointer.as_str(), x.drafts.as_slice()))
+            .collect();
+        assert_eq!(
+            from_other_drafts,
+            vec![
+                (
+                    "/$comment",
+                    &[Draft::Draft7, Draft::Draft201909, Draft::Draft202012][..]
+                ),
+                (
+                    "/definitions/c/$comment",
+                    &[Draft::Draft7, Draft::Draft201909, Draft::Draft202012][..]
+                ),
+                (
+                    "/properties/a/const",
+                    &[
+                        Draft::Draft6,
+                        Draft::Draft7,
+                        Draft::Draft201909,
+                        Draft::Draft202012
+                    ][..]
+                ),
+                (
+                    "/properties/b/if",
+                    &[Draft::Draft7, Draft::Draft201909, Draft::Draft202012][..]
+                ),
+                (
+                    "/properties/b/then",
+                    &[Draft::Draft7, Draft::Draft201909, Draft::Draft202012][..]
+                ),
+            ]
+        );
+        let unknown: Vec<&str> = ignored.unknown.iter().map(|x| x.pointer.as_str()).collect();
+        assert_eq!(unknown, vec!["/properties/a/bogus"]);
+
+        assert!(
+            find_ignored_keywords(Draft::Draft7, &json!({"if": {}, "title": "x"}), false)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_ignored_ref_siblings() {
+        let schema = json!({
+            "definitions": { "a": { "type": "string" } },
+            "properties": {
+                "b": {
+                    "$ref": "#/definitions/a",
+                    "title": "b",
+                    "maxLength": 3,
+                    "items": { "bogus": true }
+                }
+            }
+        });
+        let ignored = find_ignored_keywords(Draft::Draft7, &schema, false);
+        let beside_ref: Vec<&str> = ignored
+            .beside_ref
+            .iter()
+            .map(|x| x.pointer.as_str())
+            .collect();
+        assert_eq!(
+            beside_ref,
+            vec!["/properties/b/items", "/properties/b/maxLength"]
+        );
+        assert_eq!(
+            ignored.beside_ref[1].drafts,
+            vec![Draft::Draft201909, Draft::Draft202012]
+        );
+        assert_eq!(
+            ignored.beside_ref[1].to_error().msg,
+            "Keyword \"maxLength\" is ignored by this draft, as it is beside \"$ref\""
+        );
+        assert!(ignored.unknown.is_empty());
+
+        let ignored = find_ignored_keywords(Draft::Draft7, &schema, true);
+        let unknown: Vec<&str> = ignored.unknown.iter().map(|x| x.pointer.as_str()).collect();
+        assert_eq!(unknown, vec!["/properties/b/items/bogus"]);
+        assert!(ignored.beside_ref.is_empty());
+        assert!(find_ignored_keywords(Draft::Draft202012, &schema, false)
+            .beside_ref
+            .is_empty());
+    }
+}
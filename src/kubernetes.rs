@@ -0,0 +1,420 @@
+//! Checking that a schema is a structural schema, as Kubernetes requires of
+//! the schemas of CustomResourceDefinitions.
+
+use serde_json::{Map, Value};
+
+use crate::error::{ErrorIterator, ValidationError};
+
+/// The keywords that a structural schema may not use anywhere.
+const FORBIDDEN: &[&str] = &[
+    "$ref",
+    "$schema",
+    "additionalItems",
+    "definitions",
+    "dependencies",
+    "id",
+    "patternProperties",
+];
+
+/// The keywords that a structural schema may not use within `allOf`, `anyOf`,
+/// `oneOf` or `not`, besides the `x-kubernetes-*` extensions.
+const FORBIDDEN_IN_JUNCTORS: &[&str] = &[
+    "additionalProperties",
+    "default",
+    "description",
+    "nullable",
+    "type",
+];
+
+const JUNCTORS: &[&str] = &["allOf", "anyOf", "oneOf", "not"];
+
+/// Check that a schema is a [structural
+/// schema](https://kubernetes.io/docs/tasks/extend-kubernetes/custom-resources/custom-resource-definitions/#specifying-a-structural-schema),
+/// as required of the `openAPIV3Schema` of a Kubernetes CustomResourceDefinition.
+///
+/// In a structural schema, every schema outside of `allOf`, `anyOf`, `oneOf`
+/// and `not` specifies a `type`, unless it sets `x-kubernetes-int-or-string` or
+/// `x-kubernetes-preserve-unknown-fields`. The schemas within those keywords
+/// only restrict values, and may only refer to the properties and items that
+/// the enclosing schema specifies. The `metadata` of the custom resource may
+/// only restrict its `name` and `generateName`.
+///
+/// This checks the shape of the schema only. Validating the schema against
+/// [`Draft::Kubernetes`](crate::schemas::Draft::Kubernetes) checks its keywords.
+///
+/// ## Example:
+///
+/// ```rust
+/// # use serde_json::json;
+/// let schema = json!({"type": "object", "properties": {"spec": {"properties": {}}}});
+/// let errors: Vec<_> = jsonschema_valid::check_structural(&schema).unwrap_err().collect();
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].msg, "\"type\" must not be empty");
+/// ```
+pub fn check_structural<'a>(schema: &'a Value) -> Result<(), ErrorIterator<'a>> {
+    let mut errors = Vec::new();
+    visit(schema, &mut Vec::new(), &mut errors);
+    if let Some(metadata) = schema.pointer("/properties/metadata") {
+        check_metadata(metadata, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Box::new(errors.into_iter()))
+    }
+}
+
+fn error(msg: &str, schema: &Value, path: &[String]) -> ValidationError {
+    path.iter().rev().fold(
+        ValidationError::new(msg, None, Some(schema)),
+        |err, segment| err.schema_ctx(segment.clone()),
+    )
+}
+
+fn is_true(object: &Map<String, Value>, key: &str) -> bool {
+    object.get(key) == Some(&Value::Bool(true))
+}
+
+/// Check the keywords that are forbidden everywhere in a structural schema.
+fn check_forbidden(
+    object: &Map<String, Value>,
+    path: &[String],
+    errors: &mut Vec<ValidationError>,
+) {
+    for key in FORBIDDEN {
+        if let Some(value) = object.get(*key) {
+            errors.push(error(
+                &format!("\"{}\" is not allowed in a structural schema", key),
+                value,
+                path,
+            ));
+        }
+    }
+    if is_true(object, "uniqueItems") {
+        errors.push(error(
+            "\"uniqueItems\" must not be true; use \"x-kubernetes-list-type\" instead",
+            &object["uniqueItems"],
+            path,
+        ));
+    }
+    if object.get("additionalProperties") == Some(&Value::Bool(false)) {
+        errors.push(error(
+            "\"additionalProperties\" must not be false",
+            &object["additionalProperties"],
+            path,
+        ));
+    }
+    if let Some(items) = object.get("items").filter(|x| x.is_array()) {
+        errors.push(error(
+            "\"items\" must be a single schema, not an array",
+            items,
+            path,
+        ));
+    }
+}
+
+/// Check a specified schema: the root, or the value of `properties`,
+/// `additionalProperties` or `items` outside of any junctor.
+fn visit(schema: &Value, path: &mut Vec<String>, errors: &mut Vec<ValidationError>) {
+    let object = match schema {
+        Value::Object(object) => object,
+        _ => return,
+    };
+    check_forbidden(object, path, errors);
+    let int_or_string = is_true(object, "x-kubernetes-int-or-string");
+    if int_or_string {
+        if object.contains_key("type") {
+            errors.push(error(
+                "\"type\" must be empty with \"x-kubernetes-int-or-string\"",
+                schema,
+                path,
+            ));
+        }
+    } else if !object.contains_key("type")
+        && !is_true(object, "x-kubernetes-preserve-unknown-fields")
+    {
+        errors.push(error("\"type\" must not be empty", schema, path));
+    }
+    if is_true(object, "x-kubernetes-embedded-resource")
+        && object.get("type").and_then(Value::as_str) != Some("object")
+    {
+        errors.push(error(
+            "\"type\" must be \"object\" with \"x-kubernetes-embedded-resource\"",
+            schema,
+            path,
+        ));
+    }
+    if object.contains_key("properties") && object.contains_key("additionalProperties") {
+        errors.push(error(
+            "\"properties\" and \"additionalProperties\" are mutually exclusive",
+            schema,
+            path,
+        ));
+    }
+
+    if let Some(Value::Object(properties)) = object.get("properties") {
+        path.push("properties".to_string());
+        for (key, subschema) in properties {
+            path.push(key.clone());
+            visit(subschema, path, errors);
+            path.pop();
+        }
+        path.pop();
+    }
+    for key in &["additionalProperties", "items"] {
+        if let Some(subschema) = object.get(*key).filter(|x| x.is_object()) {
+            path.push(key.to_string());
+            visit(subschema, path, errors);
+            path.pop();
+        }
+    }
+    for (subpath, subschema) in junctor_subschemas(object) {
+        let length = path.len();
+        path.extend(subpath);
+        visit_junctor(subschema, object, int_or_string, path, errors);
+        path.truncate(length);
+    }
+}
+
+/// The subschemas of `allOf`, `anyOf`, `oneOf` and `not`, along with their
+/// path within the schema.
+fn junctor_subschemas(object: &Map<String, Value>) -> Vec<(Vec<String>, &Value)> {
+    let mut subschemas = Vec::new();
+    for junctor in JUNCTORS {
+        match object.get(*junctor) {
+            Some(Value::Array(array)) => {
+                for (i, subschema) in array.iter().enumerate() {
+                    subschemas.push((vec![junctor.to_string(), i.to_string()], subschema));
+                }
+            }
+            Some(subschema) => subschemas.push((vec![junctor.to_string()], subschema)),
+            None => {}
+        }
+    }
+    subschemas
+}
+
+/// Check a schema within a junctor, which may only restrict the values that
+/// the specified schema `outer` describes.
+fn visit_junctor(
+    schema: &Value,
+    outer: &Map<String, Value>,
+    int_or_string: bool,
+    path: &mut Vec<String>,
+    errors: &mut Vec<ValidationError>,
+) {
+    let object = match schema {
+        Value::Object(object) => object,
+        _ => return,
+    };
+    check_forbidden(object, path, errors);
+    for key in object.keys() {
+        // Kubernetes allows `anyOf: [{type: integer}, {type: string}]` along
+        // with `x-kubernetes-int-or-string`.
+        let allowed_type = key == "type" && int_or_string;
+        if (FORBIDDEN_IN_JUNCTORS.contains(&key.as_str()) && !allowed_type)
+            || key.starts_with("x-kubernetes-")
+        {
+            errors.push(error(
+                &format!(
+                    "\"{}\" is not allowed within allOf, anyOf, oneOf or not",
+                    key
+                ),
+                &object[key],
+                path,
+            ));
+        }
+    }
+
+    if let Some(Value::Object(properties)) = object.get("properties") {
+        let outer_properties = outer.get("properties").and_then(Value::as_object);
+        path.push("properties".to_string());
+        for (key, subschema) in properties {
+            path.push(key.clone());
+            match outer_properties.and_then(|x| x.get(key)) {
+                Some(Value::Object(outer_subschema)) => {
+                    visit_junctor(subschema, outer_subschema, false, path, errors)
+                }
+                _ => errors.push(error(
+                    &format!(
+                        "Property \"{}\" must be specified outside of allOf, anyOf, oneOf or not",
+                        key
+                    ),
+                    subschema,
+                    path,
+                )),
+            }
+            path.pop();
+        }
+        path.pop();
+    }
+    if let Some(subschema) = object.get("items").filter(|x| x.is_object()) {
+        path.push("items".to_string());
+        match outer.get("items") {
+            Some(Value::Object(outer_subschema)) => {
+                visit_junctor(subschema, outer_subschema, false, path, errors)
+            }
+            _ => errors.push(error(
+                "\"items\" must be specified outside of allOf, anyOf, oneOf or not",
+                subschema,
+                path,
+            )),
+        }
+        path.pop();
+    }
+    for (subpath, subschema) in junctor_subschemas(object) {
+        let length = path.len();
+        path.extend(subpath);
+        visit_junctor(subschema, outer, int_or_string, path, errors);
+        path.truncate(length);
+    }
+}
+
+/// Check that the schema of the custom resource's `metadata` only restricts
+/// its `name` and `generateName`.
+fn check_metadata(metadata: &Value, errors: &mut Vec<ValidationError>) {
+    let object = match metadata {
+        Value::Object(object) => object,
+        _ => return,
+    };
+    let mut path = vec!["properties".to_string(), "metadata".to_string()];
+    for key in object.keys() {
+        if key != "type" && key != "properties" {
+            errors.push(error(
+                &format!("\"{}\" is not allowed in the schema of metadata", key),
+                &object[key],
+                &path,
+            ));
+        }
+    }
+    if let Some(Value::Object(properties)) = object.get("properties") {
+        path.push("properties".to_string());
+        for key in properties.keys() {
+            if key != "name" && key != "generateName" {
+                errors.push(error(
+                    &format!(
+                        "Only \"name\" and \"generateName\" of metadata may be restricted, not \"{}\"",
+                        key
+                    ),
+                    &properties[key],
+                    &path,
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn structural_errors(schema: &Value) -> Vec<(String, String)> {
+        match check_structural(schema) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|x| {
+                    let path: Vec<String> = x.schema_path.into_iter().rev().collect();
+                    (path.join("/"), x.msg)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_structural() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "metadata": {
+                    "type": "object",
+                    "properties": {"name": {"type": "string", "maxLength": 63}}
+                },
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "port": {"x-kubernetes-int-or-string": true},
+                        "labels": {
+                            "type": "object",
+                            "additionalProperties": {"type": "string"}
+                        },
+                        "template": {
+                            "type": "object",
+                            "x-kubernetes-embedded-resource": true,
+                            "x-kubernetes-preserve-unknown-fields": true
+                        }
+                    },
+                    "anyOf": [{"required": ["port"]}, {"properties": {"labels": {"minProperties": 1}}}]
+                },
+                "status": {"x-kubernetes-preserve-unknown-fields": true}
+            }
+        });
+        assert_eq!(structural_errors(&schema), vec![]);
+    }
+
+    #[test]
+    fn test_not_structural() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "metadata": {
+                    "type": "object",
+                    "properties": {"labels": {"type": "object"}}
+                },
+                "spec": {
+                    "properties": {
+                        "port": {"type": "integer", "x-kubernetes-int-or-string": true},
+                        "tags": {"type": "array", "items": [{"type": "string"}], "uniqueItems": true}
+                    },
+                    "not": {"description": "x", "properties": {"other": {}}}
+                },
+                "ref": {"$ref": "#/definitions/x"}
+            }
+        });
+        assert_eq!(
+            structural_errors(&schema),
+            vec![
+                (
+                    "properties/ref".to_string(),
+                    "\"$ref\" is not allowed in a structural schema".to_string()
+                ),
+                (
+                    "properties/ref".to_string(),
+                    "\"type\" must not be empty".to_string()
+                ),
+                (
+                    "properties/spec".to_string(),
+                    "\"type\" must not be empty".to_string()
+                ),
+                (
+                    "properties/spec/properties/port".to_string(),
+                    "\"type\" must be empty with \"x-kubernetes-int-or-string\"".to_string()
+                ),
+                (
+                    "properties/spec/properties/tags".to_string(),
+                    "\"uniqueItems\" must not be true; use \"x-kubernetes-list-type\" instead"
+                        .to_string()
+                ),
+                (
+                    "properties/spec/properties/tags".to_string(),
+                    "\"items\" must be a single schema, not an array".to_string()
+                ),
+                (
+                    "properties/spec/not".to_string(),
+                    "\"description\" is not allowed within allOf, anyOf, oneOf or not"
+                        .to_string()
+                ),
+                (
+                    "properties/spec/not/properties/other".to_string(),
+                    "Property \"other\" must be specified outside of allOf, anyOf, oneOf or not"
+                        .to_string()
+                ),
+                (
+                    "properties/metadata/properties".to_string(),
+                    "Only \"name\" and \"generateName\" of metadata may be restricted, not \"labels\""
+                        .to_string()
+                ),
+            ]
+        );
+    }
+}
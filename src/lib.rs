@@ -2,7 +2,14 @@
 //!
 //! A simple crate to perform [JSON Schema](https://json-schema.org/) validation.
 //!
-//! Supports JSON Schema drafts 4, 6, and 7.
+//! Supports JSON Schema drafts 3, 4, 6, 7, 2019-09 and 2020-12, as well as the
+//! OpenAPI 3.0 Schema Object, the structural schemas of Kubernetes
+//! CustomResourceDefinitions and MongoDB's `$jsonSchema`. JSON Type Definition
+//! (RFC 8927) schemas are supported too, using [`Jtd`]. HTTP requests and responses can also be validated
+//! against an OpenAPI 3.0 or 3.1 document, using [`OpenApi`], and the links of an
+//! instance can be extracted from a JSON Hyper-Schema, using [`Config::get_links`].
+//! Documents referred to by `$ref` that aren't part of the schema can be
+//! loaded on demand with a [`Retriever`].
 //!
 //! ## Example:
 //!
@@ -26,6 +33,7 @@
 //! ````
 
 #![warn(missing_docs)]
+#![allow(clippy::result_large_err)]
 
 use serde_json::Value;
 
@@ -33,15 +41,40 @@ mod config;
 mod context;
 mod error;
 mod format;
+#[cfg(feature = "http")]
+mod http;
+mod hyperschema;
+mod jtd;
+mod keywords;
+mod kubernetes;
+mod mongodb;
+mod openapi;
+mod policy;
+mod registry;
 mod resolver;
+mod retriever;
 pub mod schemas;
 mod unique;
+mod upgrade;
+mod uritemplate;
 mod util;
 mod validators;
 
 pub use crate::config::Config;
 use crate::context::Context;
 pub use crate::error::{ErrorIterator, ValidationError};
+pub use crate::format::FormatChecker;
+#[cfg(feature = "http")]
+pub use crate::http::HttpRetriever;
+pub use crate::hyperschema::Link;
+pub use crate::jtd::{ErrorIndicator, Jtd};
+pub use crate::keywords::{IgnoredKeyword, IgnoredKeywords};
+pub use crate::kubernetes::check_structural;
+pub use crate::openapi::{OpenApi, Request, Response};
+pub use crate::policy::{PolicyViolation, ResolutionPolicy};
+pub use crate::registry::{Registry, SharedRegistry};
+pub use crate::retriever::{FileRetriever, Retriever};
+pub use crate::upgrade::{upgrade, Change};
 
 /// Validates a given JSON instance against a given JSON schema, returning the
 /// errors, if any. draft may provide the schema draft to use. If not provided,
@@ -85,6 +118,9 @@ pub fn validate<'a>(
     cfg: &'a config::Config<'a>,
     instance: &'a Value,
 ) -> Result<(), ErrorIterator<'a>> {
+    if let Some(err) = cfg.vocabulary_error() {
+        return Err(Box::new(std::iter::once(err.clone())));
+    }
     let mut errors = validators::descend(
         cfg,
         instance,
@@ -109,7 +145,22 @@ mod tests {
     use std::path::PathBuf;
 
     // Test files we know will fail.
-    const KNOWN_FAILURES: &[&str] = &["refRemote.json"];
+    const KNOWN_FAILURES: &[&str] = &[];
+
+    // The test suite's remote documents are meant to be served at
+    // http://localhost:1234/ from its `remotes` directory. This retriever
+    // stands in for that server.
+    fn remotes(uri: &str) -> Result<Value, ValidationError> {
+        let not_found = || ValidationError::new(&format!("No remote document {}", uri), None, None);
+        let relative = uri
+            .strip_prefix("http://localhost:1234/")
+            .ok_or_else(not_found)?;
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("JSON-Schema-Test-Suite/remotes");
+        path.push(relative);
+        let file = fs::File::open(path).map_err(|_| not_found())?;
+        serde_json::from_reader(file).map_err(|_| not_found())
+    }
 
     fn test_draft(dirname: &str, draft: schemas::Draft) {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -144,11 +195,15 @@ mod tests {
                         let data = test.get("data").unwrap();
                         let valid = test.get("valid").unwrap();
                         if let Value::Bool(expected_valid) = valid {
-                            let cfg = config::Config::from_schema(schema, Some(draft)).unwrap();
+                            let cfg = config::Config::from_schema(schema, Some(draft))
+                                .unwrap()
+                                .with_retriever(remotes);
                             assert!(cfg.validate_schema().is_ok());
                             let result = validate(&cfg, data);
                             assert_eq!(result.is_ok(), *expected_valid);
-                            let cfg2 = config::Config::from_schema(schema, Some(draft)).unwrap();
+                            let cfg2 = config::Config::from_schema(schema, Some(draft))
+                                .unwrap()
+                                .with_retriever(remotes);
                             let result2 = cfg2.validate(data);
                             assert!(cfg2.validate_schema().is_ok());
                             assert_eq!(result2.is_ok(), *expected_valid);
@@ -159,6 +214,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_draft202012() {
+        test_draft("draft2020-12", schemas::Draft::Draft202012);
+    }
+
+    #[test]
+    fn test_draft201909() {
+        test_draft("draft2019-09", schemas::Draft::Draft201909);
+    }
+
     #[test]
     fn test_draft7() {
         test_draft("draft7", schemas::Draft::Draft7);
@@ -173,4 +238,9 @@ mod tests {
     fn test_draft4() {
         test_draft("draft4", schemas::Draft::Draft4);
     }
+
+    #[test]
+    fn test_draft3() {
+        test_draft("draft3", schemas::Draft::Draft3);
+    }
 }
@@ -0,0 +1,124 @@
+//! The BSON types of instances validated with MongoDB's `$jsonSchema`, which
+//! are given by [Extended JSON](https://www.mongodb.com/docs/manual/reference/mongodb-extended-json/).
+
+use std::convert::TryFrom;
+
+use serde_json::{Map, Value};
+
+/// Get the BSON type of a value, as named by `bsonType`.
+///
+/// Plain JSON values have the type that relaxed Extended JSON gives them:
+/// integers are an `int` if they fit in 32 bits, and a `long` otherwise, and
+/// other numbers are a `double`. Objects that are an Extended JSON wrapper,
+/// such as `{"$oid": "..."}` or `{"$numberLong": "..."}`, have the type they
+/// wrap.
+pub(crate) fn bson_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Number(number) => match number.as_i64() {
+            Some(n) if i32::try_from(n).is_ok() => "int",
+            Some(_) => "long",
+            None => "double",
+        },
+        Value::Object(object) => wrapper_type(object).unwrap_or("object"),
+    }
+}
+
+fn wrapper_type(object: &Map<String, Value>) -> Option<&'static str> {
+    let (key, value) = match object.len() {
+        1 => object.iter().next()?,
+        2 if object.contains_key("$code") && object.contains_key("$scope") => {
+            return Some("javascriptWithScope")
+        }
+        _ => return None,
+    };
+    let bson_type = match (key.as_str(), value) {
+        ("$oid", Value::String(oid))
+            if oid.len() == 24 && oid.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            "objectId"
+        }
+        ("$date", Value::String(_)) => "date",
+        ("$date", Value::Object(date)) if wrapper_type(date) == Some("long") => "date",
+        ("$numberInt", Value::String(_)) => "int",
+        ("$numberLong", Value::String(_)) => "long",
+        ("$numberDouble", Value::String(_)) => "double",
+        ("$numberDecimal", Value::String(_)) => "decimal",
+        ("$binary", Value::Object(_)) => "binData",
+        ("$regularExpression", Value::Object(_)) => "regex",
+        ("$timestamp", Value::Object(_)) => "timestamp",
+        ("$dbPointer", Value::Object(_)) => "dbPointer",
+        ("$code", Value::String(_)) => "javascript",
+        ("$symbol", Value::String(_)) => "symbol",
+        ("$undefined", Value::Bool(true)) => "undefined",
+        ("$minKey", _) => "minKey",
+        ("$maxKey", _) => "maxKey",
+        _ => return None,
+    };
+    Some(bson_type)
+}
+
+/// Whether a value has one of the BSON types named by `bsonType`, where
+/// `number` stands for all of the numeric types.
+pub(crate) fn has_bson_type(value: &Value, name: &str) -> bool {
+    let bson_type = bson_type(value);
+    match name {
+        "number" => matches!(bson_type, "int" | "long" | "double" | "decimal"),
+        _ => bson_type == name,
+    }
+}
+
+/// Get the number that an Extended JSON number wrapper holds, as a plain JSON
+/// number. Decimals are converted to the nearest `f64`.
+pub(crate) fn bson_number(value: &Value) -> Option<Value> {
+    let object = value.as_object()?;
+    let text = match bson_type(value) {
+        "int" | "long" | "double" | "decimal" => object.values().next()?.as_str()?,
+        _ => return None,
+    };
+    match text.parse::<i64>() {
+        Ok(n) => Some(Value::from(n)),
+        Err(_) => text
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bson_type() {
+        assert_eq!(bson_type(&json!(1)), "int");
+        assert_eq!(bson_type(&json!(1_i64 << 40)), "long");
+        assert_eq!(bson_type(&json!(1.5)), "double");
+        assert_eq!(
+            bson_type(&json!({"$oid": "5f1d7a3c9b1e8a2f4c6d8e0a"})),
+            "objectId"
+        );
+        assert_eq!(bson_type(&json!({"$oid": "nope"})), "object");
+        assert_eq!(bson_type(&json!({"$date": "2020-01-01T00:00:00Z"})), "date");
+        assert_eq!(bson_type(&json!({"$date": {"$numberLong": "0"}})), "date");
+        assert_eq!(
+            bson_type(&json!({"$code": "f()", "$scope": {}})),
+            "javascriptWithScope"
+        );
+        assert_eq!(bson_type(&json!({"$numberLong": "1", "x": 1})), "object");
+
+        assert!(has_bson_type(&json!({"$numberDecimal": "1.5"}), "number"));
+        assert_eq!(bson_number(&json!({"$numberLong": "12"})), Some(json!(12)));
+        assert_eq!(
+            bson_number(&json!({"$numberDecimal": "1.5"})),
+            Some(json!(1.5))
+        );
+        assert_eq!(bson_number(&json!({"$numberDouble": "NaN"})), None);
+        assert_eq!(bson_number(&json!(12)), None);
+    }
+}
@@ -0,0 +1,1142 @@
+//! Validation of HTTP requests and responses against an OpenAPI document.
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::{ErrorIterator, ValidationError};
+use crate::schemas::{self, Draft};
+use crate::validators;
+
+const METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+// Reference Objects may refer to other Reference Objects, but not forever.
+const MAX_REFERENCE_DEPTH: usize = 32;
+
+/// An HTTP request to validate against an OpenAPI document.
+#[derive(Debug, Default, Clone)]
+pub struct Request<'a> {
+    /// The HTTP method, such as `GET`.
+    pub method: &'a str,
+
+    /// The request target: the path, optionally followed by a query string.
+    pub path: &'a str,
+
+    /// The request headers, as name/value pairs.
+    pub headers: Vec<(&'a str, &'a str)>,
+
+    /// The media type of the body. If not provided, it is taken from the
+    /// `Content-Type` header.
+    pub content_type: Option<&'a str>,
+
+    /// The request body, if any.
+    pub body: Option<&'a Value>,
+}
+
+/// An HTTP response to validate against an OpenAPI document.
+#[derive(Debug, Default, Clone)]
+pub struct Response<'a> {
+    /// The method of the request that this is a response to.
+    pub method: &'a str,
+
+    /// The path of the request that this is a response to.
+    pub path: &'a str,
+
+    /// The HTTP status code.
+    pub status: u16,
+
+    /// The response headers, as name/value pairs.
+    pub headers: Vec<(&'a str, &'a str)>,
+
+    /// The media type of the body. If not provided, it is taken from the
+    /// `Content-Type` header.
+    pub content_type: Option<&'a str>,
+
+    /// The response body, if any.
+    pub body: Option<&'a Value>,
+}
+
+/// A path template from the `paths` of the document, compiled to a regular
+/// expression.
+struct PathTemplate<'a> {
+    template: &'a str,
+    regex: regex::Regex,
+    names: Vec<String>,
+    path_item: &'a Value,
+}
+
+/// The operation that a request was matched to.
+struct Operation<'a> {
+    template: &'a str,
+    method: String,
+    path_item: &'a Value,
+    operation: &'a Value,
+    path_parameters: HashMap<String, String>,
+}
+
+/// An OpenAPI 3.0 or 3.1 document, against which HTTP requests and responses
+/// can be validated.
+///
+/// The schemas in the document are validated using the OpenAPI 3.0 dialect
+/// for OpenAPI 3.0 documents, and using Draft 2020-12 (or the document's
+/// `jsonSchemaDialect`) for OpenAPI 3.1 documents. All references are
+/// resolved relative to the document.
+///
+/// Errors report where the problem is with an instance path starting with the
+/// location of the value: `body`, `path`, `query`, `header` or `cookie`.
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::json;
+/// use jsonschema_valid::{OpenApi, Request};
+///
+/// let document = json!({
+///     "openapi": "3.0.3",
+///     "info": { "title": "Pets", "version": "1.0" },
+///     "paths": {
+///         "/pets/{petId}": {
+///             "put": {
+///                 "parameters": [
+///                     { "name": "petId", "in": "path", "required": true,
+///                       "schema": { "type": "integer" } }
+///                 ],
+///                 "requestBody": {
+///                     "content": {
+///                         "application/json": {
+///                             "schema": { "$ref": "#/components/schemas/Pet" }
+///                         }
+///                     }
+///                 },
+///                 "responses": { "204": { "description": "Updated" } }
+///             }
+///         }
+///     },
+///     "components": {
+///         "schemas": {
+///             "Pet": { "type": "object", "required": ["name"] }
+///         }
+///     }
+/// });
+/// let openapi = OpenApi::from_document(&document)?;
+///
+/// let body = json!({"name": "Rex"});
+/// let request = Request {
+///     method: "PUT",
+///     path: "/pets/42",
+///     content_type: Some("application/json"),
+///     body: Some(&body),
+///     ..Default::default()
+/// };
+/// assert!(openapi.validate_request(&request).is_ok());
+/// # Ok(()) }
+/// ```
+pub struct OpenApi<'a> {
+    document: &'a Value,
+    cfg: Config<'a>,
+    paths: Vec<PathTemplate<'a>>,
+    base_paths: Vec<String>,
+}
+
+impl<'a> OpenApi<'a> {
+    /// Create a new OpenApi object from an OpenAPI 3.0 or 3.1 document.
+    pub fn from_document(document: &'a Value) -> Result<OpenApi<'a>, ValidationError> {
+        let version = document
+            .get("openapi")
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        let draft = if version.starts_with("3.0") {
+            Draft::OpenApi30
+        } else if version.starts_with("3.1") {
+            match document.get("jsonSchemaDialect").and_then(Value::as_str) {
+                Some(dialect) => schemas::draft_from_url(dialect).ok_or_else(|| {
+                    ValidationError::new(
+                        &format!("Unsupported jsonSchemaDialect {}", dialect),
+                        None,
+                        None,
+                    )
+                })?,
+                None => Draft::Draft202012,
+            }
+        } else {
+            return Err(ValidationError::new(
+                &format!("Unsupported OpenAPI version \"{}\"", version),
+                None,
+                None,
+            ));
+        };
+
+        let mut paths = Vec::new();
+        if let Some(Value::Object(path_items)) = document.get("paths") {
+            for (template, path_item) in path_items {
+                paths.push(compile_template(template, path_item)?);
+            }
+        }
+        // Concrete paths take precedence over templated ones.
+        paths.sort_by_key(|x| x.names.len());
+
+        let base_paths = document
+            .get("servers")
+            .and_then(Value::as_array)
+            .map(|servers| {
+                servers
+                    .iter()
+                    .filter_map(|x| x.get("url").and_then(Value::as_str))
+                    .filter_map(base_path)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(OpenApi {
+            document,
+            cfg: Config::from_schema(document, Some(draft))?,
+            paths,
+            base_paths,
+        })
+    }
+
+    /// Get the `Config` used to validate against the schemas in the document.
+    pub fn get_config(&self) -> &Config<'a> {
+        &self.cfg
+    }
+
+    /// Validate an HTTP request against the matching operation of the
+    /// document: its path, query, header and cookie parameters, and its
+    /// request body.
+    pub fn validate_request(&'a self, request: &Request) -> Result<(), ErrorIterator<'a>> {
+        let mut errors = Vec::new();
+        let (path, query) = split_query(request.path);
+        match self.find_operation(request.method, path) {
+            Ok(operation) => {
+                self.validate_parameters(&operation, query, &request.headers, &mut errors);
+                if let Err(err) = self.validate_request_body(&operation, request, &mut errors) {
+                    errors.push(err);
+                }
+            }
+            Err(err) => errors.push(err),
+        }
+        to_result(errors)
+    }
+
+    /// Validate an HTTP response against the matching response of the
+    /// operation in the document: its headers and its body.
+    pub fn validate_response(&'a self, response: &Response) -> Result<(), ErrorIterator<'a>> {
+        let mut errors = Vec::new();
+        let (path, _query) = split_query(response.path);
+        match self.find_operation(response.method, path) {
+            Ok(operation) => {
+                if let Err(err) = self.validate_response_object(&operation, response, &mut errors) {
+                    errors.push(err);
+                }
+            }
+            Err(err) => errors.push(err),
+        }
+        to_result(errors)
+    }
+
+    /// Follow a chain of Reference Objects to the object they refer to.
+    fn resolve(&'a self, value: &'a Value) -> Result<&'a Value, ValidationError> {
+        let mut value = value;
+        for _ in 0..MAX_REFERENCE_DEPTH {
+            match value.get("$ref").and_then(Value::as_str) {
+                Some(reference) => {
+                    value = self
+                        .cfg
+                        .get_resolver()
+                        .resolve_fragment(
+                            self.cfg.draft,
+                            reference,
+                            &Context::new_from(self.document),
+                            self.document,
+                        )
+                        .map_err(|_| {
+                            ValidationError::new(
+                                &format!("Couldn't resolve reference {}", reference),
+                                None,
+                                None,
+                            )
+                        })?
+                        .2;
+                }
+                None => return Ok(value),
+            }
+        }
+        Err(ValidationError::new(
+            "Too many nested references",
+            None,
+            Some(value),
+        ))
+    }
+
+    fn find_operation(
+        &'a self,
+        method: &str,
+        path: &str,
+    ) -> Result<Operation<'a>, ValidationError> {
+        let candidates = std::iter::once(path).chain(
+            self.base_paths
+                .iter()
+                .filter_map(|base| path.strip_prefix(base.as_str()))
+                .filter(|x| x.starts_with('/')),
+        );
+        // If a path matches but doesn't have the method, a less specific path
+        // might still have it.
+        let mut not_allowed = None;
+        for candidate in candidates {
+            for path_template in &self.paths {
+                if let Some(captures) = path_template.regex.captures(candidate) {
+                    let method = method.to_ascii_lowercase();
+                    let path_item = self.resolve(path_template.path_item)?;
+                    let operation = match path_item.get(&method) {
+                        Some(operation) if METHODS.contains(&method.as_str()) => operation,
+                        _ => {
+                            not_allowed.get_or_insert_with(|| {
+                                ValidationError::new(
+                                    &format!(
+                                        "Method {} is not allowed for path {}",
+                                        method.to_ascii_uppercase(),
+                                        path_template.template
+                                    ),
+                                    None,
+                                    Some(path_item),
+                                )
+                            });
+                            continue;
+                        }
+                    };
+                    let path_parameters = path_template
+                        .names
+                        .iter()
+                        .zip(captures.iter().skip(1))
+                        .filter_map(|(name, value)| {
+                            value.map(|value| {
+                                let value = percent_encoding::percent_decode_str(value.as_str())
+                                    .decode_utf8_lossy()
+                                    .into_owned();
+                                (name.clone(), value)
+                            })
+                        })
+                        .collect();
+                    return Ok(Operation {
+                        template: path_template.template,
+                        method,
+                        path_item,
+                        operation,
+                        path_parameters,
+                    });
+                }
+            }
+        }
+        Err(not_allowed.unwrap_or_else(|| {
+            ValidationError::new(
+                &format!("No path in the document matches {}", path),
+                None,
+                None,
+            )
+        }))
+    }
+
+    /// Validate a value from the request or response against a schema from
+    /// the document. `instance_path` is the location of the value in the
+    /// request or response, and `schema_path` the location of the schema in
+    /// the document.
+    fn validate_value(
+        &'a self,
+        instance: &Value,
+        schema: &Value,
+        instance_path: &[&str],
+        schema_path: &[String],
+        errors: &mut Vec<ValidationError>,
+    ) {
+        errors.extend(
+            validators::descend(
+                &self.cfg,
+                instance,
+                schema,
+                None,
+                Context::new_from(self.document),
+            )
+            .map(|err| with_path(err, instance_path, schema_path)),
+        );
+    }
+
+    fn validate_parameters(
+        &'a self,
+        operation: &Operation<'a>,
+        query: Option<&str>,
+        headers: &[(&str, &str)],
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let query: Vec<(String, String)> = query
+            .map(|x| {
+                url::form_urlencoded::parse(x.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let cookies: Vec<(&str, &str)> = header_values(headers, "cookie")
+            .flat_map(|x| x.split(';'))
+            .filter_map(|x| x.split_once('='))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect();
+
+        // Operation parameters override path item parameters with the same
+        // name and location.
+        let mut parameters: Vec<(Vec<String>, &'a Value)> = Vec::new();
+        let owners = [
+            (
+                vec!["paths", operation.template],
+                operation.path_item.get("parameters"),
+            ),
+            (
+                vec!["paths", operation.template, &operation.method],
+                operation.operation.get("parameters"),
+            ),
+        ];
+        for (owner, parameter_list) in owners.iter() {
+            for (index, parameter) in parameter_list
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                let parameter = match self.resolve(parameter) {
+                    Ok(parameter) => parameter,
+                    Err(err) => {
+                        errors.push(err);
+                        continue;
+                    }
+                };
+                parameters.retain(|(_, x)| {
+                    x.get("name") != parameter.get("name") || x.get("in") != parameter.get("in")
+                });
+                let mut schema_path: Vec<String> = owner.iter().map(|x| x.to_string()).collect();
+                schema_path.push("parameters".to_string());
+                schema_path.push(index.to_string());
+                parameters.push((schema_path, parameter));
+            }
+        }
+
+        for (schema_path, parameter) in parameters {
+            let name = parameter.get("name").and_then(Value::as_str).unwrap_or("");
+            let location = parameter.get("in").and_then(Value::as_str).unwrap_or("");
+            let raw: Vec<&str> = match location {
+                "path" => operation
+                    .path_parameters
+                    .get(name)
+                    .map(String::as_str)
+                    .into_iter()
+                    .collect(),
+                "query" => query
+                    .iter()
+                    .filter(|(k, _)| k == name)
+                    .map(|(_, v)| v.as_str())
+                    .collect(),
+                "header" => header_values(headers, name).collect(),
+                "cookie" => cookies
+                    .iter()
+                    .filter(|(k, _)| *k == name)
+                    .map(|(_, v)| *v)
+                    .collect(),
+                _ => continue,
+            };
+
+            if raw.is_empty() {
+                if location == "path" || parameter.get("required") == Some(&Value::Bool(true)) {
+                    errors.push(with_path(
+                        ValidationError::new(
+                            &format!("Required {} parameter \"{}\" is missing", location, name),
+                            None,
+                            Some(parameter),
+                        ),
+                        &[location],
+                        &schema_path,
+                    ));
+                }
+            } else if let Err(err) =
+                self.validate_parameter(parameter, &[location, name], &raw, schema_path, errors)
+            {
+                errors.push(err);
+            }
+        }
+    }
+
+    /// Validate the raw string values of a parameter or header against its
+    /// schema, after converting them to the types that the schema expects.
+    fn validate_parameter(
+        &'a self,
+        parameter: &'a Value,
+        instance_path: &[&str],
+        raw: &[&str],
+        mut schema_path: Vec<String>,
+        errors: &mut Vec<ValidationError>,
+    ) -> Result<(), ValidationError> {
+        if let Some(schema) = parameter.get("schema") {
+            let style = parameter.get("style").and_then(Value::as_str);
+            let explode = parameter
+                .get("explode")
+                .and_then(Value::as_bool)
+                .unwrap_or_else(|| style.unwrap_or("form") == "form");
+            let value = self.coerce(instance_path[0], explode, raw, schema)?;
+            schema_path.push("schema".to_string());
+            self.validate_value(&value, schema, instance_path, &schema_path, errors);
+        } else if let Some((media_type, media_type_object)) = parameter
+            .get("content")
+            .and_then(Value::as_object)
+            .and_then(|x| x.iter().next())
+        {
+            // A parameter with `content` is serialized as that media type,
+            // which is only understood here if it is JSON.
+            if let Some(schema) = media_type_object.get("schema") {
+                let value = serde_json::from_str(raw[0])
+                    .unwrap_or_else(|_| Value::String(raw[0].to_string()));
+                schema_path.extend(vec![
+                    "content".to_string(),
+                    media_type.clone(),
+                    "schema".to_string(),
+                ]);
+                self.validate_value(&value, schema, instance_path, &schema_path, errors);
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert the raw string values of a parameter to the JSON types that its
+    /// schema expects.
+    fn coerce(
+        &'a self,
+        location: &str,
+        explode: bool,
+        raw: &[&str],
+        schema: &'a Value,
+    ) -> Result<Value, ValidationError> {
+        let schema = self.resolve(schema)?;
+        if schema_types(schema).any(|x| x == "array") {
+            let items = match schema.get("items") {
+                Some(items) => self.resolve(items)?,
+                None => &Value::Null,
+            };
+            let values: Vec<&str> = if location == "query" && explode {
+                raw.to_vec()
+            } else {
+                raw[0].split(',').collect()
+            };
+            Ok(Value::Array(
+                values
+                    .into_iter()
+                    .map(|x| coerce_scalar(x, items))
+                    .collect(),
+            ))
+        } else {
+            Ok(coerce_scalar(raw[0], schema))
+        }
+    }
+
+    fn validate_request_body(
+        &'a self,
+        operation: &Operation<'a>,
+        request: &Request,
+        errors: &mut Vec<ValidationError>,
+    ) -> Result<(), ValidationError> {
+        let request_body = match operation.operation.get("requestBody") {
+            Some(request_body) => self.resolve(request_body)?,
+            None => return Ok(()),
+        };
+        let schema_path = vec![
+            "paths".to_string(),
+            operation.template.to_string(),
+            operation.method.clone(),
+            "requestBody".to_string(),
+        ];
+        match request.body {
+            Some(body) => self.validate_content(
+                request_body,
+                request.content_type,
+                &request.headers,
+                body,
+                schema_path,
+                errors,
+            ),
+            None => {
+                if request_body.get("required") == Some(&Value::Bool(true)) {
+                    errors.push(with_path(
+                        ValidationError::new(
+                            "Required request body is missing",
+                            None,
+                            Some(request_body),
+                        ),
+                        &["body"],
+                        &schema_path,
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn validate_response_object(
+        &'a self,
+        operation: &Operation<'a>,
+        response: &Response,
+        errors: &mut Vec<ValidationError>,
+    ) -> Result<(), ValidationError> {
+        let responses = operation
+            .operation
+            .get("responses")
+            .and_then(Value::as_object);
+        let status = response.status.to_string();
+        let range = format!("{}XX", response.status / 100);
+        let (key, response_object) = match responses.and_then(|x| {
+            x.get_key_value(&status)
+                .or_else(|| x.get_key_value(&range))
+                .or_else(|| x.get_key_value(&range.to_ascii_lowercase()))
+                .or_else(|| x.get_key_value("default"))
+        }) {
+            Some((key, response_object)) => (key, self.resolve(response_object)?),
+            None => {
+                return Err(ValidationError::new(
+                    &format!(
+                        "No response is defined for status {} of {} {}",
+                        response.status,
+                        operation.method.to_ascii_uppercase(),
+                        operation.template
+                    ),
+                    None,
+                    None,
+                ))
+            }
+        };
+        let schema_path = vec![
+            "paths".to_string(),
+            operation.template.to_string(),
+            operation.method.clone(),
+            "responses".to_string(),
+            key.clone(),
+        ];
+
+        if let Some(Value::Object(headers)) = response_object.get("headers") {
+            for (name, header) in headers {
+                // The Content-Type is described by `content` instead.
+                if name.eq_ignore_ascii_case("content-type") {
+                    continue;
+                }
+                let header = self.resolve(header)?;
+                let raw: Vec<&str> = header_values(&response.headers, name).collect();
+                let mut header_path = schema_path.clone();
+                header_path.extend(vec!["headers".to_string(), name.clone()]);
+                if raw.is_empty() {
+                    if header.get("required") == Some(&Value::Bool(true)) {
+                        errors.push(with_path(
+                            ValidationError::new(
+                                &format!("Required header \"{}\" is missing", name),
+                                None,
+                                Some(header),
+                            ),
+                            &["header"],
+                            &header_path,
+                        ));
+                    }
+                } else {
+                    self.validate_parameter(header, &["header", name], &raw, header_path, errors)?;
+                }
+            }
+        }
+
+        match response.body {
+            Some(body) => self.validate_content(
+                response_object,
+                response.content_type,
+                &response.headers,
+                body,
+                schema_path,
+                errors,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate a body against the schema of the media type in `content` that
+    /// best matches its content type.
+    fn validate_content(
+        &'a self,
+        owner: &'a Value,
+        content_type: Option<&str>,
+        headers: &[(&str, &str)],
+        body: &Value,
+        mut schema_path: Vec<String>,
+        errors: &mut Vec<ValidationError>,
+    ) -> Result<(), ValidationError> {
+        let content = match owner.get("content").and_then(Value::as_object) {
+            Some(content) if !content.is_empty() => content,
+            _ => return Ok(()),
+        };
+        let content_type = content_type.or_else(|| header_values(headers, "content-type").next());
+        let media_type = match content_type {
+            Some(content_type) => select_media_type(content, content_type),
+            None if content.len() == 1 => content.iter().next(),
+            None => None,
+        };
+        let (media_type, media_type_object) = match media_type {
+            Some(media_type) => media_type,
+            None => {
+                errors.push(with_path(
+                    ValidationError::new(
+                        &format!(
+                            "Content type {} is not one of {}",
+                            content_type.unwrap_or("(none)"),
+                            content
+                                .keys()
+                                .map(String::as_str)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                        None,
+                        None,
+                    ),
+                    &["body"],
+                    &schema_path,
+                ));
+                return Ok(());
+            }
+        };
+        if let Some(schema) = media_type_object.get("schema") {
+            schema_path.extend(vec![
+                "content".to_string(),
+                media_type.clone(),
+                "schema".to_string(),
+            ]);
+            self.validate_value(body, schema, &["body"], &schema_path, errors);
+        }
+        Ok(())
+    }
+}
+
+/// Compile a path template such as `/pets/{petId}` to a regular expression.
+fn compile_template<'a>(
+    template: &'a str,
+    path_item: &'a Value,
+) -> Result<PathTemplate<'a>, ValidationError> {
+    lazy_static::lazy_static! {
+        static ref PARAMETER: regex::Regex = regex::Regex::new(r"\{([^}/]+)\}").unwrap();
+    }
+    let mut pattern = String::from("^");
+    let mut names = Vec::new();
+    let mut last = 0;
+    for captures in PARAMETER.captures_iter(template) {
+        let whole = captures.get(0).unwrap();
+        pattern.push_str(&regex::escape(&template[last..whole.start()]));
+        pattern.push_str("([^/]+)");
+        names.push(captures[1].to_string());
+        last = whole.end();
+    }
+    pattern.push_str(&regex::escape(&template[last..]));
+    pattern.push('$');
+    let regex = regex::Regex::new(&pattern).map_err(|_| {
+        ValidationError::new(
+            &format!("Invalid path template {}", template),
+            None,
+            Some(path_item),
+        )
+    })?;
+    Ok(PathTemplate {
+        template,
+        regex,
+        names,
+        path_item,
+    })
+}
+
+/// Get the path of a server URL, if requests to the server have a base path.
+fn base_path(url: &str) -> Option<String> {
+    if url.contains('{') {
+        return None;
+    }
+    let path = match url::Url::parse(url) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => url.to_string(),
+    };
+    let path = path.trim_end_matches('/');
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// Split a request target into its path and query string.
+fn split_query(path: &str) -> (&str, Option<&str>) {
+    match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    }
+}
+
+/// Get the values of a header, whose name is case-insensitive.
+fn header_values<'h>(
+    headers: &'h [(&'h str, &'h str)],
+    name: &'h str,
+) -> impl Iterator<Item = &'h str> + 'h {
+    headers
+        .iter()
+        .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| *v)
+}
+
+/// Find the media type in `content` that best matches a content type: an
+/// exact match, then a match of the `type/*` range, then `*/*`.
+fn select_media_type<'c>(
+    content: &'c Map<String, Value>,
+    content_type: &str,
+) -> Option<(&'c String, &'c Value)> {
+    let essence = |x: &str| {
+        x.split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase()
+    };
+    let content_type = essence(content_type);
+    let range = match content_type.split_once('/') {
+        Some((kind, _)) => format!("{}/*", kind),
+        None => "*/*".to_string(),
+    };
+    [content_type.as_str(), range.as_str(), "*/*"]
+        .iter()
+        .find_map(|wanted| content.iter().find(|(k, _)| essence(k) == *wanted))
+}
+
+/// Get the names in the `type` of a schema, which may be a string or, from
+/// OpenAPI 3.1, an array.
+fn schema_types(schema: &Value) -> impl Iterator<Item = &str> {
+    schema
+        .get("type")
+        .into_iter()
+        .flat_map(crate::util::iter_or_once)
+        .filter_map(Value::as_str)
+}
+
+/// Convert a raw string value to the first of the types in the schema that it
+/// can be parsed as. If there is none, it stays a string.
+fn coerce_scalar(raw: &str, schema: &Value) -> Value {
+    for kind in schema_types(schema) {
+        let value = match kind {
+            "integer" => raw.parse::<i64>().ok().map(Value::from),
+            "number" => raw
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number),
+            "boolean" => raw.parse::<bool>().ok().map(Value::Bool),
+            "null" if raw.is_empty() => Some(Value::Null),
+            _ => None,
+        };
+        if let Some(value) = value {
+            return value;
+        }
+    }
+    Value::String(raw.to_string())
+}
+
+/// Prefix the paths of an error with the location of the value in the request
+/// or response, and the location of the schema in the document.
+fn with_path(
+    mut err: ValidationError,
+    instance_path: &[&str],
+    schema_path: &[String],
+) -> ValidationError {
+    for segment in instance_path.iter().rev() {
+        err = err.instance_ctx(segment.to_string());
+    }
+    // Path templates contain slashes, so the segments are escaped as in a
+    // JSON pointer.
+    for segment in schema_path.iter().rev() {
+        err = err.schema_ctx(segment.replace('~', "~0").replace('/', "~1"));
+    }
+    err
+}
+
+fn to_result<'a>(errors: Vec<ValidationError>) -> Result<(), ErrorIterator<'a>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Box::new(errors.into_iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn petstore(version: &str) -> Value {
+        json!({
+            "openapi": version,
+            "info": { "title": "Pets", "version": "1.0" },
+            "servers": [{ "url": "https://example.com/v1" }],
+            "paths": {
+                "/pets": {
+                    "get": {
+                        "parameters": [
+                            { "$ref": "#/components/parameters/limit" },
+                            {
+                                "name": "tags", "in": "query",
+                                "schema": { "type": "array", "items": { "type": "string" } }
+                            },
+                            {
+                                "name": "X-Request-Id", "in": "header", "required": true,
+                                "schema": { "type": "string", "format": "uuid" }
+                            }
+                        ],
+                        "responses": {
+                            "2XX": {
+                                "description": "Pets",
+                                "headers": {
+                                    "X-Total": {
+                                        "required": true, "schema": { "type": "integer" }
+                                    }
+                                },
+                                "content": {
+                                    "application/json": {
+                                        "schema": {
+                                            "type": "array",
+                                            "items": { "$ref": "#/components/schemas/Pet" }
+                                        }
+                                    }
+                                }
+                            },
+                            "default": { "$ref": "#/components/responses/Error" }
+                        }
+                    },
+                    "post": {
+                        "requestBody": {
+                            "required": true,
+                            "content": {
+                                "application/*": {
+                                    "schema": { "$ref": "#/components/schemas/Pet" }
+                                }
+                            }
+                        },
+                        "responses": { "201": { "description": "Created" } }
+                    }
+                },
+                "/pets/{petId}": {
+                    "parameters": [
+                        {
+                            "name": "petId", "in": "path", "required": true,
+                            "schema": { "type": "integer", "minimum": 1 }
+                        }
+                    ],
+                    "get": { "responses": { "200": { "description": "A pet" } } }
+                },
+                "/pets/mine": {
+                    "get": { "responses": { "200": { "description": "My pets" } } }
+                }
+            },
+            "components": {
+                "parameters": {
+                    "limit": {
+                        "name": "limit", "in": "query",
+                        "schema": { "type": "integer", "maximum": 100 }
+                    }
+                },
+                "responses": {
+                    "Error": {
+                        "description": "Error",
+                        "content": {
+                            "application/json": {
+                                "schema": { "type": "object", "required": ["message"] }
+                            }
+                        }
+                    }
+                },
+                "schemas": {
+                    "Pet": {
+                        "type": "object",
+                        "required": ["name"],
+                        "properties": { "name": { "type": "string" } }
+                    }
+                }
+            }
+        })
+    }
+
+    fn request_errors(openapi: &OpenApi, request: &Request) -> Vec<ValidationError> {
+        match openapi.validate_request(request) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.collect(),
+        }
+    }
+
+    #[test]
+    fn test_parameters() {
+        for version in &["3.0.3", "3.1.0"] {
+            let document = petstore(version);
+            let openapi = OpenApi::from_document(&document).unwrap();
+            let headers = vec![("x-request-id", "abc")];
+
+            let request = Request {
+                method: "GET",
+                path: "/pets?limit=10&tags=a&tags=b",
+                headers: headers.clone(),
+                ..Default::default()
+            };
+            assert!(request_errors(&openapi, &request).is_empty());
+
+            let request = Request {
+                method: "get",
+                path: "/v1/pets?limit=1000",
+                ..Default::default()
+            };
+            let errors = request_errors(&openapi, &request);
+            assert_eq!(errors.len(), 2);
+            assert_eq!(errors[0].instance_path, vec!("limit", "query"));
+            assert!(format!("{}", errors[0])
+                .contains("At schema path /paths/~1pets/get/parameters/0/schema/maximum"));
+            assert!(errors[1]
+                .msg
+                .contains("Required header parameter \"X-Request-Id\" is missing"));
+
+            let request = Request {
+                method: "GET",
+                path: "/pets/0",
+                ..Default::default()
+            };
+            let errors = request_errors(&openapi, &request);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].instance_path, vec!("petId", "path"));
+
+            let request = Request {
+                method: "GET",
+                path: "/pets/mine",
+                ..Default::default()
+            };
+            assert!(request_errors(&openapi, &request).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_request_body() {
+        let document = petstore("3.0.3");
+        let openapi = OpenApi::from_document(&document).unwrap();
+
+        let body = json!({"name": "Rex"});
+        let request = Request {
+            method: "POST",
+            path: "/pets",
+            headers: vec![(
+                "Content-Type",
+                "application/merge-patch+json; charset=utf-8",
+            )],
+            body: Some(&body),
+            ..Default::default()
+        };
+        assert!(request_errors(&openapi, &request).is_empty());
+
+        let body = json!({"name": 42});
+        let request = Request {
+            method: "POST",
+            path: "/pets",
+            content_type: Some("application/json"),
+            body: Some(&body),
+            ..Default::default()
+        };
+        let errors = request_errors(&openapi, &request);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].instance_path, vec!("name", "body"));
+
+        let request = Request {
+            method: "POST",
+            path: "/pets",
+            content_type: Some("text/plain"),
+            body: Some(&body),
+            ..Default::default()
+        };
+        let errors = request_errors(&openapi, &request);
+        assert!(errors[0]
+            .msg
+            .contains("Content type text/plain is not one of"));
+
+        let request = Request {
+            method: "POST",
+            path: "/pets",
+            ..Default::default()
+        };
+        let errors = request_errors(&openapi, &request);
+        assert!(errors[0].msg.contains("Required request body is missing"));
+    }
+
+    #[test]
+    fn test_response() {
+        let document = petstore("3.1.0");
+        let openapi = OpenApi::from_document(&document).unwrap();
+
+        let body = json!([{"name": "Rex"}]);
+        let response = Response {
+            method: "GET",
+            path: "/pets",
+            status: 206,
+            headers: vec![("X-Total", "1")],
+            content_type: Some("application/json"),
+            body: Some(&body),
+        };
+        assert!(openapi.validate_response(&response).is_ok());
+
+        let body = json!({"error": "oops"});
+        let response = Response {
+            method: "GET",
+            path: "/pets",
+            status: 500,
+            content_type: Some("application/json"),
+            body: Some(&body),
+            ..Default::default()
+        };
+        let errors: Vec<_> = openapi.validate_response(&response).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(format!("{}", errors[0]).contains("/paths/~1pets/get/responses/default"));
+
+        let response = Response {
+            method: "GET",
+            path: "/pets/1",
+            status: 404,
+            ..Default::default()
+        };
+        let errors: Vec<_> = openapi.validate_response(&response).unwrap_err().collect();
+        assert!(errors[0]
+            .msg
+            .contains("No response is defined for status 404 of GET /pets/{petId}"));
+    }
+
+    #[test]
+    fn test_unknown_operation() {
+        let document = petstore("3.0.3");
+        let openapi = OpenApi::from_document(&document).unwrap();
+
+        let request = Request {
+            method: "DELETE",
+            path: "/pets",
+            ..Default::default()
+        };
+        let errors = request_errors(&openapi, &request);
+        assert!(errors[0]
+            .msg
+            .contains("Method DELETE is not allowed for path /pets"));
+
+        let request = Request {
+            method: "GET",
+            path: "/owners",
+            ..Default::default()
+        };
+        let errors = request_errors(&openapi, &request);
+        assert!(errors[0]
+            .msg
+            .contains("No path in the document matches /owners"));
+
+        let document = json!({"swagger": "2.0"});
+        assert!(OpenApi::from_document(&document).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Restricting the external documents that references may load.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ValidationError;
+
+/// Limits on the documents that a [`crate::Config`] loads with its
+/// [`crate::Retriever`], for validating against schemas that aren't trusted.
+///
+/// Every document that isn't one of the schema's own resources or a known
+/// metaschema is checked against the policy before it is retrieved, and again
+/// once it is. A reference that the policy refuses fails to resolve, with a
+/// [`PolicyViolation`] in the `policy_violation` of its error.
+///
+/// A new policy allows everything, and each method adds a restriction.
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::{json, Value};
+/// # use jsonschema_valid::{Config, PolicyViolation, ResolutionPolicy};
+/// let schema = json!({"$ref": "http://169.254.169.254/latest/meta-data"});
+/// let cfg = Config::from_schema(&schema, None)?
+///     .with_retriever(|_: &str| Ok(json!({})))
+///     .with_policy(
+///         ResolutionPolicy::new()
+///             .with_allowed_schemes(&["https"])
+///             .allow_host("schemas.example.com"),
+///     );
+/// let errors: Vec<_> = cfg.validate(&json!(1)).unwrap_err().collect();
+/// assert_eq!(
+///     errors[0].policy_violation,
+///     Some(PolicyViolation::SchemeNotAllowed("http".to_string()))
+/// );
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionPolicy {
+    schemes: Option<Vec<String>>,
+    allowed_hosts: Option<Vec<String>>,
+    denied_hosts: Vec<String>,
+    fs_root: Option<PathBuf>,
+    max_document_size: Option<usize>,
+    max_documents: Option<usize>,
+}
+
+/// The reason that a [`ResolutionPolicy`] refused to load a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// The URI scheme, such as `file` or `http`, isn't one of the allowed
+    /// schemes.
+    SchemeNotAllowed(String),
+    /// The host isn't on the list of allowed hosts.
+    HostNotAllowed(String),
+    /// The host is on the list of denied hosts.
+    HostDenied(String),
+    /// The file is outside of the directory that files are confined to.
+    OutsideRoot(PathBuf),
+    /// The document is larger than the maximum size, in bytes.
+    DocumentTooLarge {
+        /// The size of the document: as read by the retriever, or as compact
+        /// JSON if the retriever doesn't check it. When the retriever stops
+        /// reading a document that is too large, this is only the size read.
+        size: usize,
+        /// The maximum size allowed.
+        max: usize,
+    },
+    /// As many documents as allowed have been loaded already.
+    TooManyDocuments(usize),
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyViolation::SchemeNotAllowed(scheme) => {
+                write!(f, "the scheme \"{}\" isn't allowed", scheme)
+            }
+            PolicyViolation::HostNotAllowed(host) => {
+                write!(f, "the host \"{}\" isn't allowed", host)
+            }
+            PolicyViolation::HostDenied(host) => write!(f, "the host \"{}\" is denied", host),
+            PolicyViolation::OutsideRoot(path) => {
+                write!(f, "the file {} is outside the allowed root", path.display())
+            }
+            PolicyViolation::DocumentTooLarge { size, max } => write!(
+                f,
+                "the document is {} bytes, more than the maximum of {}",
+                size, max
+            ),
+            PolicyViolation::TooManyDocuments(max) => {
+                write!(f, "the maximum of {} documents were loaded already", max)
+            }
+        }
+    }
+}
+
+impl PolicyViolation {
+    /// Make the error for refusing to load the document at a URL.
+    pub(crate) fn to_error(&self, url: &str) -> ValidationError {
+        ValidationError {
+            policy_violation: Some(self.clone()),
+            ..ValidationError::new(&format!("Refused to load {}: {}", url, self), None, None)
+        }
+    }
+}
+
+impl ResolutionPolicy {
+    /// Create a policy with no restrictions.
+    pub fn new() -> ResolutionPolicy {
+        ResolutionPolicy::default()
+    }
+
+    /// Only load documents whose URI has one of the given schemes, such as
+    /// `https` or `file`.
+    pub fn with_allowed_schemes(mut self, schemes: &[&str]) -> Self {
+        self.schemes = Some(schemes.iter().map(|x| x.to_ascii_lowercase()).collect());
+        self
+    }
+
+    /// Only load documents from the allowed hosts. A host starting with
+    /// `*.`, such as `*.example.com`, allows all of the subdomains of the
+    /// rest.
+    pub fn allow_host(mut self, host: &str) -> Self {
+        self.allowed_hosts
+            .get_or_insert_with(Vec::new)
+            .push(host.to_ascii_lowercase());
+        self
+    }
+
+    /// Never load documents from a host, even if it is allowed. A host
+    /// starting with `*.` denies all of the subdomains of the rest.
+    pub fn deny_host(mut self, host: &str) -> Self {
+        self.denied_hosts.push(host.to_ascii_lowercase());
+        self
+    }
+
+    /// Only load `file:` documents from within the given directory. Symbolic
+    /// links are followed before checking.
+    pub fn with_fs_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.fs_root = Some(root.into());
+        self
+    }
+
+    /// Only accept documents of up to the given size, in bytes. The policy is
+    /// passed to [`crate::Retriever::retrieve_with_policy`], so that the
+    /// retriever can refuse a document before reading all of it. As a
+    /// retriever may not check the size, documents are checked again once
+    /// loaded, by their size as compact JSON, which may be smaller than the
+    /// size that the retriever read.
+    pub fn with_max_document_size(mut self, size: usize) -> Self {
+        self.max_document_size = Some(size);
+        self
+    }
+
+    /// Only load up to the given number of documents.
+    pub fn with_max_documents(mut self, count: usize) -> Self {
+        self.max_documents = Some(count);
+        self
+    }
+
+    /// Check whether another document may be loaded, given the number of
+    /// documents loaded, or being loaded, already.
+    pub(crate) fn check_count(&self, loaded: usize) -> Result<(), PolicyViolation> {
+        match self.max_documents {
+            Some(max) if loaded >= max => Err(PolicyViolation::TooManyDocuments(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check whether a document may be loaded from a URL, by its scheme, host
+    /// and path, but not by the number of documents loaded already.
+    ///
+    /// The `Config` checks the URL of each reference before retrieving it,
+    /// so a [`crate::Retriever`] only needs to check the URLs that it is
+    /// redirected to.
+    pub fn check_location(&self, url: &url::Url) -> Result<(), PolicyViolation> {
+        if let Some(schemes) = &self.schemes {
+            if !schemes.iter().any(|x| x == url.scheme()) {
+                return Err(PolicyViolation::SchemeNotAllowed(url.scheme().to_string()));
+            }
+        }
+        if let Some(host) = url.host_str() {
+            let host = host.to_ascii_lowercase();
+            if self.denied_hosts.iter().any(|x| host_matches(x, &host)) {
+                return Err(PolicyViolation::HostDenied(host));
+            }
+            if let Some(allowed) = &self.allowed_hosts {
+                if !allowed.iter().any(|x| host_matches(x, &host)) {
+                    return Err(PolicyViolation::HostNotAllowed(host));
+                }
+            }
+        }
+        if let (Some(root), "file") = (&self.fs_root, url.scheme()) {
+            let path = url
+                .to_file_path()
+                .map_err(|_| PolicyViolation::OutsideRoot(PathBuf::from(url.path())))?;
+            if !is_within(root, &path) {
+                return Err(PolicyViolation::OutsideRoot(path));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the maximum size of a document, in bytes, if there is one.
+    pub fn max_document_size(&self) -> Option<usize> {
+        self.max_document_size
+    }
+
+    /// Check whether a loaded document of the given size may be used.
+    pub(crate) fn check_size(&self, size: usize) -> Result<(), PolicyViolation> {
+        match self.max_document_size {
+            Some(max) if size > max => Err(PolicyViolation::DocumentTooLarge { size, max }),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => matches!(host.strip_suffix(domain), Some(x) if x.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+/// Whether a path is within a directory, once both have their symbolic links
+/// resolved, as far as they exist.
+fn is_within(root: &Path, path: &Path) -> bool {
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    path.starts_with(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn check(policy: &ResolutionPolicy, url: &str) -> Result<(), PolicyViolation> {
+        policy.check_location(&url::Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = ResolutionPolicy::new()
+            .with_allowed_schemes(&["https", "file"])
+            .allow_host("*.example.com")
+            .allow_host("example.org")
+            .deny_host("internal.example.com");
+        assert_eq!(check(&policy, "https://schemas.example.com/a.json"), Ok(()));
+        assert_eq!(check(&policy, "https://EXAMPLE.org/a.json"), Ok(()));
+        assert_eq!(
+            check(&policy, "http://schemas.example.com/a.json"),
+            Err(PolicyViolation::SchemeNotAllowed("http".to_string()))
+        );
+        assert_eq!(
+            check(&policy, "https://example.com/a.json"),
+            Err(PolicyViolation::HostNotAllowed("example.com".to_string()))
+        );
+        assert_eq!(
+            check(&policy, "https://badexample.com/a.json"),
+            Err(PolicyViolation::HostNotAllowed(
+                "badexample.com".to_string()
+            ))
+        );
+        assert_eq!(
+            check(&policy, "https://internal.example.com/a.json"),
+            Err(PolicyViolation::HostDenied(
+                "internal.example.com".to_string()
+            ))
+        );
+
+        let policy = ResolutionPolicy::new()
+            .with_fs_root("/srv/schemas")
+            .with_max_documents(1)
+            .with_max_document_size(10);
+        assert_eq!(check(&policy, "file:///srv/schemas/a/b.json"), Ok(()));
+        assert_eq!(
+            check(&policy, "file:///srv/schemas-other/b.json"),
+            Err(PolicyViolation::OutsideRoot(PathBuf::from(
+                "/srv/schemas-other/b.json"
+            )))
+        );
+        assert_eq!(
+            check(&policy, "file:///etc/passwd"),
+            Err(PolicyViolation::OutsideRoot(PathBuf::from("/etc/passwd")))
+        );
+        assert_eq!(policy.check_count(0), Ok(()));
+        assert_eq!(
+            policy.check_count(1),
+            Err(PolicyViolation::TooManyDocuments(1))
+        );
+        assert_eq!(policy.check_size(10), Ok(()));
+        assert_eq!(
+            policy.check_size(11),
+            Err(PolicyViolation::DocumentTooLarge { size: 11, max: 10 })
+        );
+    }
+
+    #[test]
+    fn test_policy_violations() {
+        let schema = json!({
+            "properties": {
+                "a": {"$ref": "https://example.com/a.json"},
+                "b": {"$ref": "https://example.com/b.json"},
+                "big": {"$ref": "https://example.com/big.json"}
+            }
+        });
+        let retrieved = Arc::new(AtomicUsize::new(0));
+        let counter = retrieved.clone();
+        let cfg = Config::from_schema(&schema, None)
+            .unwrap()
+            .with_retriever(move |uri: &str| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                match uri {
+                    "https://example.com/big.json" => Ok(json!({"enum": vec![0; 100]})),
+                    _ => Ok(json!({"type": "integer"})),
+                }
+            })
+            .with_policy(
+                ResolutionPolicy::new()
+                    .with_max_documents(1)
+                    .with_max_document_size(100),
+            );
+        let violations = |instance| -> Vec<Option<PolicyViolation>> {
+            match cfg.validate(&instance) {
+                Ok(()) => Vec::new(),
+                Err(errors) => errors.map(|x| x.policy_violation).collect(),
+            }
+        };
+        assert_eq!(
+            violations(json!({"big": 0})),
+            vec![Some(PolicyViolation::DocumentTooLarge {
+                size: 210,
+                max: 100
+            })]
+        );
+        // The refusal is remembered, and the document isn't retrieved again.
+        assert_eq!(violations(json!({"big": 0})).len(), 1);
+        assert_eq!(retrieved.load(Ordering::SeqCst), 1);
+        assert_eq!(violations(json!({"a": 1})), vec![]);
+        assert_eq!(violations(json!({"a": "one"})), vec![None]);
+        assert_eq!(
+            violations(json!({"b": 1})),
+            vec![Some(PolicyViolation::TooManyDocuments(1))]
+        );
+    }
+
+    #[test]
+    fn test_max_documents_threaded() {
+        let schema = json!({
+            "properties": (0..8)
+                .map(|i| (i.to_string(), json!({"$ref": format!("https://example.com/{}.json", i)})))
+                .collect::<serde_json::Map<_, _>>()
+        });
+        let retrieved = Arc::new(AtomicUsize::new(0));
+        let counter = retrieved.clone();
+        let cfg = Config::from_schema(&schema, None)
+            .unwrap()
+            .with_retriever(move |_: &str| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                // Retrieving slowly leaves time for the other threads to
+                // start retrieving too.
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                Ok(json!({}))
+            })
+            .with_policy(ResolutionPolicy::new().with_max_documents(2));
+        let refused = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|i| {
+                    let cfg = &cfg;
+                    scope.spawn(move || cfg.validate(&json!({i.to_string(): 0})).is_err())
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|x| x.join().unwrap())
+                .filter(|refused| *refused)
+                .count()
+        });
+        assert_eq!(retrieved.load(Ordering::SeqCst), 2);
+        assert_eq!(refused, 6);
+    }
+}
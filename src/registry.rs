@@ -0,0 +1,505 @@
+//! A store of schema documents that refer to each other.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::error::ValidationError;
+use crate::resolver::{self, Resolver, SharedIndex};
+use crate::schemas::{self, Draft};
+use crate::validators;
+
+/// A schema document in a [`Registry`].
+#[derive(Debug, Clone)]
+struct Document {
+    /// The URI the document is known by, without a fragment.
+    uri: String,
+    /// The URI of the document's own id, if it has one.
+    id: Option<String>,
+    value: Value,
+    draft: Draft,
+}
+
+/// The index of the documents in a [`Registry`], which the configs made from
+/// it share.
+#[derive(Debug)]
+struct Index {
+    shared: SharedIndex,
+    /// The error found with each document as the root schema, such as a
+    /// reference cycle, if any.
+    errors: Vec<Option<ValidationError>>,
+}
+
+/// A set of schema documents that refer to each other, by their URIs or by the
+/// ids and anchors within them.
+///
+/// The ids of the documents are checked as they are added, and the documents
+/// are indexed once they are all added, when the first [`Config`] is made.
+/// A `Config` can be made with any of them as the root schema, in which
+/// references to the others resolve without retrieving anything. The index
+/// is shared by all of the configs, and by the clones of the registry until
+/// they are changed.
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::json;
+/// # use jsonschema_valid::Registry;
+/// let mut registry = Registry::new();
+/// registry.add(json!({
+///     "$id": "https://example.com/address.json",
+///     "properties": {"zip": {"$ref": "#zip"}},
+///     "$defs": {"zip": {"$anchor": "zip", "pattern": "^[0-9]{5}$"}},
+///     "$schema": "https://json-schema.org/draft/2020-12/schema"
+/// }))?;
+/// registry.add(json!({
+///     "$id": "https://example.com/person.json",
+///     "properties": {"address": {"$ref": "address.json"}}
+/// }))?;
+///
+/// let cfg = registry.config("https://example.com/person.json", None)?;
+/// assert!(cfg.validate(&json!({"address": {"zip": "12345"}})).is_ok());
+/// assert!(cfg.validate(&json!({"address": {"zip": "1234"}})).is_err());
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    documents: Vec<Document>,
+    /// The document that each URI, id and anchor is found in.
+    ids: HashMap<String, usize>,
+    /// The index of the documents, made when first needed.
+    index: OnceLock<Result<Arc<Index>, ValidationError>>,
+}
+
+impl Registry {
+    /// Create an empty registry.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Add a document, which is known by its own absolute id.
+    ///
+    /// The draft of the document is given by its `$schema`, or is Draft 7.
+    /// It is an error for the document not to have an absolute id, or for it
+    /// to have an id or anchor that another document has.
+    pub fn add(&mut self, document: Value) -> Result<(), ValidationError> {
+        let draft = schemas::draft_from_schema(&document).unwrap_or(Draft::Draft7);
+        let id = resolver::id_of(draft, &document)
+            .and_then(|id| url::Url::parse(id).ok())
+            .ok_or_else(|| {
+                ValidationError::new("A document must have an absolute id", None, None)
+            })?;
+        self.add_document(id, document, draft)
+    }
+
+    /// Add a document that is known by the given absolute URI, against
+    /// which its own id, if any, and its relative references are resolved.
+    ///
+    /// As with [`Registry::add`], it is an error for the document to have an
+    /// id or anchor that another document has.
+    pub fn add_with_uri(&mut self, uri: &str, document: Value) -> Result<(), ValidationError> {
+        let draft = schemas::draft_from_schema(&document).unwrap_or(Draft::Draft7);
+        self.add_document(url::Url::parse(uri)?, document, draft)
+    }
+
+    fn add_document(
+        &mut self,
+        uri: url::Url,
+        document: Value,
+        draft: Draft,
+    ) -> Result<(), ValidationError> {
+        let uri = resolver::normalize(uri);
+        let id = match resolver::id_of(draft, &document) {
+            Some(id) => Some(resolver::normalize(uri.join(id)?).to_string()),
+            None => None,
+        };
+        let mut ids = resolver::ids_of(draft, &document, &uri)?;
+        ids.push(uri.to_string());
+        ids.sort();
+        ids.dedup();
+        if let Some(id) = ids.iter().find(|id| self.ids.contains_key(id.as_str())) {
+            return Err(ValidationError::new(
+                &format!("{} is already in the registry", id),
+                None,
+                None,
+            ));
+        }
+        let index = self.documents.len();
+        self.ids.extend(ids.into_iter().map(|id| (id, index)));
+        self.documents.push(Document {
+            uri: uri.to_string(),
+            id,
+            value: document,
+            draft,
+        });
+        self.index = OnceLock::new();
+        Ok(())
+    }
+
+    /// Get the index of the documents, making it if needed.
+    fn index(&self) -> Result<&Index, ValidationError> {
+        match self.index.get_or_init(|| self.make_index().map(Arc::new)) {
+            Ok(index) => Ok(index),
+            Err(err) => Err(err.clone()),
+        }
+    }
+
+    fn make_index(&self) -> Result<Index, ValidationError> {
+        let documents: Vec<(Draft, &Value, &str)> = self
+            .documents
+            .iter()
+            .map(|x| (x.draft, &x.value, x.uri.as_str()))
+            .collect();
+        let shared = SharedIndex::new(&documents)?;
+        let errors = self
+            .documents
+            .iter()
+            .map(|root| {
+                self.make_config(&shared, root, root.draft)
+                    .and_then(|cfg| validators::check_reference_cycles(&cfg))
+                    .err()
+            })
+            .collect();
+        Ok(Index { shared, errors })
+    }
+
+    /// Make a [`Config`] with a document as its root schema, evaluated with
+    /// the given draft, that looks the others up in the shared index.
+    fn make_config<'a>(
+        &'a self,
+        shared: &'a SharedIndex,
+        root: &'a Document,
+        draft: Draft,
+    ) -> Result<Config<'a>, ValidationError> {
+        let documents = self.documents.iter().map(|x| &x.value).collect();
+        let mut resolver = Resolver::from_shared_index(shared, documents, &root.uri)?;
+        if draft != root.draft {
+            // The shared index has the document as written in its own draft.
+            resolver.add_document(draft, &root.value, &root.uri)?;
+        }
+        Config::from_resolver(&root.value, draft, false, resolver)
+    }
+
+    /// Get the document known by a URI, or by an id or anchor within it.
+    pub fn get(&self, uri: &str) -> Option<&Value> {
+        let uri = resolver::normalize(url::Url::parse(uri).ok()?);
+        self.ids
+            .get(uri.as_str())
+            .map(|x| &self.documents[*x].value)
+    }
+
+    /// Make a [`Config`] with the document known by `uri`, or by its own id,
+    /// as its root schema.
+    ///
+    /// As with [`Config::from_schema`], the draft is given by `draft`, or
+    /// else by the `$schema` of the document. References to the other
+    /// documents in the registry resolve to them, and each of them is
+    /// evaluated with the draft of its own `$schema`.
+    pub fn config(&self, uri: &str, draft: Option<Draft>) -> Result<Config<'_>, ValidationError> {
+        let normalized = resolver::normalize(url::Url::parse(uri)?).to_string();
+        let position = self
+            .documents
+            .iter()
+            .position(|x| x.uri == normalized || x.id.as_ref() == Some(&normalized))
+            .ok_or_else(|| {
+                ValidationError::new(&format!("No document {} in the registry", uri), None, None)
+            })?;
+        let root = &self.documents[position];
+        let draft = draft.unwrap_or(root.draft);
+        let index = self.index()?;
+        if draft != root.draft {
+            let cfg = self.make_config(&index.shared, root, draft)?;
+            validators::check_reference_cycles(&cfg)?;
+            return Ok(cfg);
+        }
+        if let Some(err) = &index.errors[position] {
+            return Err(err.clone());
+        }
+        self.make_config(&index.shared, root, draft)
+    }
+}
+
+/// A [`Registry`] shared between threads, whose documents can be replaced
+/// while it is in use.
+///
+/// Validation works on a snapshot of the registry, which stays the same for
+/// as long as it is held, even if the shared registry is changed meanwhile.
+/// Changes are made to a copy of the current registry, which then replaces it
+/// for all of the snapshots taken afterwards. Taking a snapshot only briefly
+/// locks the shared registry, and doesn't wait for changes to be made.
+///
+/// Cloning a `SharedRegistry` gives another handle to the same registry.
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::json;
+/// # use jsonschema_valid::{Registry, SharedRegistry};
+/// let shared = SharedRegistry::new(Registry::new());
+/// shared.update(|registry| {
+///     registry.add(json!({"$id": "https://example.com/id.json", "type": "string"}))
+/// })?;
+///
+/// let snapshot = shared.snapshot();
+/// let cfg = snapshot.config("https://example.com/id.json", None)?;
+///
+/// let mut registry = Registry::new();
+/// registry.add(json!({"$id": "https://example.com/id.json", "type": "integer"}))?;
+/// shared.replace(registry);
+///
+/// // The snapshot taken before is unchanged.
+/// assert!(cfg.validate(&json!("a")).is_ok());
+/// let snapshot = shared.snapshot();
+/// let cfg = snapshot.config("https://example.com/id.json", None)?;
+/// assert!(cfg.validate(&json!("a")).is_err());
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SharedRegistry {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    current: RwLock<Arc<Registry>>,
+    /// Held while changing the registry, so that concurrent changes aren't
+    /// lost.
+    writer: Mutex<()>,
+}
+
+impl SharedRegistry {
+    /// Share a registry.
+    pub fn new(registry: Registry) -> SharedRegistry {
+        SharedRegistry {
+            shared: Arc::new(Shared {
+                current: RwLock::new(Arc::new(registry)),
+                writer: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Get the current registry, which stays the same for as long as it is
+    /// held.
+    pub fn snapshot(&self) -> Arc<Registry> {
+        // The lock only guards swapping an `Arc`, which can't be left half
+        // done, so a poisoned lock is still good to use.
+        self.shared
+            .current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replace the registry, returning the previous one.
+    pub fn replace(&self, registry: Registry) -> Arc<Registry> {
+        let _writer = self
+            .shared
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.swap(Arc::new(registry))
+    }
+
+    /// Change a copy of the registry with `change`, such as by adding
+    /// documents to it, and then replace the registry with it. If `change`
+    /// fails, the registry is left as it was.
+    pub fn update<F>(&self, change: F) -> Result<(), ValidationError>
+    where
+        F: FnOnce(&mut Registry) -> Result<(), ValidationError>,
+    {
+        let _writer = self
+            .shared
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut registry = Registry::clone(&self.snapshot());
+        change(&mut registry)?;
+        self.swap(Arc::new(registry));
+        Ok(())
+    }
+
+    fn swap(&self, registry: Arc<Registry>) -> Arc<Registry> {
+        let mut current = self
+            .shared
+            .current
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = Registry::new();
+        registry
+            .add(json!({
+                "$id": "https://example.com/schemas/common.json",
+                "definitions": {
+                    "positive": {"type": "integer", "minimum": 1},
+                    "name": {"$id": "name.json", "type": "string"}
+                }
+            }))
+            .unwrap();
+        registry
+            .add_with_uri(
+                "https://example.com/schemas/order.json",
+                json!({
+                    "properties": {
+                        "quantity": {"$ref": "common.json#/definitions/positive"},
+                        "customer": {"$ref": "name.json"},
+                        "items": {"items": {"$ref": "#"}}
+                    }
+                }),
+            )
+            .unwrap();
+
+        let cfg = registry
+            .config("https://example.com/schemas/order.json", None)
+            .unwrap();
+        assert!(cfg
+            .validate(&json!({"quantity": 1, "customer": "x", "items": [{"quantity": 2}]}))
+            .is_ok());
+        assert!(cfg.validate(&json!({"quantity": 0})).is_err());
+        assert!(cfg.validate(&json!({"customer": 1})).is_err());
+        assert!(cfg.validate(&json!({"items": [{"quantity": 0}]})).is_err());
+        drop(cfg);
+
+        // Only whole documents can be the root.
+        assert!(registry
+            .config("https://example.com/schemas/name.json", None)
+            .is_err());
+        assert!(registry
+            .config("https://example.com/schemas/common.json#", None)
+            .is_ok());
+        assert_eq!(
+            registry.get("https://example.com/schemas/name.json#"),
+            registry.get("https://example.com/schemas/common.json")
+        );
+
+        assert!(registry.add(json!({"type": "string"})).is_err());
+        assert!(registry
+            .add(json!({"$id": "https://example.com/schemas/name.json"}))
+            .is_err());
+        assert!(registry
+            .config("https://example.com/schemas/missing.json", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_registry_index() {
+        let mut registry = Registry::new();
+        registry
+            .add(json!({
+                "$id": "https://example.com/a.json",
+                "$schema": "http://json-schema.org/draft-04/schema#",
+                "properties": {"e": {"$ref": "e.json"}}
+            }))
+            .unwrap();
+        registry
+            .add(json!({"$id": "https://example.com/e.json", "type": "string"}))
+            .unwrap();
+        registry
+            .add(json!({
+                "$id": "https://example.com/b.json",
+                "allOf": [{"$ref": "c.json"}]
+            }))
+            .unwrap();
+        registry
+            .add_with_uri("https://example.com/c.json", json!({"$ref": "b.json"}))
+            .unwrap();
+
+        // The index is made once, and shared with clones.
+        assert!(registry.config("https://example.com/a.json", None).is_ok());
+        let index = registry.index.get().unwrap().as_ref().unwrap().clone();
+        assert!(registry.config("https://example.com/a.json", None).is_ok());
+        let clone = registry.clone();
+        assert!(clone.config("https://example.com/a.json", None).is_ok());
+        assert!(Arc::ptr_eq(
+            &index,
+            clone.index.get().unwrap().as_ref().unwrap()
+        ));
+
+        // The documents referring to each other in a cycle can't be the root.
+        let err = registry
+            .config("https://example.com/b.json", None)
+            .err()
+            .unwrap();
+        assert!(err.msg.contains("c.json"));
+        assert!(registry
+            .config("https://example.com/c.json", Some(Draft::Draft4))
+            .is_err());
+
+        // The root document can be evaluated with another draft.
+        let cfg = registry
+            .config("https://example.com/a.json", Some(Draft::Draft7))
+            .unwrap();
+        assert_eq!(cfg.get_draft_number(), 7);
+        assert!(cfg.validate(&json!({"e": "x"})).is_ok());
+        assert!(cfg.validate(&json!({"e": 1})).is_err());
+        drop(cfg);
+
+        registry
+            .add(json!({"$id": "https://example.com/d.json"}))
+            .unwrap();
+        assert!(registry.index.get().is_none());
+    }
+
+    #[test]
+    fn test_shared_registry() {
+        fn version(n: u64) -> Registry {
+            let mut registry = Registry::new();
+            registry
+                .add(json!({"$id": "https://example.com/v.json", "const": n}))
+                .unwrap();
+            registry
+        }
+
+        let shared = SharedRegistry::new(version(0));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        let snapshot = shared.snapshot();
+                        let cfg = snapshot.config("https://example.com/v.json", None).unwrap();
+                        let n =
+                            snapshot.get("https://example.com/v.json").unwrap()["const"].clone();
+                        // A snapshot is consistent, however often the
+                        // registry is replaced meanwhile.
+                        for _ in 0..10 {
+                            assert!(cfg.validate(&n).is_ok());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for n in 1..=50 {
+            shared.replace(version(n));
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert!(shared
+            .update(|registry| registry.add(json!({"$id": "https://example.com/v.json"})))
+            .is_err());
+        shared
+            .update(|registry| registry.add(json!({"$id": "https://example.com/w.json"})))
+            .unwrap();
+        let snapshot = shared.snapshot();
+        assert_eq!(
+            snapshot.get("https://example.com/v.json").unwrap()["const"],
+            50
+        );
+        assert!(snapshot.get("https://example.com/w.json").is_some());
+    }
+}
@@ -1,20 +1,34 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::iter;
+use std::sync::{PoisonError, RwLock};
 
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 use crate::context::Context;
 use crate::error::ValidationError;
+use crate::policy::{PolicyViolation, ResolutionPolicy};
+use crate::retriever::{RetrievedDocuments, Retriever};
 use crate::schemas::{self, Draft};
 // TODO: Make the choice of resolver dynamic
 
-const DOCUMENT_PROTOCOL: &str = "document:///";
+pub(crate) const DOCUMENT_PROTOCOL: &str = "document:///";
 
-fn id_of(draft: Draft, schema: &Value) -> Option<&str> {
+/// Get the id of a schema, which sets its base URL. Before Draft 2019-09, a
+/// `$ref` overrides all of its siblings, including the id.
+pub(crate) fn id_of(draft: Draft, schema: &Value) -> Option<&str> {
     if let Value::Object(object) = schema {
-        if draft == Draft::Draft4 {
-            object.get("$id").or_else(|| object.get("id"))
-        } else {
-            object.get("$id")
+        if draft.ref_overrides_siblings() && object.contains_key("$ref") {
+            return None;
+        }
+        match draft.builtin() {
+            Draft::Draft3 => object.get("id"),
+            Draft::Draft4 | Draft::OpenApi30 | Draft::Kubernetes => {
+                object.get("$id").or_else(|| object.get("id"))
+            }
+            // MongoDB has no ids, as it has no `$ref`.
+            Draft::MongoDb => None,
+            _ => object.get("$id"),
         }
         .and_then(Value::as_str)
     } else {
@@ -22,75 +36,505 @@ fn id_of(draft: Draft, schema: &Value) -> Option<&str> {
     }
 }
 
+/// Get the draft of a schema, which is `draft` unless the schema is an
+/// embedded resource that declares a different one with `$schema`.
+pub(crate) fn draft_of(draft: Draft, schema: &Value) -> Draft {
+    match schemas::draft_from_schema(schema) {
+        Some(own) if own != draft && id_of(own, schema).is_some() => own,
+        _ => draft,
+    }
+}
+
+/// Get the location-independent identifiers of a schema, if the draft supports
+/// `$anchor`. From Draft 2020-12, a `$dynamicAnchor` can also be referenced as
+/// a plain `$anchor`.
+fn anchors_of(draft: Draft, schema: &Value) -> impl Iterator<Item = &str> {
+    let keywords: &[&str] = match draft.get_draft_number() {
+        0..=7 => &[],
+        8 => &["$anchor"],
+        _ => &["$anchor", "$dynamicAnchor"],
+    };
+    keywords
+        .iter()
+        .filter_map(move |keyword| schema.get(keyword).and_then(Value::as_str))
+}
+
+/// Get the `$dynamicAnchor` of a schema, if the draft supports it.
+pub(crate) fn dynamic_anchor_of(draft: Draft, schema: &Value) -> Option<&str> {
+    if draft.get_draft_number() >= 9 {
+        schema.get("$dynamicAnchor").and_then(Value::as_str)
+    } else {
+        None
+    }
+}
+
+/// Remove the empty fragment from a URL, so that `http://x/y#` and `http://x/y`
+/// refer to the same resource.
+pub(crate) fn normalize(mut url: url::Url) -> url::Url {
+    if url.fragment() == Some("") {
+        url.set_fragment(None);
+    }
+    url
+}
+
+/// Iterate over the immediate subschemas of a schema object. Only the values
+/// of keywords that take schemas are visited: the values of other keywords,
+/// such as `enum` or `const`, may look like schemas, but their ids must not be
+/// treated as real identifiers.
+fn subschemas<'a>(object: &'a Map<String, Value>) -> impl Iterator<Item = &'a Value> + 'a {
+    subschemas_with_paths(object).map(|(_, subschema)| subschema)
+}
+
+/// Whether the value of a keyword is a schema, or an array of schemas.
+pub(crate) fn takes_schema(keyword: &str) -> bool {
+    matches!(
+        keyword,
+        "additionalItems"
+            | "additionalProperties"
+            | "allOf"
+            | "anyOf"
+            | "contains"
+            | "contentSchema"
+            | "else"
+            | "extends"
+            | "if"
+            | "items"
+            | "not"
+            | "oneOf"
+            | "prefixItems"
+            | "propertyNames"
+            | "then"
+            | "unevaluatedItems"
+            | "unevaluatedProperties"
+    )
+}
+
+/// Whether the value of a keyword is an object of schemas, by name.
+pub(crate) fn takes_named_schemas(keyword: &str) -> bool {
+    matches!(
+        keyword,
+        "$defs"
+            | "definitions"
+            | "dependencies"
+            | "dependentSchemas"
+            | "patternProperties"
+            | "properties"
+    )
+}
+
+/// Iterate over the immediate subschemas of a schema object, along with their
+/// paths relative to it.
+pub(crate) fn subschemas_with_paths<'a>(
+    object: &'a Map<String, Value>,
+) -> impl Iterator<Item = (Vec<String>, &'a Value)> + 'a {
+    object.iter().flat_map(
+        |(k, v)| -> Box<dyn Iterator<Item = (Vec<String>, &'a Value)> + 'a> {
+            match v {
+                Value::Array(array) if takes_schema(k) => Box::new(
+                    array
+                        .iter()
+                        .enumerate()
+                        .map(move |(i, x)| (vec![k.clone(), i.to_string()], x)),
+                ),
+                _ if takes_schema(k) => Box::new(iter::once((vec![k.clone()], v))),
+                Value::Object(map) if takes_named_schemas(k) => Box::new(
+                    map.iter()
+                        .map(move |(name, x)| (vec![k.clone(), name.clone()], x)),
+                ),
+                _ => Box::new(iter::empty()),
+            }
+        },
+    )
+}
+
+/// What a reference resolves to: the base URL of the schema, the schema
+/// resource that contains it, and the schema itself.
+type Resolved<'a> = (url::Url, &'a Value, &'a Value);
+
+/// The absolute URLs of the references resolved against one base, with their
+/// keys in the index, by the reference itself.
+type Joined = HashMap<String, (url::Url, String)>;
+
+/// An index of the schemas in a set of documents, as made by a `Resolver`
+/// that knows all of them. The schemas are referred to by the position of
+/// their document and their JSON pointers within it, rather than borrowed,
+/// so that the index can be kept beside the documents and shared by the
+/// resolvers for each of them.
+#[derive(Debug, Default)]
+pub(crate) struct SharedIndex {
+    /// The base URL, document, resource pointer and schema pointer of each
+    /// entry, by key.
+    entries: HashMap<String, (url::Url, usize, String, String)>,
+}
+
+impl SharedIndex {
+    /// Index a set of documents, each with its draft and the base URL it is
+    /// known by. The ids of the earlier documents take precedence.
+    pub(crate) fn new(documents: &[(Draft, &Value, &str)]) -> Result<SharedIndex, ValidationError> {
+        let mut resolver = Resolver::new(url::Url::parse(DOCUMENT_PROTOCOL)?);
+        let mut locations = HashMap::new();
+        for (position, (draft, document, base_url)) in documents.iter().enumerate() {
+            resolver.add_document(*draft, document, base_url)?;
+            locate(document, position, &mut String::new(), &mut locations);
+        }
+        let entries = resolver
+            .index
+            .into_iter()
+            .filter_map(|(key, (url, resource, schema))| {
+                let (position, resource) = locations.get(&(resource as *const Value))?;
+                let (_, schema) = locations.get(&(schema as *const Value))?;
+                Some((key, (url, *position, resource.clone(), schema.clone())))
+            })
+            .collect();
+        Ok(SharedIndex { entries })
+    }
+}
+
+/// Record the position of the document and the JSON pointer of `value` and
+/// each value within it, by their address.
+fn locate(
+    value: &Value,
+    position: usize,
+    pointer: &mut String,
+    locations: &mut HashMap<*const Value, (usize, String)>,
+) {
+    locations.insert(value as *const Value, (position, pointer.clone()));
+    let len = pointer.len();
+    match value {
+        Value::Object(object) => {
+            for (key, x) in object {
+                pointer.push('/');
+                pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                locate(x, position, pointer, locations);
+                pointer.truncate(len);
+            }
+        }
+        Value::Array(array) => {
+            for (i, x) in array.iter().enumerate() {
+                pointer.push('/');
+                pointer.push_str(&i.to_string());
+                locate(x, position, pointer, locations);
+                pointer.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
 pub struct Resolver<'a> {
     base_url: String,
-    id_mapping: HashMap<String, &'a Value>,
+    /// The schemas in the documents known up front, by absolute URL: their
+    /// ids and anchors, then each document and subschema by the URL of its
+    /// document or resource with a JSON pointer as the fragment.
+    index: HashMap<String, Resolved<'a>>,
+    /// An index of other documents, looked up after the resolver's own, with
+    /// the documents it refers to.
+    shared: Option<(&'a SharedIndex, Vec<&'a Value>)>,
+    /// The references resolved so far, by the base they were resolved
+    /// against, as made by `join_memoized`.
+    joined: RwLock<HashMap<String, Joined>>,
+    retriever: Option<Box<dyn Retriever + 'a>>,
+    retrieved: RetrievedDocuments,
+    policy: ResolutionPolicy,
+    /// The documents that the policy refused, by URL, so that they aren't
+    /// retrieved again.
+    refused: RwLock<HashMap<String, PolicyViolation>>,
+}
+
+/// Get the key that the schema at a URL is indexed by: the URL without its
+/// fragment, followed by the fragment percent-decoded, so that the different
+/// spellings of the same anchor or JSON pointer have the same key.
+fn index_key(url: &url::Url) -> String {
+    let mut resource = url.clone();
+    resource.set_fragment(None);
+    match url.fragment() {
+        Some(fragment) if !fragment.is_empty() => format!(
+            "{}#{}",
+            resource,
+            percent_encoding::percent_decode_str(fragment).decode_utf8_lossy()
+        ),
+        _ => resource.to_string(),
+    }
+}
+
+/// Get the keys of the ids and anchors within a document at the given base
+/// URL.
+pub(crate) fn ids_of(
+    draft: Draft,
+    document: &Value,
+    base_url: &url::Url,
+) -> Result<Vec<String>, ValidationError> {
+    Ok(collect_ids(draft, document, base_url)?
+        .into_iter()
+        .map(|(key, _, _)| key)
+        .collect())
+}
+
+/// Get the keys of the ids and anchors within a document at the given base
+/// URL, along with the schema resource containing each one and the schema
+/// itself. It is an error for an anchor to be defined by more than one
+/// schema within the same resource.
+fn collect_ids<'a>(
+    draft: Draft,
+    document: &'a Value,
+    base_url: &url::Url,
+) -> Result<Vec<(String, &'a Value, &'a Value)>, ValidationError> {
+    let mut ids: Vec<(String, &'a Value, &'a Value)> = Vec::new();
+    let mut anchors: HashMap<String, &'a Value> = HashMap::new();
+    let mut duplicate = None;
+    find_ids(
+        draft,
+        document,
+        document,
+        base_url,
+        &mut |key, resource, x| {
+            if key.contains('#') {
+                match anchors.get(&key) {
+                    Some(other) if !std::ptr::eq(*other, x) => {
+                        duplicate = Some(ValidationError::new(
+                            &format!("Duplicate anchor {}", key),
+                            None,
+                            Some(x),
+                        ));
+                        return true;
+                    }
+                    Some(_) => return false,
+                    None => {
+                        anchors.insert(key.clone(), x);
+                    }
+                }
+            }
+            ids.push((key, resource, x));
+            false
+        },
+    )?;
+    match duplicate {
+        Some(err) => Err(err),
+        None => Ok(ids),
+    }
 }
 
 /// Iterate through all of the document fragments with an assigned id, calling a
-/// callback at each location.
+/// callback at each location. The callback is given the key of the id or
+/// anchor, as made by `index_key`, the schema resource containing the
+/// fragment, and the fragment itself.
 fn find_ids<'a, F>(
     draft: Draft,
     schema: &'a Value,
+    resource: &'a Value,
     base_url: &url::Url,
     visitor: &mut F,
-) -> Result<Option<&'a Value>, ValidationError>
+) -> Result<Option<(&'a Value, &'a Value)>, ValidationError>
 where
-    F: FnMut(String, &'a Value) -> Option<&'a Value>,
+    F: FnMut(String, &'a Value, &'a Value) -> bool,
 {
-    match schema {
-        Value::Object(object) => {
-            if let Some(url) = id_of(draft, schema) {
-                let new_url = base_url.join(url)?;
-                if let Some(x) = visitor(new_url.to_string(), schema) {
-                    return Ok(Some(x));
-                }
-                for (_k, v) in object {
-                    let result = find_ids(draft, v, &new_url, visitor)?;
-                    if result.is_some() {
-                        return Ok(result);
-                    }
-                }
-            } else {
-                for (_k, v) in object {
-                    let result = find_ids(draft, v, base_url, visitor)?;
-                    if result.is_some() {
-                        return Ok(result);
-                    }
+    if let Value::Object(object) = schema {
+        let mut resource = resource;
+        let mut base_url = base_url.clone();
+        if let Some(id) = id_of(draft, schema) {
+            // Before Draft 2019-09, an id may have a plain-name fragment,
+            // which is an anchor within the resource the rest of it names.
+            let url = base_url.join(id)?;
+            base_url = url.clone();
+            base_url.set_fragment(None);
+            if !id.starts_with('#') {
+                resource = schema;
+                if visitor(base_url.to_string(), resource, schema) {
+                    return Ok(Some((resource, schema)));
                 }
             }
+            if matches!(url.fragment(), Some(x) if !x.is_empty())
+                && visitor(index_key(&url), resource, schema)
+            {
+                return Ok(Some((resource, schema)));
+            }
         }
-        Value::Array(array) => {
-            for v in array {
-                let result = find_ids(draft, v, base_url, visitor)?;
-                if result.is_some() {
-                    return Ok(result);
-                }
+        for anchor in anchors_of(draft, schema) {
+            if visitor(format!("{}#{}", base_url, anchor), resource, schema) {
+                return Ok(Some((resource, schema)));
+            }
+        }
+        for subschema in subschemas(object) {
+            let draft = draft_of(draft, subschema);
+            let result = find_ids(draft, subschema, resource, &base_url, visitor)?;
+            if result.is_some() {
+                return Ok(result);
             }
         }
-        _ => {}
     }
     Ok(None)
 }
 
 impl<'a> Resolver<'a> {
     pub fn from_schema(draft: Draft, schema: &'a Value) -> Result<Resolver<'a>, ValidationError> {
-        let base_url = match id_of(draft, schema) {
-            Some(url) => url.to_string(),
-            None => DOCUMENT_PROTOCOL.to_string(),
+        Resolver::from_schema_with_base(draft, schema, DOCUMENT_PROTOCOL)
+    }
+
+    /// Create a resolver for a schema retrieved from the given base URL. The
+    /// schema's own id, if any, is resolved against it.
+    pub(crate) fn from_schema_with_base(
+        draft: Draft,
+        schema: &'a Value,
+        base_url: &str,
+    ) -> Result<Resolver<'a>, ValidationError> {
+        let base_url = normalize(url::Url::parse(base_url)?);
+        let mut resolver = Resolver::new(base_url.clone());
+        resolver.index_document(draft, schema, &base_url)?;
+        Ok(resolver)
+    }
+
+    /// Create a resolver for one of a set of documents, at the given base
+    /// URL, that looks schemas up in their shared index.
+    pub(crate) fn from_shared_index(
+        shared: &'a SharedIndex,
+        documents: Vec<&'a Value>,
+        base_url: &str,
+    ) -> Result<Resolver<'a>, ValidationError> {
+        let mut resolver = Resolver::new(normalize(url::Url::parse(base_url)?));
+        resolver.shared = Some((shared, documents));
+        Ok(resolver)
+    }
+
+    fn new(base_url: url::Url) -> Resolver<'a> {
+        Resolver {
+            base_url: base_url.to_string(),
+            index: HashMap::new(),
+            shared: None,
+            joined: RwLock::new(HashMap::new()),
+            retriever: None,
+            retrieved: RetrievedDocuments::default(),
+            policy: ResolutionPolicy::default(),
+            refused: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Look up the schema indexed by a key, in the resolver's own index and
+    /// then in the shared one.
+    fn lookup(&self, key: &str) -> Option<Resolved<'a>> {
+        if let Some(resolved) = self.index.get(key) {
+            return Some(resolved.clone());
+        }
+        let (shared, documents) = self.shared.as_ref()?;
+        let (url, position, resource, schema) = shared.entries.get(key)?;
+        let document = documents.get(*position)?;
+        Some((
+            url.clone(),
+            document.pointer(resource)?,
+            document.pointer(schema)?,
+        ))
+    }
+
+    /// Add the ids and anchors of a document at the given base URL to the
+    /// index, and then the JSON pointers to its subschemas, from the document
+    /// and from each schema resource within it. The entries already in the
+    /// index take precedence.
+    fn index_document(
+        &mut self,
+        draft: Draft,
+        document: &'a Value,
+        base_url: &url::Url,
+    ) -> Result<(), ValidationError> {
+        if base_url.as_str() != self.base_url {
+            self.index.entry(base_url.to_string()).or_insert((
+                base_url.clone(),
+                document,
+                document,
+            ));
+        }
+        let mut resources = vec![(base_url.clone(), document)];
+        for (key, resource, x) in collect_ids(draft, document, base_url)? {
+            let scope = url::Url::parse(key.split('#').next().unwrap_or(""))?;
+            if !key.contains('#') {
+                resources.push((scope.clone(), x));
+            }
+            self.index.entry(key).or_insert((scope, resource, x));
+        }
+
+        // The pointers are relative to the document that a URL without a
+        // fragment resolves to: the schema itself at its base URL, and
+        // otherwise a known metaschema or the first resource with that id.
+        for (url, resource) in resources {
+            let resolves_here = if url.as_str() == self.base_url {
+                base_url.as_str() == self.base_url && std::ptr::eq(resource, document)
+            } else {
+                matches!(self.resolve_known_url(&url, document), Some(x) if std::ptr::eq(x, resource))
+            };
+            if resolves_here {
+                self.index_pointers(draft, resource, resource, &url, &mut String::new());
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the JSON pointers to `schema` and its subschemas, relative to
+    /// `document` at the given URL, to the index.
+    fn index_pointers(
+        &mut self,
+        draft: Draft,
+        document: &'a Value,
+        schema: &'a Value,
+        url: &url::Url,
+        pointer: &mut String,
+    ) {
+        let key = if pointer.is_empty() {
+            url.to_string()
+        } else {
+            format!("{}#{}", url, pointer)
+        };
+        let resolved = match id_of(draft_of(draft, schema), schema) {
+            Some(id) if !id.starts_with('#') => url.join(id).ok().map(|mut scope| {
+                scope.set_fragment(None);
+                (scope, schema, schema)
+            }),
+            _ => Some((url.clone(), document, schema)),
         };
+        if let Some(resolved) = resolved {
+            self.index.entry(key).or_insert(resolved);
+        }
+        if let Value::Object(object) = schema {
+            for (path, subschema) in subschemas_with_paths(object) {
+                let len = pointer.len();
+                for segment in path {
+                    pointer.push('/');
+                    pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+                }
+                self.index_pointers(draft, document, subschema, url, pointer);
+                pointer.truncate(len);
+            }
+        }
+    }
 
-        let mut id_mapping: HashMap<String, &'a Value> = HashMap::new();
+    /// Set the retriever used to load the documents that aren't otherwise
+    /// known.
+    pub(crate) fn set_retriever(&mut self, retriever: Box<dyn Retriever + 'a>) {
+        self.retriever = Some(retriever);
+    }
 
-        find_ids(draft, schema, &url::Url::parse(&base_url)?, &mut |id, x| {
-            id_mapping.insert(id, x);
-            None
-        })?;
+    /// Set the policy that documents must meet to be retrieved.
+    pub(crate) fn set_policy(&mut self, policy: ResolutionPolicy) {
+        self.policy = policy;
+        self.refused
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
 
-        Ok(Resolver {
-            base_url,
-            id_mapping,
-        })
+    /// Index another document at the given base URL, so that references to
+    /// it, and to the ids and anchors within it, resolve. The ids of the
+    /// documents indexed before take precedence.
+    pub(crate) fn add_document(
+        &mut self,
+        draft: Draft,
+        document: &'a Value,
+        base_url: &str,
+    ) -> Result<(), ValidationError> {
+        let base_url = normalize(url::Url::parse(base_url)?);
+        self.index_document(draft, document, &base_url)?;
+        self.joined
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        Ok(())
     }
 
     pub fn join_url(
@@ -99,78 +543,268 @@ impl<'a> Resolver<'a> {
         url_ref: &str,
         ctx: &Context,
     ) -> Result<url::Url, ValidationError> {
-        let mut urls: Vec<&str> = vec![url_ref];
+        Ok(self.base_url_of(draft, ctx)?.join(url_ref)?)
+    }
+
+    /// Get the base URL that references are relative to in the given
+    /// context.
+    pub(crate) fn base_url_of(
+        &self,
+        draft: Draft,
+        ctx: &Context,
+    ) -> Result<url::Url, ValidationError> {
+        let (base_url, ids) = self.base_of(draft, ctx);
+        let url = ids
+            .iter()
+            .try_fold(url::Url::parse(base_url)?, |x, y| x.join(y));
+        Ok(url?)
+    }
+
+    /// Get the base URL of the nearest frame of a context that has it, or
+    /// else the base URL of the resolver, along with the ids of the frames
+    /// above it, from the outermost, which are joined to it to make the base
+    /// URL of the context.
+    fn base_of<'c>(&'c self, draft: Draft, ctx: &'c Context) -> (&'c str, Vec<&'c str>) {
+        let mut ids = Vec::new();
         let mut frame = ctx;
-        loop {
-            if let Some(id) = id_of(draft, frame.x) {
-                urls.push(id);
+        let base_url = loop {
+            if let Some(base_url) = frame.base_url {
+                break base_url;
+            }
+            if let Some(id) = id_of(frame.get_draft(draft), frame.x) {
+                ids.push(id);
             }
+            match frame.parent {
+                Some(x) => frame = x,
+                None => break self.base_url.as_str(),
+            }
+        };
+        ids.reverse();
+        (base_url, ids)
+    }
+
+    /// Get the schema resources in the dynamic scope, along with their base
+    /// URLs, from the innermost to the outermost.
+    pub fn dynamic_scope(
+        &self,
+        draft: Draft,
+        ctx: &Context<'a>,
+    ) -> Result<Vec<(url::Url, &'a Value)>, ValidationError> {
+        let mut frames = Vec::new();
+        let mut frame = ctx;
+        loop {
+            frames.push(frame);
             match frame.parent {
                 Some(x) => frame = x,
                 None => break,
             }
         }
-        let base_url = url::Url::parse(&self.base_url)?;
-        let url = urls.iter().rev().try_fold(base_url, |x, y| x.join(y));
-        Ok(url?)
+
+        let mut url = url::Url::parse(&self.base_url)?;
+        let mut scope = Vec::new();
+        for frame in frames.iter().rev() {
+            if let Some(base_url) = frame.base_url {
+                url = url::Url::parse(base_url)?;
+            } else if let Some(id) = id_of(frame.get_draft(draft), frame.x) {
+                url = url.join(id)?;
+            }
+            let mut resource_url = url.clone();
+            resource_url.set_fragment(None);
+            scope.push((resource_url, frame.get_resource()));
+        }
+        scope.reverse();
+        Ok(scope)
     }
 
-    pub fn resolve_url(
+    /// Get the document at a URL, among the schema's own resources and the
+    /// known metaschemas, without retrieving it.
+    pub(crate) fn resolve_known_url(
         &self,
         url: &url::Url,
         instance: &'a Value,
+    ) -> Option<&'a Value> {
+        let url_str = url.as_str();
+        if url_str == self.base_url {
+            return Some(instance);
+        }
+        schemas::metaschema_from_url(url_str)
+            .or_else(|| self.lookup(&index_key(url)).map(|(_, _, x)| x))
+    }
+
+    /// Get the document at a URL, retrieving it with the retriever if it
+    /// isn't already known. Retrieved documents are kept for later lookups.
+    pub fn resolve_url(
+        &'a self,
+        url: &url::Url,
+        instance: &'a Value,
     ) -> Result<&'a Value, ValidationError> {
+        if let Some(value) = self.resolve_known_url(url, instance) {
+            return Ok(value);
+        }
         let url_str = url.as_str();
-        match url_str {
-            DOCUMENT_PROTOCOL => Ok(instance),
-            _ => match schemas::draft_from_url(url_str) {
-                Some(value) => Ok(value.get_schema()),
-                _ => match self.id_mapping.get(url_str) {
-                    Some(value) => Ok(value),
-                    None => Err(ValidationError::new(
-                        &format!("Can't resolve url {}", url_str),
-                        None,
-                        None,
-                    )),
-                },
-            },
+        if let Some(value) = self.retrieved.get(url_str) {
+            return Ok(value);
+        }
+        match &self.retriever {
+            Some(retriever) => {
+                let refused = self.refused.read().unwrap_or_else(PoisonError::into_inner);
+                if let Some(violation) = refused.get(url_str) {
+                    return Err(violation.to_error(url_str));
+                }
+                drop(refused);
+                let refuse = |violation: PolicyViolation| {
+                    let err = violation.to_error(url_str);
+                    self.refused
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(url_str.to_string(), violation);
+                    err
+                };
+                self.policy.check_location(url).map_err(refuse)?;
+                // The place is given back if the document isn't added.
+                let (reservation, loaded) = self.retrieved.reserve();
+                self.policy.check_count(loaded).map_err(refuse)?;
+                let document = retriever
+                    .retrieve_with_policy(url_str, &self.policy)
+                    .map_err(|err| match err.policy_violation {
+                        Some(violation) => refuse(violation),
+                        None => ValidationError::new(
+                            &format!("Can't retrieve url {}: {}", url_str, err.msg),
+                            None,
+                            None,
+                        ),
+                    })?;
+                // A retriever may ignore the maximum size, so the document
+                // is measured again, as compact JSON, if there is one.
+                if self.policy.max_document_size().is_some() {
+                    let size = serde_json::to_vec(&document).map_or(0, |x| x.len());
+                    self.policy.check_size(size).map_err(refuse)?;
+                }
+                Ok(reservation.insert(url_str, document))
+            }
+            None => Err(ValidationError::new(
+                &format!("Can't resolve url {}", url_str),
+                None,
+                None,
+            )),
         }
     }
 
+    /// Resolve a reference, relative to the given context.
+    ///
+    /// Returns the base URL of the resolved schema, the schema resource that
+    /// contains it, and the resolved schema itself.
     pub fn resolve_fragment(
+        &'a self,
+        draft: Draft,
+        url: &str,
+        ctx: &Context,
+        instance: &'a Value,
+    ) -> Result<(url::Url, &'a Value, &'a Value), ValidationError> {
+        self.resolve_fragment_in(draft, url, ctx, instance, true)
+    }
+
+    /// Resolve a reference, relative to the given context, as
+    /// [`Resolver::resolve_fragment`] does, but only within the documents that
+    /// are known already, without retrieving any.
+    pub(crate) fn resolve_known_fragment(
+        &'a self,
+        draft: Draft,
+        url: &str,
+        ctx: &Context,
+        instance: &'a Value,
+    ) -> Option<(url::Url, &'a Value, &'a Value)> {
+        self.resolve_fragment_in(draft, url, ctx, instance, false)
+            .ok()
+    }
+
+    /// Get the absolute URL of a reference in the given context, as
+    /// [`Resolver::join_url`] does, and its key in the index, reusing the
+    /// result for the same reference against the same base. The base is
+    /// that of the nearest frame that has its base URL, with the ids above
+    /// it, so that neither is joined again.
+    fn join_memoized(
         &self,
         draft: Draft,
+        url_ref: &str,
+        ctx: &Context,
+    ) -> Result<(url::Url, String), ValidationError> {
+        let (base_url, ids) = self.base_of(draft, ctx);
+        // Each id is prefixed with its length, so that the key is different
+        // for every base URL and list of ids.
+        let base_key = if ids.is_empty() {
+            Cow::Borrowed(base_url)
+        } else {
+            Cow::Owned(ids.iter().fold(base_url.to_string(), |key, id| {
+                format!("{} {}:{}", key, id.len(), id)
+            }))
+        };
+        let joined = self.joined.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(joined) = joined.get(base_key.as_ref()).and_then(|x| x.get(url_ref)) {
+            return Ok(joined.clone());
+        }
+        drop(joined);
+        let url = normalize(self.base_url_of(draft, ctx)?.join(url_ref)?);
+        let joined = (url.clone(), index_key(&url));
+        self.joined
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(base_key.into_owned())
+            .or_default()
+            .insert(url_ref.to_string(), joined.clone());
+        Ok(joined)
+    }
+
+    fn resolve_fragment_in(
+        &'a self,
+        draft: Draft,
         url: &str,
         ctx: &Context,
         instance: &'a Value,
-    ) -> Result<(url::Url, &'a Value), ValidationError> {
-        let url = self.join_url(draft, url, ctx)?;
+        retrieve: bool,
+    ) -> Result<(url::Url, &'a Value, &'a Value), ValidationError> {
+        let (url, key) = self.join_memoized(draft, url, ctx)?;
+        if let Some(resolved) = self.lookup(&key) {
+            return Ok(resolved);
+        }
+
         let mut resource = url.clone();
         resource.set_fragment(None);
-        let fragment = percent_encoding::percent_decode(url.fragment().unwrap_or("").as_bytes())
-            .decode_utf8()
-            .unwrap();
+        let fragment =
+            percent_encoding::percent_decode_str(url.fragment().unwrap_or("")).decode_utf8_lossy();
 
-        if let Some(x) = find_ids(
-            draft,
-            instance,
-            &url::Url::parse(DOCUMENT_PROTOCOL)?,
-            &mut |id, x| {
-                if id == url.as_str() {
-                    Some(x)
-                } else {
-                    None
-                }
-            },
-        )? {
-            return Ok((resource, x));
-        }
+        let document = if retrieve {
+            self.resolve_url(&resource, instance)?
+        } else {
+            self.resolve_known_url(&resource, instance).ok_or_else(|| {
+                ValidationError::new(&format!("Unknown url {}", resource), None, None)
+            })?
+        };
 
-        let document = self.resolve_url(&resource, instance)?;
+        // A plain-name fragment refers to an anchor within a document other
+        // than the one being validated, such as one of the metaschemas. The
+        // document may be written in a draft of its own.
+        if !fragment.is_empty() && !fragment.starts_with('/') {
+            if let Some((resource_document, x)) = find_ids(
+                draft_of(draft, document),
+                document,
+                document,
+                &resource,
+                &mut |id, _resource, _x| id == key,
+            )? {
+                return Ok((resource, resource_document, x));
+            }
+        }
 
-        // TODO Prevent infinite reference recursion
         match document.pointer(&fragment) {
-            Some(x) => Ok((resource, x)),
+            Some(x) => match id_of(draft_of(draft, x), x) {
+                Some(id) if !id.starts_with('#') => {
+                    let mut scope = resource.join(id)?;
+                    scope.set_fragment(None);
+                    Ok((scope, x, x))
+                }
+                _ => Ok((resource, document, x)),
+            },
             None => Err(ValidationError::new(
                 &format!("Couldn't resolve JSON pointer {}", url),
                 None,
@@ -179,3 +813,206 @@ impl<'a> Resolver<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_index() {
+        let schema = json!({
+            "$id": "https://example.com/root.json",
+            "$defs": {
+                "a": {"type": "integer"},
+                "b": {"$id": "b.json", "$anchor": "top", "$defs": {"c": {"minimum": 1}}},
+                "%41": {"type": "string"},
+                "A": {"type": "null"}
+            }
+        });
+        let resolver = Resolver::from_schema(Draft::Draft202012, &schema).unwrap();
+        let ctx = Context::new_from(&schema);
+        let resolve = |reference: &str| {
+            let (scope, resource, x) = resolver
+                .resolve_fragment(Draft::Draft202012, reference, &ctx, &schema)
+                .unwrap();
+            (scope.to_string(), resource.get("$id").cloned(), x.clone())
+        };
+        let root = Some(json!("https://example.com/root.json"));
+        let b = Some(json!("b.json"));
+        assert_eq!(
+            resolve("#/$defs/a"),
+            (
+                "https://example.com/root.json".to_string(),
+                root.clone(),
+                json!({"type": "integer"})
+            )
+        );
+        assert_eq!(
+            resolve("b.json#/$defs/c"),
+            (
+                "https://example.com/b.json".to_string(),
+                b.clone(),
+                json!({"minimum": 1})
+            )
+        );
+        assert_eq!(resolve("b.json#top").1, b);
+        assert_eq!(resolve("#/$defs/b").0, "https://example.com/b.json");
+        // A pointer with `%` in it is looked up without the index, as the
+        // reference's fragment is percent-decoded.
+        assert_eq!(resolve("#/$defs/%2541").2, json!({"type": "string"}));
+        assert_eq!(resolve("#/$defs/%41").2, json!({"type": "null"}));
+        assert!(resolver
+            .resolve_fragment(Draft::Draft202012, "#/$defs/d", &ctx, &schema)
+            .is_err());
+
+        // The absolute URL of each reference is kept, under the base URL
+        // that it is relative to.
+        let joined = || {
+            let joined = resolver.joined.read().unwrap();
+            (
+                joined.len(),
+                joined.values().map(HashMap::len).sum::<usize>(),
+            )
+        };
+        assert_eq!(joined(), (1, 7));
+        resolve("#/$defs/a");
+        assert_eq!(joined(), (1, 7));
+    }
+
+    #[test]
+    fn test_frame_base_url() {
+        let schema = json!({
+            "$id": "https://example.com/root.json",
+            "$defs": {
+                "b": {
+                    "$id": "dir/b.json",
+                    "$defs": {
+                        "e": {"$id": "e.json", "$defs": {"d": {"type": "integer"}}}
+                    }
+                },
+                "c": {"$id": "dir/c.json", "type": "string"}
+            }
+        });
+        let resolver = Resolver::from_schema(Draft::Draft202012, &schema).unwrap();
+        let root = Context::new_from(&schema);
+        let b_schema = &schema["$defs"]["b"];
+        let e_schema = &b_schema["$defs"]["e"];
+        let b = root.push(b_schema);
+        let e = b.push(e_schema);
+        let base_url = |ctx: &Context| {
+            resolver
+                .base_url_of(Draft::Draft202012, ctx)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(base_url(&e), "https://example.com/dir/e.json");
+
+        // A frame that keeps its base URL resolves the same as one that
+        // doesn't, without the ids of the frames below it.
+        let b_url = base_url(&b);
+        let cached_b = b.with_base_url(Some(&b_url));
+        let cached_e = cached_b.push(e_schema);
+        assert_eq!(
+            resolver.base_of(Draft::Draft202012, &cached_e).1,
+            ["e.json"]
+        );
+        assert_eq!(base_url(&cached_e), base_url(&e));
+        let resolve = |reference: &str, ctx: &Context| {
+            resolver
+                .resolve_fragment(Draft::Draft202012, reference, ctx, &schema)
+                .unwrap()
+                .2
+                .clone()
+        };
+        assert_eq!(
+            resolve("#/$defs/d", &cached_e),
+            b_schema["$defs"]["e"]["$defs"]["d"]
+        );
+        assert_eq!(resolve("c.json", &cached_b), schema["$defs"]["c"]);
+        assert_eq!(resolve("c.json", &b), schema["$defs"]["c"]);
+
+        // The references are memoized under the base URL that the frame keeps,
+        // or else under the base URL and ids that it is joined from.
+        let joined = resolver.joined.read().unwrap();
+        assert!(joined.contains_key(b_url.as_str()));
+        assert!(joined.contains_key("document:/// 29:https://example.com/root.json 10:dir/b.json"));
+    }
+
+    #[test]
+    fn test_anchors() {
+        let schema = json!({
+            "$id": "https://example.com/root.json",
+            "definitions": {
+                "a": {
+                    "$id": "nested/a.json",
+                    "definitions": {"b": {"$id": "#foo", "type": "integer"}}
+                },
+                "c": {"$id": "other.json#bar", "type": "string"}
+            }
+        });
+        let resolver = Resolver::from_schema(Draft::Draft7, &schema).unwrap();
+        let ctx = Context::new_from(&schema);
+        let resolve = |reference: &str| {
+            resolver
+                .resolve_fragment(Draft::Draft7, reference, &ctx, &schema)
+                .map(|(scope, resource, x)| (scope.to_string(), resource["$id"].clone(), x))
+        };
+        let b = (
+            "https://example.com/nested/a.json".to_string(),
+            json!("nested/a.json"),
+            &schema["definitions"]["a"]["definitions"]["b"],
+        );
+        assert_eq!(resolve("nested/a.json#foo").unwrap(), b);
+        assert_eq!(resolve("nested/a.json#%66o%6F").unwrap(), b);
+        // The anchor belongs to the nested resource, not the root.
+        assert!(resolve("#foo").is_err());
+        // An id with a fragment names both a resource and an anchor in it.
+        let c = &schema["definitions"]["c"];
+        assert_eq!(resolve("other.json#bar").unwrap().2, c);
+        assert_eq!(resolve("other.json").unwrap().2, c);
+
+        let duplicate = |schema: Value| match Resolver::from_schema(Draft::Draft202012, &schema) {
+            Ok(_) => None,
+            Err(err) => Some(err.msg),
+        };
+        assert_eq!(
+            duplicate(json!({
+                "$id": "https://example.com/root.json",
+                "$defs": {"a": {"$anchor": "x"}, "b": {"$anchor": "x"}}
+            })),
+            Some("Duplicate anchor https://example.com/root.json#x".to_string())
+        );
+        assert_eq!(
+            duplicate(json!({
+                "$id": "https://example.com/root.json",
+                "$defs": {
+                    "a": {"$anchor": "x", "$dynamicAnchor": "x"},
+                    "b": {"$id": "b.json", "$anchor": "x"}
+                }
+            })),
+            None
+        );
+
+        // A retrieved document's anchors are found with its own draft.
+        let schema = json!({"$ref": "https://example.com/other.json#foo"});
+        let mut resolver = Resolver::from_schema(Draft::Draft7, &schema).unwrap();
+        resolver.set_retriever(Box::new(|_: &str| {
+            Ok(json!({
+                "$id": "https://example.com/other.json",
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "$defs": {"foo": {"$anchor": "foo", "type": "integer"}}
+            }))
+        }));
+        let ctx = Context::new_from(&schema);
+        let (_, _, foo) = resolver
+            .resolve_fragment(
+                Draft::Draft7,
+                "https://example.com/other.json#foo",
+                &ctx,
+                &schema,
+            )
+            .unwrap();
+        assert_eq!(foo, &json!({"$anchor": "foo", "type": "integer"}));
+    }
+}
@@ -0,0 +1,355 @@
+//! Loading the documents that external `$ref`s refer to.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use serde_json::Value;
+
+use crate::error::ValidationError;
+use crate::policy::{PolicyViolation, ResolutionPolicy};
+
+/// Loads the schema documents that a schema refers to, but that aren't one of
+/// its own resources or a known metaschema.
+///
+/// A retriever is given to a [`crate::Config`] with
+/// [`crate::Config::with_retriever`]. It is called at most once for each
+/// document, with the absolute URI of the document, without a fragment, and
+/// the documents it returns are kept for as long as the `Config`.
+///
+/// Any function or closure taking the URI as a `&str` and returning a
+/// `Result<Value, ValidationError>` is a retriever.
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::{json, Value};
+/// # use jsonschema_valid::{Config, ValidationError};
+/// let schema = json!({"$ref": "https://example.com/positive.json"});
+/// let cfg = Config::from_schema(&schema, None)?.with_retriever(|uri: &str| {
+///     match uri {
+///         "https://example.com/positive.json" => Ok(json!({"exclusiveMinimum": 0})),
+///         _ => Err(ValidationError::new(&format!("Unknown document {}", uri), None, None)),
+///     }
+/// });
+/// assert!(cfg.validate(&json!(1)).is_ok());
+/// assert!(cfg.validate(&json!(-1)).is_err());
+/// # Ok(()) }
+/// ```
+pub trait Retriever: Send + Sync {
+    /// Load the document at the given absolute URI.
+    fn retrieve(&self, uri: &str) -> Result<Value, ValidationError>;
+
+    /// Load the document at the given absolute URI, within the limits of the
+    /// [`ResolutionPolicy`] of the `Config`, which has already allowed the
+    /// URI itself.
+    ///
+    /// A retriever that can tell the size of a document before reading all
+    /// of it should refuse it then, if it is larger than
+    /// [`ResolutionPolicy::max_document_size`], and a retriever that follows
+    /// redirects should check each URL it is redirected to with
+    /// [`ResolutionPolicy::check_location`]. A refusal is reported with its
+    /// [`PolicyViolation`] in the `policy_violation` of the error. By
+    /// default, this calls [`Retriever::retrieve`], and the size of the
+    /// document is checked once it is loaded.
+    fn retrieve_with_policy(
+        &self,
+        uri: &str,
+        policy: &ResolutionPolicy,
+    ) -> Result<Value, ValidationError> {
+        let _ = policy;
+        self.retrieve(uri)
+    }
+}
+
+impl<F> Retriever for F
+where
+    F: Fn(&str) -> Result<Value, ValidationError> + Send + Sync,
+{
+    fn retrieve(&self, uri: &str) -> Result<Value, ValidationError> {
+        self(uri)
+    }
+}
+
+/// A [`Retriever`] that loads documents from the filesystem.
+///
+/// `file:` URIs are read from the path they name. Other URIs can be mapped to
+/// local directories by their prefix with [`FileRetriever::with_mapping`], so
+/// that schemas published under a URL can be used from a local copy. When
+/// several prefixes match, the longest one is used.
+///
+/// ## Example:
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::json;
+/// # use jsonschema_valid::{Config, FileRetriever};
+/// let schema = json!({"$ref": "https://schemas.example.com/common/address.json"});
+/// let cfg = Config::from_schema(&schema, None)?.with_retriever(
+///     FileRetriever::new().with_mapping("https://schemas.example.com/", "schemas"),
+/// );
+/// // Validating reads `schemas/common/address.json`.
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileRetriever {
+    mappings: Vec<(String, PathBuf)>,
+}
+
+impl FileRetriever {
+    /// Create a retriever that only reads `file:` URIs.
+    pub fn new() -> FileRetriever {
+        FileRetriever::default()
+    }
+
+    /// Read the documents whose URI starts with `prefix` from `directory`,
+    /// with the rest of the URI as a path relative to it.
+    pub fn with_mapping(mut self, prefix: &str, directory: impl Into<PathBuf>) -> Self {
+        self.mappings.push((prefix.to_string(), directory.into()));
+        self
+    }
+
+    /// Get the path of the file holding the document at a URI.
+    fn path_of(&self, uri: &str) -> Option<PathBuf> {
+        let mapped = self
+            .mappings
+            .iter()
+            .filter_map(|(prefix, directory)| Some((uri.strip_prefix(prefix.as_str())?, directory)))
+            .min_by_key(|(rest, _)| rest.len());
+        match mapped {
+            Some((rest, directory)) => {
+                let rest = rest.split(['?', '#']).next().unwrap_or("");
+                let rest = percent_encoding::percent_decode_str(rest)
+                    .decode_utf8()
+                    .ok()?;
+                // Dot segments are resolved in the URI already, so any left
+                // were percent-encoded. They are skipped, so that the path
+                // stays within the directory.
+                Some(
+                    rest.split('/')
+                        .fold(directory.clone(), |path, segment| match segment {
+                            "" | "." | ".." => path,
+                            _ => path.join(segment),
+                        }),
+                )
+            }
+            None => url::Url::parse(uri)
+                .ok()
+                .filter(|x| x.scheme() == "file")?
+                .to_file_path()
+                .ok(),
+        }
+    }
+}
+
+impl Retriever for FileRetriever {
+    fn retrieve(&self, uri: &str) -> Result<Value, ValidationError> {
+        self.retrieve_with_policy(uri, &ResolutionPolicy::new())
+    }
+
+    fn retrieve_with_policy(
+        &self,
+        uri: &str,
+        policy: &ResolutionPolicy,
+    ) -> Result<Value, ValidationError> {
+        let path = self.path_of(uri).ok_or_else(|| {
+            ValidationError::new(&format!("No local file for {}", uri), None, None)
+        })?;
+        let file = fs::File::open(&path).map_err(|err| {
+            ValidationError::new(
+                &format!("Can't read {}: {}", path.display(), err),
+                None,
+                None,
+            )
+        })?;
+        if let (Some(max), Ok(metadata)) = (policy.max_document_size(), file.metadata()) {
+            let size = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+            if size > max {
+                return Err(PolicyViolation::DocumentTooLarge { size, max }.to_error(uri));
+            }
+        }
+        serde_json::from_reader(std::io::BufReader::new(file)).map_err(|err| {
+            ValidationError::new(
+                &format!("Invalid JSON in {}: {}", path.display(), err),
+                None,
+                None,
+            )
+        })
+    }
+}
+
+/// A retrieved document, and the next one to be retrieved after it.
+struct Retrieved {
+    uri: String,
+    document: Value,
+    next: OnceLock<Box<Retrieved>>,
+}
+
+/// The documents loaded by a retriever. Documents are only ever added, and
+/// never moved once added, so references to them last as long as the store.
+#[derive(Default)]
+pub(crate) struct RetrievedDocuments {
+    first: OnceLock<Box<Retrieved>>,
+    /// The number of documents added, and of those being retrieved to be
+    /// added, as reserved with [`RetrievedDocuments::reserve`].
+    reserved: AtomicUsize,
+}
+
+/// A place for a document that is being retrieved, which is given back when
+/// dropped, unless the document is added with [`Reservation::insert`].
+pub(crate) struct Reservation<'r> {
+    documents: &'r RetrievedDocuments,
+}
+
+impl RetrievedDocuments {
+    /// Reserve a place for a document that is about to be retrieved, and get
+    /// the number of places that were taken before it. Reserving before
+    /// retrieving, rather than counting the documents once added, means that
+    /// concurrent retrievals each see a different number.
+    pub(crate) fn reserve(&self) -> (Reservation<'_>, usize) {
+        let taken = self.reserved.fetch_add(1, Ordering::SeqCst);
+        (Reservation { documents: self }, taken)
+    }
+
+    /// Get a document that was already retrieved.
+    pub(crate) fn get(&self, uri: &str) -> Option<&Value> {
+        let mut next = self.first.get();
+        while let Some(retrieved) = next {
+            if retrieved.uri == uri {
+                return Some(&retrieved.document);
+            }
+            next = retrieved.next.get();
+        }
+        None
+    }
+
+    /// Add a retrieved document, unless one was already added for the same
+    /// URI, and get the document that is kept, and whether it was added.
+    fn insert(&self, uri: &str, document: Value) -> (&Value, bool) {
+        let mut new = Some(Box::new(Retrieved {
+            uri: uri.to_string(),
+            document,
+            next: OnceLock::new(),
+        }));
+        let mut slot = &self.first;
+        loop {
+            let retrieved = slot.get_or_init(|| new.take().unwrap());
+            if new.is_none() || retrieved.uri == uri {
+                return (&retrieved.document, new.is_none());
+            }
+            slot = &retrieved.next;
+        }
+    }
+}
+
+impl<'r> Reservation<'r> {
+    /// Add the retrieved document, keeping the place reserved for it, unless
+    /// another one was added for the same URI in the meantime, and get the
+    /// document that is kept.
+    pub(crate) fn insert(self, uri: &str, document: Value) -> &'r Value {
+        let (document, added) = self.documents.insert(uri, document);
+        if added {
+            std::mem::forget(self);
+        }
+        document
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.documents.reserved.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use serde_json::json;
+    use std::path::Path;
+
+    #[test]
+    fn test_retrieved_documents() {
+        let documents = RetrievedDocuments::default();
+        assert_eq!(documents.get("a"), None);
+        let a = documents.reserve().0.insert("a", json!(1));
+        let (reservation, taken) = documents.reserve();
+        assert_eq!(taken, 1);
+        let b = reservation.insert("b", json!(2));
+        assert_eq!(documents.reserve().0.insert("a", json!(3)), &json!(1));
+        assert_eq!((a, b), (&json!(1), &json!(2)));
+        assert_eq!(documents.get("b"), Some(&json!(2)));
+        // Places are given back when their document isn't added, or is
+        // already there.
+        let (reservation, taken) = documents.reserve();
+        assert_eq!(taken, 2);
+        drop(reservation);
+        assert_eq!(documents.reserve().1, 2);
+    }
+
+    #[test]
+    fn test_file_retriever() {
+        let root = std::env::temp_dir().join(format!("jsonschema-valid-{}", std::process::id()));
+        let write = |path: &str, document: Value| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, document.to_string()).unwrap();
+        };
+        write(
+            "common/address.json",
+            json!({"definitions": {"zip": {"$ref": "../shared/digits.json", "maxLength": 5}}}),
+        );
+        write("shared/digits.json", json!({"pattern": "^[0-9]+$"}));
+        write("published/v1/country.json", json!({"enum": ["US", "CA"]}));
+
+        let schema = json!({
+            "properties": {
+                "zip": {"$ref": "common/address.json#/definitions/zip"},
+                "country": {"$ref": "https://schemas.example.com/v1/country.json"}
+            }
+        });
+        let cfg = Config::from_schema_at_path(&schema, None, &root.join("schema.json")).unwrap();
+        assert!(cfg.validate(&json!({"zip": "12345"})).is_ok());
+        assert!(cfg.validate(&json!({"zip": "1234a"})).is_err());
+        assert!(cfg.validate(&json!({"country": "US"})).is_err());
+
+        let cfg = cfg.with_retriever(
+            FileRetriever::new()
+                .with_mapping("https://schemas.example.com/", root.join("published"))
+                .with_mapping("https://schemas.example.com/v1/", root.join("published/v1")),
+        );
+        assert!(cfg.validate(&json!({"country": "US"})).is_ok());
+        assert!(cfg.validate(&json!({"country": "FR"})).is_err());
+
+        let digits = url::Url::from_file_path(root.join("shared/digits.json")).unwrap();
+        let err = FileRetriever::new()
+            .retrieve_with_policy(
+                digits.as_str(),
+                &ResolutionPolicy::new().with_max_document_size(5),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.policy_violation,
+            Some(PolicyViolation::DocumentTooLarge { size: 22, max: 5 })
+        );
+        assert!(FileRetriever::new()
+            .retrieve_with_policy(
+                digits.as_str(),
+                &ResolutionPolicy::new().with_max_document_size(22),
+            )
+            .is_ok());
+
+        fs::remove_dir_all(&root).unwrap();
+
+        let retriever = FileRetriever::new().with_mapping("https://example.com/", "local");
+        // Encoded dot segments can't leave the mapped directory.
+        assert_eq!(
+            retriever.path_of("https://example.com/a/%2E%2E/%2E%2E/b%20c.json?x=1"),
+            Some(Path::new("local").join("a").join("b c.json"))
+        );
+        assert_eq!(retriever.path_of("https://example.org/a.json"), None);
+    }
+}
@@ -1,9 +1,15 @@
 //! Implementations of the different drafts of JSON schema.
 //!
 
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
 use lazy_static::lazy_static;
 use serde_json::Value;
 
+use crate::config::Config;
+use crate::error::{ErrorIterator, ValidationError};
 use crate::format;
 use crate::format::FormatChecker;
 use crate::validators;
@@ -12,6 +18,9 @@ use crate::validators::Validator;
 /// The validator can validate JSON data against different versions of JSON Schema.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Draft {
+    /// JSONSchema [Draft 3](https://json-schema.org/specification-links.html#draft-3)
+    Draft3,
+
     /// JSONSchema [Draft 4](https://json-schema.org/specification-links.html#draft-4)
     Draft4,
 
@@ -20,14 +29,50 @@ pub enum Draft {
 
     /// JSONSchema [Draft 7](https://json-schema.org/specification-links.html#draft-7)
     Draft7,
+
+    /// JSONSchema [Draft 2019-09](https://json-schema.org/specification-links.html#draft-2019-09-formerly-known-as-draft-8)
+    Draft201909,
+
+    /// JSONSchema [Draft 2020-12](https://json-schema.org/specification-links.html#2020-12)
+    Draft202012,
+
+    /// The [OpenAPI 3.0](https://spec.openapis.org/oas/v3.0.3#schema-object)
+    /// Schema Object, an extended subset of Draft 4.
+    OpenApi30,
+
+    /// The [structural schemas](https://kubernetes.io/docs/tasks/extend-kubernetes/custom-resources/custom-resource-definitions/#specifying-a-structural-schema)
+    /// of Kubernetes CustomResourceDefinitions, which extend OpenAPI 3.0 with
+    /// the `x-kubernetes-*` keywords. Unknown fields are reported, as with
+    /// strict field validation.
+    Kubernetes,
+
+    /// The [`$jsonSchema`](https://www.mongodb.com/docs/manual/reference/operator/query/jsonSchema/)
+    /// of MongoDB collection validators, a variant of Draft 4 that adds
+    /// `bsonType` and omits `$ref`, `definitions`, `format` and the `integer`
+    /// type. Instances tag their BSON types with
+    /// [Extended JSON](https://www.mongodb.com/docs/manual/reference/mongodb-extended-json/)
+    /// wrappers such as `{"$oid": "..."}` and `{"$date": "..."}`.
+    MongoDb,
+
+    /// A user-defined [`Dialect`], created with [`Dialect::register`].
+    Custom(&'static Dialect),
 }
 
 impl Draft {
-    pub(crate) fn get_validator(self, key: &str) -> Option<Validator> {
+    pub(crate) fn get_validator(self, key: &str) -> Option<Validator<'_>> {
         match self {
+            Draft::Draft3 => draft3::get_validator(key),
             Draft::Draft4 => draft4::get_validator(key),
             Draft::Draft6 => draft6::get_validator(key),
             Draft::Draft7 => draft7::get_validator(key),
+            Draft::Draft201909 => draft201909::get_validator(key),
+            Draft::Draft202012 => draft202012::get_validator(key),
+            Draft::OpenApi30 => openapi30::get_validator(key),
+            Draft::Kubernetes => kubernetes::get_validator(key),
+            Draft::MongoDb => mongodb::get_validator(key),
+            Draft::Custom(_) => self
+                .get_override(key)
+                .unwrap_or_else(|| self.builtin().get_validator(key)),
         }
     }
 
@@ -37,32 +82,904 @@ impl Draft {
             Draft::Draft7 => draft7::get_schema(),
             Draft::Draft6 => draft6::get_schema(),
             Draft::Draft4 => draft4::get_schema(),
+            Draft::Draft3 => draft3::get_schema(),
+            Draft::Draft201909 => draft201909::get_schema(),
+            Draft::Draft202012 => draft202012::get_schema(),
+            Draft::OpenApi30 => openapi30::get_schema(),
+            Draft::Kubernetes => kubernetes::get_schema(),
+            Draft::MongoDb => mongodb::get_schema(),
+            Draft::Custom(dialect) => &dialect.metaschema,
         }
     }
 
     /// Get a format check function.
     pub(crate) fn get_format_checker(self, format: &str) -> Option<FormatChecker> {
         match self {
+            Draft::Draft3 => draft3::get_format_checker(format),
             Draft::Draft4 => draft4::get_format_checker(format),
             Draft::Draft6 => draft6::get_format_checker(format),
             Draft::Draft7 => draft7::get_format_checker(format),
+            Draft::Draft201909 => draft201909::get_format_checker(format),
+            Draft::Draft202012 => draft202012::get_format_checker(format),
+            Draft::OpenApi30 => openapi30::get_format_checker(format),
+            Draft::Kubernetes => openapi30::get_format_checker(format),
+            Draft::MongoDb => None,
+            Draft::Custom(dialect) => dialect.get_format_checker(format),
         }
     }
 
     /// Return the draft's number.
+    ///
+    /// Draft 2019-09 was formerly known as Draft 8, and is numbered as such.
+    /// Draft 2020-12 follows it as Draft 9. OpenAPI 3.0, Kubernetes, MongoDB
+    /// and user-defined dialects are numbered as the draft they are based on.
     pub fn get_draft_number(self) -> u8 {
         match self {
+            Draft::Draft3 => 3,
             Draft::Draft4 => 4,
             Draft::Draft6 => 6,
             Draft::Draft7 => 7,
+            Draft::Draft201909 => 8,
+            Draft::Draft202012 => 9,
+            Draft::OpenApi30 => 4,
+            Draft::Kubernetes => 4,
+            Draft::MongoDb => 4,
+            Draft::Custom(dialect) => dialect.base.get_draft_number(),
+        }
+    }
+
+    /// Whether the draft knows a keyword, either because it validates it or
+    /// because its metaschema declares it, as for annotations such as `title`.
+    pub(crate) fn knows_keyword(self, key: &str) -> bool {
+        match self {
+            Draft::Custom(dialect) => match dialect.keywords.get(key) {
+                Some(Keyword::Removed) => false,
+                Some(_) => true,
+                None => {
+                    declares_keyword(&dialect.metaschema, key) || dialect.base.knows_keyword(key)
+                }
+            },
+            // The links of JSON Hyper-Schema accompany Draft 7 and later.
+            Draft::Draft7 | Draft::Draft201909 | Draft::Draft202012
+                if key == "base" || key == "links" =>
+            {
+                true
+            }
+            // Like later drafts, Draft 4 also takes its id from `$id`.
+            Draft::Draft4 | Draft::OpenApi30 | Draft::Kubernetes if key == "$id" => true,
+            _ => {
+                self.get_validator(key).is_some()
+                    || self
+                        .get_keyword_schemas()
+                        .into_iter()
+                        .any(|schema| declares_keyword(schema, key))
+            }
+        }
+    }
+
+    /// Get the parts of the metaschema that declare the keywords of a schema.
+    fn get_keyword_schemas(self) -> Vec<&'static Value> {
+        match self {
+            Draft::Draft201909 | Draft::Draft202012 => {
+                let schema = self.get_schema();
+                let base = url::Url::parse(self.get_url()).unwrap();
+                let vocabularies = schema
+                    .get("allOf")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|x| x.get("$ref").and_then(Value::as_str))
+                    .filter_map(|x| base.join(x).ok())
+                    .filter_map(|x| metaschema_from_url(x.as_str()));
+                std::iter::once(schema).chain(vocabularies).collect()
+            }
+            Draft::OpenApi30 => vec![&self.get_schema()["definitions"]["Schema"]],
+            Draft::Kubernetes => vec![&self.get_schema()["definitions"]["JSONSchemaProps"]],
+            _ => vec![self.get_schema()],
+        }
+    }
+
+    /// Get the URL identifying the draft, as used in `$schema`.
+    pub fn get_url(self) -> &'static str {
+        match self {
+            Draft::Draft3 => "http://json-schema.org/draft-03/schema#",
+            Draft::Draft4 => "http://json-schema.org/draft-04/schema#",
+            Draft::Draft6 => "http://json-schema.org/draft-06/schema#",
+            Draft::Draft7 => "http://json-schema.org/draft-07/schema#",
+            Draft::Draft201909 => "https://json-schema.org/draft/2019-09/schema",
+            Draft::Draft202012 => "https://json-schema.org/draft/2020-12/schema",
+            Draft::OpenApi30 => openapi30::URL,
+            Draft::Kubernetes => kubernetes::URL,
+            Draft::MongoDb => mongodb::URL,
+            Draft::Custom(dialect) => &dialect.url,
+        }
+    }
+
+    /// Whether `$ref` overrides all of its sibling keywords. Starting with
+    /// Draft 2019-09, `$ref` is evaluated alongside its siblings.
+    pub(crate) fn ref_overrides_siblings(self) -> bool {
+        self.get_draft_number() < 8
+    }
+
+    /// Get the built-in draft that this one is based on. This is the draft
+    /// itself, unless it is a user-defined dialect.
+    pub(crate) fn builtin(self) -> Draft {
+        match self {
+            Draft::Custom(dialect) => dialect.base.builtin(),
+            _ => self,
+        }
+    }
+
+    /// Get the vocabularies that the draft's metaschema may enable with
+    /// `$vocabulary`. This is empty before Draft 2019-09.
+    pub(crate) fn get_vocabularies(self) -> &'static [Vocabulary] {
+        match self.builtin() {
+            Draft::Draft201909 => draft201909::VOCABULARIES,
+            Draft::Draft202012 => draft202012::VOCABULARIES,
+            _ => &[],
+        }
+    }
+
+    /// Whether a vocabulary is known, either as one of the draft's own or as
+    /// one declared by a user-defined dialect.
+    fn knows_vocabulary(self, url: &str) -> bool {
+        match self {
+            Draft::Custom(dialect) => {
+                dialect.vocabularies.iter().any(|x| x == url) || dialect.base.knows_vocabulary(url)
+            }
+            _ => self.get_vocabularies().iter().any(|x| x.url == url),
+        }
+    }
+
+    /// Get the validator of a keyword that a user-defined dialect adds or
+    /// removes, as opposed to one that it inherits from its base draft. The
+    /// inner `None` means that the keyword is ignored.
+    pub(crate) fn get_override(self, key: &str) -> Option<Option<Validator<'_>>> {
+        match self {
+            Draft::Custom(dialect) => match dialect.keywords.get(key) {
+                Some(Keyword::Draft(draft)) => Some(draft.get_validator(key)),
+                Some(_) => Some(None),
+                None => dialect.base.get_override(key),
+            },
+            _ => None,
+        }
+    }
+
+    /// Get a validator for a keyword added by a user-defined dialect.
+    pub(crate) fn get_keyword_validator(self, key: &str) -> Option<KeywordValidator> {
+        match self {
+            Draft::Custom(dialect) => match dialect.keywords.get(key) {
+                Some(Keyword::Custom(validator)) => Some(*validator),
+                Some(_) => None,
+                None => dialect.base.get_keyword_validator(key),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Whether a metaschema declares a keyword, as one of its `properties` or as
+/// matching one of its `patternProperties`.
+fn declares_keyword(metaschema: &Value, key: &str) -> bool {
+    let declared =
+        matches!(metaschema.get("properties"), Some(Value::Object(x)) if x.contains_key(key));
+    declared
+        || match metaschema.get("patternProperties") {
+            Some(Value::Object(x)) => x
+                .keys()
+                .filter_map(|pattern| regex::Regex::new(pattern).ok())
+                .any(|re| re.is_match(key)),
+            _ => false,
+        }
+}
+
+/// A set of keywords, identified by a URL, that a metaschema can enable with
+/// `$vocabulary`.
+#[derive(Clone, Copy)]
+pub(crate) struct Vocabulary {
+    pub url: &'static str,
+    get_validator: for<'k> fn(&'k str) -> Option<Validator<'k>>,
+}
+
+impl Vocabulary {
+    pub fn get_validator<'k>(&self, key: &'k str) -> Option<Validator<'k>> {
+        (self.get_validator)(key)
+    }
+}
+
+/// The keyword table of a vocabulary that only contains annotations.
+fn annotation(_key: &str) -> Option<Validator<'_>> {
+    None
+}
+
+/// Get the vocabularies enabled by the `$vocabulary` of a metaschema, or `None`
+/// if it doesn't declare any. The core vocabulary is always enabled, and
+/// unknown vocabularies are ignored unless they are required.
+pub(crate) fn vocabularies_from_metaschema(
+    draft: Draft,
+    metaschema: &Value,
+) -> Result<Option<Vec<Vocabulary>>, ValidationError> {
+    let declared = match metaschema.get("$vocabulary").and_then(Value::as_object) {
+        Some(declared) => declared,
+        None => return Ok(None),
+    };
+    for (url, required) in declared {
+        if required == &Value::Bool(true) && !draft.knows_vocabulary(url) {
+            return Err(ValidationError::new(
+                &format!("Unknown required vocabulary {}", url),
+                None,
+                None,
+            ));
+        }
+    }
+    Ok(Some(
+        draft
+            .get_vocabularies()
+            .iter()
+            .enumerate()
+            // The first vocabulary of each draft is the core vocabulary.
+            .filter(|(i, vocabulary)| *i == 0 || declared.contains_key(vocabulary.url))
+            .map(|(_, vocabulary)| *vocabulary)
+            .collect(),
+    ))
+}
+
+/// A validation function for a keyword added by a user-defined [`Dialect`].
+///
+/// It is called with the instance, the value of the keyword and the schema
+/// object containing it. The schema path of any errors returned is prefixed
+/// with the keyword.
+pub type KeywordValidator = for<'a> fn(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+) -> ErrorIterator<'a>;
+
+enum Keyword {
+    Custom(KeywordValidator),
+    Draft(Draft),
+    Removed,
+}
+
+/// A user-defined dialect of JSON Schema.
+///
+/// A dialect extends one of the drafts, its base, by adding and removing
+/// keywords and formats. Once registered, it is selected by any schema whose
+/// `$schema` is the dialect's URL, and its metaschema is used by
+/// [`Config::validate_schema`].
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::{json, Value};
+/// # use jsonschema_valid::{schemas, Config, ErrorIterator, ValidationError};
+/// fn even<'a>(
+///     _cfg: &'a Config<'a>,
+///     instance: &'a Value,
+///     schema: &'a Value,
+///     _parent_schema: Option<&'a Value>,
+/// ) -> ErrorIterator<'a> {
+///     match (instance.as_i64(), schema) {
+///         (Some(n), Value::Bool(true)) if n % 2 != 0 => Box::new(std::iter::once(
+///             ValidationError::new("Not even.", Some(instance), Some(schema)),
+///         )),
+///         _ => Box::new(std::iter::empty()),
+///     }
+/// }
+///
+/// schemas::Dialect::new(
+///     "https://example.com/even/schema",
+///     schemas::Draft::Draft7,
+///     json!({"$ref": "http://json-schema.org/draft-07/schema#"}),
+/// )
+/// .with_keyword("even", even)
+/// .register();
+///
+/// let schema = json!({"$schema": "https://example.com/even/schema", "even": true});
+/// let cfg = Config::from_schema(&schema, None)?;
+/// assert!(cfg.validate(&json!(2)).is_ok());
+/// assert!(cfg.validate(&json!(3)).is_err());
+/// # Ok(()) }
+/// ```
+pub struct Dialect {
+    url: String,
+    base: Draft,
+    metaschema: Value,
+    keywords: HashMap<String, Keyword>,
+    formats: HashMap<String, FormatChecker>,
+    vocabularies: Vec<String>,
+}
+
+lazy_static! {
+    static ref DIALECTS: RwLock<Vec<&'static Dialect>> = RwLock::new(Vec::new());
+}
+
+impl Dialect {
+    /// Create a new dialect, identified by `url`, that extends the `base`
+    /// draft. Schemas written in the dialect are validated against
+    /// `metaschema`. If the metaschema has no id, it is given `url`.
+    pub fn new(url: &str, base: Draft, metaschema: Value) -> Dialect {
+        let url = url.trim_end_matches('#').to_string();
+        let mut metaschema = metaschema;
+        if let Value::Object(object) = &mut metaschema {
+            let id = match base.builtin() {
+                Draft::Draft3
+                | Draft::Draft4
+                | Draft::OpenApi30
+                | Draft::Kubernetes
+                | Draft::MongoDb => "id",
+                _ => "$id",
+            };
+            if !object.contains_key(id) {
+                object.insert(id.to_string(), Value::String(url.clone()));
+            }
+        }
+        Dialect {
+            url,
+            base,
+            metaschema,
+            keywords: HashMap::new(),
+            formats: HashMap::new(),
+            vocabularies: Vec::new(),
+        }
+    }
+
+    /// Add a keyword, validated by the given function.
+    pub fn with_keyword(mut self, name: &str, validator: KeywordValidator) -> Dialect {
+        self.keywords
+            .insert(name.to_string(), Keyword::Custom(validator));
+        self
+    }
+
+    /// Add a keyword, validated as it is in the given draft. This can be used
+    /// to bring back a keyword that the base draft has dropped, such as
+    /// `format` assertions in Draft 2020-12.
+    pub fn with_draft_keyword(mut self, name: &str, draft: Draft) -> Dialect {
+        self.keywords
+            .insert(name.to_string(), Keyword::Draft(draft));
+        self
+    }
+
+    /// Remove a keyword of the base draft, so that it is ignored.
+    pub fn without_keyword(mut self, name: &str) -> Dialect {
+        self.keywords.insert(name.to_string(), Keyword::Removed);
+        self
+    }
+
+    /// Declare a vocabulary, so that metaschemas may require it through
+    /// `$vocabulary`. The keywords added with [`Dialect::with_keyword`] are
+    /// always active, whether or not the vocabulary is enabled.
+    pub fn with_vocabulary(mut self, url: &str) -> Dialect {
+        self.vocabularies.push(url.to_string());
+        self
+    }
+
+    /// Add a string format, or replace one of the base draft.
+    pub fn with_format(mut self, name: &str, checker: FormatChecker) -> Dialect {
+        self.formats.insert(name.to_string(), checker);
+        self
+    }
+
+    /// Get the URL identifying the dialect.
+    pub fn get_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the draft that the dialect extends.
+    pub fn get_base(&self) -> Draft {
+        self.base
+    }
+
+    /// Register the dialect, so that [`draft_from_url`] and
+    /// [`draft_from_schema`] recognise its URL, and return it as a `Draft`.
+    ///
+    /// Registered dialects live for the rest of the program. If several
+    /// dialects share a URL, the one registered last is used.
+    pub fn register(self) -> Draft {
+        let dialect: &'static Dialect = Box::leak(Box::new(self));
+        DIALECTS.write().unwrap().push(dialect);
+        Draft::Custom(dialect)
+    }
+
+    fn get_format_checker(&self, format: &str) -> Option<FormatChecker> {
+        match self.formats.get(format) {
+            Some(checker) => Some(*checker),
+            None => self.base.get_format_checker(format),
+        }
+    }
+}
+
+impl fmt::Debug for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Dialect")
+            .field("url", &self.url)
+            .field("base", &self.base)
+            .finish()
+    }
+}
+
+impl PartialEq for Dialect {
+    fn eq(&self, other: &Dialect) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Dialect {}
+
+mod kubernetes {
+    use super::*;
+
+    pub(super) const URL: &str = "https://kubernetes.io/schemas/apiextensions/v1/JSONSchemaProps";
+
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "$ref" => Some(validators::ref_ as Validator),
+            "additionalItems" => Some(validators::additionalItems as Validator),
+            "additionalProperties" => Some(validators::additionalProperties as Validator),
+            "allOf" => Some(validators::allOf as Validator),
+            "anyOf" => Some(validators::anyOf as Validator),
+            "dependencies" => Some(validators::dependencies as Validator),
+            "enum" => Some(validators::enum_ as Validator),
+            "format" => Some(validators::format as Validator),
+            "items" => Some(validators::items as Validator),
+            "maxItems" => Some(validators::maxItems as Validator),
+            "maxLength" => Some(validators::maxLength as Validator),
+            "maxProperties" => Some(validators::maxProperties as Validator),
+            "maximum" => Some(validators::maximum_draft4 as Validator),
+            "minItems" => Some(validators::minItems as Validator),
+            "minLength" => Some(validators::minLength as Validator),
+            "minProperties" => Some(validators::minProperties as Validator),
+            "minimum" => Some(validators::minimum_draft4 as Validator),
+            "multipleOf" => Some(validators::multipleOf as Validator),
+            "not" => Some(validators::not as Validator),
+            "oneOf" => Some(validators::oneOf as Validator),
+            "pattern" => Some(validators::pattern as Validator),
+            "patternProperties" => Some(validators::patternProperties as Validator),
+            "properties" => Some(validators::properties as Validator),
+            "required" => Some(validators::required as Validator),
+            "type" => Some(validators::type_kubernetes as Validator),
+            "uniqueItems" => Some(validators::uniqueItems as Validator),
+            "x-kubernetes-embedded-resource" => {
+                Some(validators::xKubernetesEmbeddedResource as Validator)
+            }
+            "x-kubernetes-int-or-string" => Some(validators::xKubernetesIntOrString as Validator),
+            "x-kubernetes-list-type" => Some(validators::xKubernetesListType as Validator),
+            _ => None,
+        }
+    }
+
+    /// Kubernetes doesn't publish a metaschema for `JSONSchemaProps`, so this
+    /// one is written from its API reference. It accepts all of the fields of
+    /// `JSONSchemaProps`, including those that structural schemas forbid,
+    /// which are instead reported by `check_structural`.
+    pub(super) fn get_schema() -> &'static Value {
+        lazy_static! {
+            static ref KUBERNETES: Value =
+                serde_json::from_str(include_str!("kubernetes.json")).unwrap();
         }
+        &KUBERNETES
+    }
+}
+
+mod mongodb {
+    use super::*;
+
+    pub(super) const URL: &str =
+        "https://www.mongodb.com/docs/manual/reference/operator/query/jsonSchema/";
+
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "additionalItems" => Some(validators::additionalItems as Validator),
+            "additionalProperties" => Some(validators::additionalProperties as Validator),
+            "allOf" => Some(validators::allOf as Validator),
+            "anyOf" => Some(validators::anyOf as Validator),
+            "bsonType" => Some(validators::bsonType as Validator),
+            "dependencies" => Some(validators::dependencies as Validator),
+            "enum" => Some(validators::enum_ as Validator),
+            "items" => Some(validators::items as Validator),
+            "maxItems" => Some(validators::maxItems as Validator),
+            "maxLength" => Some(validators::maxLength as Validator),
+            "maxProperties" => Some(validators::maxProperties as Validator),
+            "maximum" => Some(validators::maximum_mongodb as Validator),
+            "minItems" => Some(validators::minItems as Validator),
+            "minLength" => Some(validators::minLength as Validator),
+            "minProperties" => Some(validators::minProperties as Validator),
+            "minimum" => Some(validators::minimum_mongodb as Validator),
+            "multipleOf" => Some(validators::multipleOf_mongodb as Validator),
+            "not" => Some(validators::not as Validator),
+            "oneOf" => Some(validators::oneOf as Validator),
+            "pattern" => Some(validators::pattern as Validator),
+            "patternProperties" => Some(validators::patternProperties as Validator),
+            "properties" => Some(validators::properties as Validator),
+            "required" => Some(validators::required as Validator),
+            "type" => Some(validators::type_mongodb as Validator),
+            "uniqueItems" => Some(validators::uniqueItems as Validator),
+            _ => None,
+        }
+    }
+
+    /// MongoDB doesn't publish a metaschema for `$jsonSchema`, so this one is
+    /// written from its manual. Like MongoDB, it rejects unknown keywords.
+    pub(super) fn get_schema() -> &'static Value {
+        lazy_static! {
+            static ref MONGODB: Value = serde_json::from_str(include_str!("mongodb.json")).unwrap();
+        }
+        &MONGODB
+    }
+}
+
+mod openapi30 {
+    use super::*;
+
+    pub(super) const URL: &str = "https://spec.openapis.org/oas/3.0/schema-object";
+
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "$ref" => Some(validators::ref_ as Validator),
+            "additionalItems" => Some(validators::additionalItems as Validator),
+            "additionalProperties" => Some(validators::additionalProperties as Validator),
+            "allOf" => Some(validators::allOf as Validator),
+            "anyOf" => Some(validators::anyOf_openapi30 as Validator),
+            "dependencies" => Some(validators::dependencies as Validator),
+            "enum" => Some(validators::enum_ as Validator),
+            "format" => Some(validators::format as Validator),
+            "items" => Some(validators::items as Validator),
+            "maxItems" => Some(validators::maxItems as Validator),
+            "maxLength" => Some(validators::maxLength as Validator),
+            "maxProperties" => Some(validators::maxProperties as Validator),
+            "maximum" => Some(validators::maximum_draft4 as Validator),
+            "minItems" => Some(validators::minItems as Validator),
+            "minLength" => Some(validators::minLength as Validator),
+            "minProperties" => Some(validators::minProperties as Validator),
+            "minimum" => Some(validators::minimum_draft4 as Validator),
+            "multipleOf" => Some(validators::multipleOf as Validator),
+            "not" => Some(validators::not as Validator),
+            "oneOf" => Some(validators::oneOf_openapi30 as Validator),
+            "pattern" => Some(validators::pattern as Validator),
+            "patternProperties" => Some(validators::patternProperties as Validator),
+            "properties" => Some(validators::properties as Validator),
+            "required" => Some(validators::required as Validator),
+            "type" => Some(validators::type_openapi30 as Validator),
+            "uniqueItems" => Some(validators::uniqueItems as Validator),
+            _ => None,
+        }
+    }
+
+    /// The metaschema only allows the keywords of the Schema Object, so that
+    /// validating a schema rejects the Draft 4 keywords that OpenAPI doesn't
+    /// support. The Draft 4 validators for those keywords are still in the
+    /// table above, since the metaschema itself uses them.
+    pub(super) fn get_schema() -> &'static Value {
+        lazy_static! {
+            static ref OPENAPI30: Value =
+                serde_json::from_str(include_str!("openapi30.json")).unwrap();
+        }
+        &OPENAPI30
+    }
+
+    pub(super) fn get_format_checker(key: &str) -> Option<FormatChecker> {
+        match key {
+            "byte" => Some(format::byte as FormatChecker),
+            "date" => Some(format::date as FormatChecker),
+            "date-time" => Some(format::datetime as FormatChecker),
+            "email" => Some(format::email as FormatChecker),
+            "hostname" => Some(format::hostname as FormatChecker),
+            "ipv4" => Some(format::ipv4 as FormatChecker),
+            "ipv6" => Some(format::ipv6 as FormatChecker),
+            "regex" => Some(format::regex as FormatChecker),
+            "uri" => Some(format::uri as FormatChecker),
+            _ => None,
+        }
+    }
+}
+
+mod draft202012 {
+    use super::*;
+
+    pub(super) const FORMAT_ASSERTION: &str =
+        "https://json-schema.org/draft/2020-12/vocab/format-assertion";
+
+    pub(super) const VOCABULARIES: &[Vocabulary] = &[
+        Vocabulary {
+            url: "https://json-schema.org/draft/2020-12/vocab/core",
+            get_validator: core,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2020-12/vocab/applicator",
+            get_validator: applicator,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2020-12/vocab/unevaluated",
+            get_validator: unevaluated,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2020-12/vocab/validation",
+            get_validator: validation,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2020-12/vocab/meta-data",
+            get_validator: annotation,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2020-12/vocab/format-annotation",
+            get_validator: annotation,
+        },
+        Vocabulary {
+            url: FORMAT_ASSERTION,
+            get_validator: format_assertion,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2020-12/vocab/content",
+            get_validator: annotation,
+        },
+    ];
+
+    /// The keywords of all of the vocabularies enabled by the metaschema. As
+    /// in Draft 2019-09, `format` is only an annotation by default.
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
+        VOCABULARIES
+            .iter()
+            .filter(|vocabulary| vocabulary.url != FORMAT_ASSERTION)
+            .find_map(|vocabulary| vocabulary.get_validator(key))
+    }
+
+    fn core(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "$dynamicRef" => Some(validators::dynamicRef as Validator),
+            "$ref" => Some(validators::ref_ as Validator),
+            _ => None,
+        }
+    }
+
+    fn applicator(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "additionalProperties" => Some(validators::additionalProperties as Validator),
+            "allOf" => Some(validators::allOf as Validator),
+            "anyOf" => Some(validators::anyOf as Validator),
+            "contains" => Some(validators::contains_draft201909 as Validator),
+            "dependentSchemas" => Some(validators::dependentSchemas as Validator),
+            "if" => Some(validators::if_ as Validator),
+            "items" => Some(validators::items_draft202012 as Validator),
+            "not" => Some(validators::not as Validator),
+            "oneOf" => Some(validators::oneOf as Validator),
+            "patternProperties" => Some(validators::patternProperties as Validator),
+            "prefixItems" => Some(validators::prefixItems as Validator),
+            "properties" => Some(validators::properties as Validator),
+            "propertyNames" => Some(validators::propertyNames as Validator),
+            _ => None,
+        }
+    }
+
+    fn unevaluated(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "unevaluatedItems" => Some(validators::unevaluatedItems as Validator),
+            "unevaluatedProperties" => Some(validators::unevaluatedProperties as Validator),
+            _ => None,
+        }
+    }
+
+    fn validation(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "const" => Some(validators::const_ as Validator),
+            "dependentRequired" => Some(validators::dependentRequired as Validator),
+            "enum" => Some(validators::enum_ as Validator),
+            "exclusiveMaximum" => Some(validators::exclusiveMaximum as Validator),
+            "exclusiveMinimum" => Some(validators::exclusiveMinimum as Validator),
+            "maxItems" => Some(validators::maxItems as Validator),
+            "maxLength" => Some(validators::maxLength as Validator),
+            "maxProperties" => Some(validators::maxProperties as Validator),
+            "maximum" => Some(validators::maximum as Validator),
+            "minItems" => Some(validators::minItems as Validator),
+            "minLength" => Some(validators::minLength as Validator),
+            "minProperties" => Some(validators::minProperties as Validator),
+            "minimum" => Some(validators::minimum as Validator),
+            "multipleOf" => Some(validators::multipleOf as Validator),
+            "pattern" => Some(validators::pattern as Validator),
+            "required" => Some(validators::required as Validator),
+            "type" => Some(validators::type_ as Validator),
+            "uniqueItems" => Some(validators::uniqueItems as Validator),
+            _ => None,
+        }
+    }
+
+    fn format_assertion(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "format" => Some(validators::format as Validator),
+            _ => None,
+        }
+    }
+
+    pub(super) fn get_schema() -> &'static Value {
+        lazy_static! {
+            static ref DRAFT202012: Value =
+                serde_json::from_str(include_str!("draft202012.json")).unwrap();
+        }
+        &DRAFT202012
+    }
+
+    /// Get one of the vocabulary metaschemas that the main metaschema is
+    /// composed of.
+    pub(super) fn get_vocabulary_schema(url: &str) -> Option<&'static Value> {
+        lazy_static! {
+            static ref CORE: Value =
+                serde_json::from_str(include_str!("draft202012-core.json")).unwrap();
+            static ref APPLICATOR: Value =
+                serde_json::from_str(include_str!("draft202012-applicator.json")).unwrap();
+            static ref UNEVALUATED: Value =
+                serde_json::from_str(include_str!("draft202012-unevaluated.json")).unwrap();
+            static ref VALIDATION: Value =
+                serde_json::from_str(include_str!("draft202012-validation.json")).unwrap();
+            static ref META_DATA: Value =
+                serde_json::from_str(include_str!("draft202012-meta-data.json")).unwrap();
+            static ref FORMAT_ANNOTATION: Value =
+                serde_json::from_str(include_str!("draft202012-format-annotation.json")).unwrap();
+            static ref FORMAT_ASSERTION: Value =
+                serde_json::from_str(include_str!("draft202012-format-assertion.json")).unwrap();
+            static ref CONTENT: Value =
+                serde_json::from_str(include_str!("draft202012-content.json")).unwrap();
+        }
+        match url {
+            "https://json-schema.org/draft/2020-12/meta/core" => Some(&CORE),
+            "https://json-schema.org/draft/2020-12/meta/applicator" => Some(&APPLICATOR),
+            "https://json-schema.org/draft/2020-12/meta/unevaluated" => Some(&UNEVALUATED),
+            "https://json-schema.org/draft/2020-12/meta/validation" => Some(&VALIDATION),
+            "https://json-schema.org/draft/2020-12/meta/meta-data" => Some(&META_DATA),
+            "https://json-schema.org/draft/2020-12/meta/format-annotation" => {
+                Some(&FORMAT_ANNOTATION)
+            }
+            "https://json-schema.org/draft/2020-12/meta/format-assertion" => {
+                Some(&FORMAT_ASSERTION)
+            }
+            "https://json-schema.org/draft/2020-12/meta/content" => Some(&CONTENT),
+            _ => None,
+        }
+    }
+
+    /// As in Draft 2019-09, `format` is only an annotation by default.
+    pub(super) fn get_format_checker(key: &str) -> Option<FormatChecker> {
+        super::draft7::get_format_checker(key)
+    }
+}
+
+mod draft201909 {
+    use super::*;
+
+    pub(super) const VOCABULARIES: &[Vocabulary] = &[
+        Vocabulary {
+            url: "https://json-schema.org/draft/2019-09/vocab/core",
+            get_validator: core,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2019-09/vocab/applicator",
+            get_validator: applicator,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2019-09/vocab/validation",
+            get_validator: validation,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2019-09/vocab/meta-data",
+            get_validator: annotation,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2019-09/vocab/format",
+            get_validator: annotation,
+        },
+        Vocabulary {
+            url: "https://json-schema.org/draft/2019-09/vocab/content",
+            get_validator: annotation,
+        },
+    ];
+
+    /// The keywords of all of the vocabularies enabled by the metaschema.
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
+        VOCABULARIES
+            .iter()
+            .find_map(|vocabulary| vocabulary.get_validator(key))
+    }
+
+    fn core(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "$recursiveRef" => Some(validators::recursiveRef as Validator),
+            "$ref" => Some(validators::ref_ as Validator),
+            _ => None,
+        }
+    }
+
+    fn applicator(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "additionalItems" => Some(validators::additionalItems as Validator),
+            "additionalProperties" => Some(validators::additionalProperties as Validator),
+            "allOf" => Some(validators::allOf as Validator),
+            "anyOf" => Some(validators::anyOf as Validator),
+            "contains" => Some(validators::contains_draft201909 as Validator),
+            "dependentSchemas" => Some(validators::dependentSchemas as Validator),
+            "if" => Some(validators::if_ as Validator),
+            "items" => Some(validators::items as Validator),
+            "not" => Some(validators::not as Validator),
+            "oneOf" => Some(validators::oneOf as Validator),
+            "patternProperties" => Some(validators::patternProperties as Validator),
+            "properties" => Some(validators::properties as Validator),
+            "propertyNames" => Some(validators::propertyNames as Validator),
+            "unevaluatedItems" => Some(validators::unevaluatedItems as Validator),
+            "unevaluatedProperties" => Some(validators::unevaluatedProperties as Validator),
+            _ => None,
+        }
+    }
+
+    fn validation(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "const" => Some(validators::const_ as Validator),
+            "dependentRequired" => Some(validators::dependentRequired as Validator),
+            "enum" => Some(validators::enum_ as Validator),
+            "exclusiveMaximum" => Some(validators::exclusiveMaximum as Validator),
+            "exclusiveMinimum" => Some(validators::exclusiveMinimum as Validator),
+            "maxItems" => Some(validators::maxItems as Validator),
+            "maxLength" => Some(validators::maxLength as Validator),
+            "maxProperties" => Some(validators::maxProperties as Validator),
+            "maximum" => Some(validators::maximum as Validator),
+            "minItems" => Some(validators::minItems as Validator),
+            "minLength" => Some(validators::minLength as Validator),
+            "minProperties" => Some(validators::minProperties as Validator),
+            "minimum" => Some(validators::minimum as Validator),
+            "multipleOf" => Some(validators::multipleOf as Validator),
+            "pattern" => Some(validators::pattern as Validator),
+            "required" => Some(validators::required as Validator),
+            "type" => Some(validators::type_ as Validator),
+            "uniqueItems" => Some(validators::uniqueItems as Validator),
+            _ => None,
+        }
+    }
+
+    pub(super) fn get_schema() -> &'static Value {
+        lazy_static! {
+            static ref DRAFT201909: Value =
+                serde_json::from_str(include_str!("draft201909.json")).unwrap();
+        }
+        &DRAFT201909
+    }
+
+    /// Get one of the vocabulary metaschemas that the main metaschema is
+    /// composed of.
+    pub(super) fn get_vocabulary_schema(url: &str) -> Option<&'static Value> {
+        lazy_static! {
+            static ref CORE: Value =
+                serde_json::from_str(include_str!("draft201909-core.json")).unwrap();
+            static ref APPLICATOR: Value =
+                serde_json::from_str(include_str!("draft201909-applicator.json")).unwrap();
+            static ref VALIDATION: Value =
+                serde_json::from_str(include_str!("draft201909-validation.json")).unwrap();
+            static ref META_DATA: Value =
+                serde_json::from_str(include_str!("draft201909-meta-data.json")).unwrap();
+            static ref FORMAT: Value =
+                serde_json::from_str(include_str!("draft201909-format.json")).unwrap();
+            static ref CONTENT: Value =
+                serde_json::from_str(include_str!("draft201909-content.json")).unwrap();
+        }
+        match url {
+            "https://json-schema.org/draft/2019-09/meta/core" => Some(&CORE),
+            "https://json-schema.org/draft/2019-09/meta/applicator" => Some(&APPLICATOR),
+            "https://json-schema.org/draft/2019-09/meta/validation" => Some(&VALIDATION),
+            "https://json-schema.org/draft/2019-09/meta/meta-data" => Some(&META_DATA),
+            "https://json-schema.org/draft/2019-09/meta/format" => Some(&FORMAT),
+            "https://json-schema.org/draft/2019-09/meta/content" => Some(&CONTENT),
+            _ => None,
+        }
+    }
+
+    /// Draft 2019-09 only treats `format` as an annotation, so it isn't in the
+    /// validator table above. The set of known formats is the same as Draft 7.
+    pub(super) fn get_format_checker(key: &str) -> Option<FormatChecker> {
+        super::draft7::get_format_checker(key)
     }
 }
 
 mod draft7 {
     use super::*;
 
-    pub(super) fn get_validator(key: &str) -> Option<Validator> {
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
         match key {
             "$ref" => Some(validators::ref_ as Validator),
             "additionalItems" => Some(validators::additionalItems as Validator),
@@ -132,7 +1049,7 @@ mod draft7 {
 mod draft6 {
     use super::*;
 
-    pub(super) fn get_validator(key: &str) -> Option<Validator> {
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
         match key {
             "$ref" => Some(validators::ref_ as Validator),
             "additionalItems" => Some(validators::additionalItems as Validator),
@@ -198,7 +1115,7 @@ mod draft6 {
 mod draft4 {
     use super::*;
 
-    pub(super) fn get_validator(key: &str) -> Option<Validator> {
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
         match key {
             "$ref" => Some(validators::ref_ as Validator),
             "additionalItems" => Some(validators::additionalItems as Validator),
@@ -251,16 +1168,107 @@ mod draft4 {
     }
 }
 
+mod draft3 {
+    use super::*;
+
+    pub(super) fn get_validator(key: &str) -> Option<Validator<'_>> {
+        match key {
+            "$ref" => Some(validators::ref_ as Validator),
+            "additionalItems" => Some(validators::additionalItems as Validator),
+            "additionalProperties" => Some(validators::additionalProperties as Validator),
+            "dependencies" => Some(validators::dependencies as Validator),
+            "disallow" => Some(validators::disallow as Validator),
+            "divisibleBy" => Some(validators::multipleOf as Validator),
+            "enum" => Some(validators::enum_ as Validator),
+            "extends" => Some(validators::extends as Validator),
+            "format" => Some(validators::format as Validator),
+            "items" => Some(validators::items as Validator),
+            "maxItems" => Some(validators::maxItems as Validator),
+            "maxLength" => Some(validators::maxLength as Validator),
+            "maximum" => Some(validators::maximum_draft4 as Validator),
+            "minItems" => Some(validators::minItems as Validator),
+            "minLength" => Some(validators::minLength as Validator),
+            "minimum" => Some(validators::minimum_draft4 as Validator),
+            "pattern" => Some(validators::pattern as Validator),
+            "patternProperties" => Some(validators::patternProperties as Validator),
+            "properties" => Some(validators::properties_draft3 as Validator),
+            "type" => Some(validators::type_draft3 as Validator),
+            "uniqueItems" => Some(validators::uniqueItems as Validator),
+            _ => None,
+        }
+    }
+
+    pub(super) fn get_schema() -> &'static Value {
+        lazy_static! {
+            static ref DRAFT3: Value = serde_json::from_str(include_str!("draft3.json")).unwrap();
+        }
+        &DRAFT3
+    }
+
+    pub(super) fn get_format_checker(key: &str) -> Option<FormatChecker> {
+        match key {
+            "color" => Some(format::color as FormatChecker),
+            "date" => Some(format::date as FormatChecker),
+            "date-time" => Some(format::datetime as FormatChecker),
+            "email" => Some(format::email as FormatChecker),
+            "host-name" => Some(format::hostname as FormatChecker),
+            "ip-address" => Some(format::ipv4 as FormatChecker),
+            "ipv6" => Some(format::ipv6 as FormatChecker),
+            "regex" => Some(format::regex as FormatChecker),
+            "time" => Some(format::time as FormatChecker),
+            "uri" => Some(format::uri as FormatChecker),
+            _ => None,
+        }
+    }
+}
+
 /// Get the `Draft` from a JSON Schema URL.
+///
+/// The official URLs of the drafts are matched with either `http` or `https`,
+/// and with or without an empty fragment, so that
+/// `http://json-schema.org/draft-07/schema#` and
+/// `https://json-schema.org/draft-07/schema` are both Draft 7. The URLs of
+/// user-defined dialects may also have an empty fragment.
 pub fn draft_from_url(url: &str) -> Option<Draft> {
-    match url {
-        "http://json-schema.org/draft-07/schema" => Some(Draft::Draft7),
-        "http://json-schema.org/draft-06/schema" => Some(Draft::Draft6),
-        "http://json-schema.org/draft-04/schema" => Some(Draft::Draft4),
-        _ => None,
+    let url = url.trim_end_matches('#');
+    let location = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    match location {
+        Some("json-schema.org/draft/2020-12/schema") => Some(Draft::Draft202012),
+        Some("json-schema.org/draft/2019-09/schema")
+        | Some("json-schema.org/draft/2019-09/hyper-schema") => Some(Draft::Draft201909),
+        Some("json-schema.org/draft-07/schema") | Some("json-schema.org/draft-07/hyper-schema") => {
+            Some(Draft::Draft7)
+        }
+        Some("json-schema.org/draft-06/schema") => Some(Draft::Draft6),
+        Some("json-schema.org/draft-04/schema") => Some(Draft::Draft4),
+        Some("json-schema.org/draft-03/schema") => Some(Draft::Draft3),
+        Some("spec.openapis.org/oas/3.0/schema-object") => Some(Draft::OpenApi30),
+        Some("kubernetes.io/schemas/apiextensions/v1/JSONSchemaProps") => Some(Draft::Kubernetes),
+        Some("www.mongodb.com/docs/manual/reference/operator/query/jsonSchema/") => {
+            Some(Draft::MongoDb)
+        }
+        _ => DIALECTS
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|dialect| dialect.url == url)
+            .map(|dialect| Draft::Custom(dialect)),
     }
 }
 
+/// Get one of the built-in metaschema documents from its URL. This includes
+/// the main metaschema of each draft, as well as any vocabulary metaschemas it
+/// is composed of.
+pub(crate) fn metaschema_from_url(url: &str) -> Option<&'static Value> {
+    draft_from_url(url)
+        .map(Draft::get_schema)
+        .or_else(|| draft201909::get_vocabulary_schema(url))
+        .or_else(|| draft202012::get_vocabulary_schema(url))
+}
+
 /// Get the `Draft` from a JSON Schema.
 pub fn draft_from_schema(schema: &Value) -> Option<Draft> {
     schema
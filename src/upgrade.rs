@@ -0,0 +1,753 @@
+//! Rewriting schemas from one draft to a newer one.
+
+use std::fmt;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::ValidationError;
+use crate::resolver;
+use crate::schemas::{self, Draft};
+
+/// A change made to a schema when upgrading it to a newer draft.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// A JSON pointer to the location of the change, in the upgraded schema.
+    pub pointer: String,
+    /// A description of the change.
+    pub msg: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.pointer, self.msg)
+    }
+}
+
+/// What the steps know about the schema resource that an object is in.
+#[derive(Debug, Clone, Copy, Default)]
+struct Scope {
+    /// Whether the resource has `"$recursiveAnchor": true`, which makes the
+    /// `$recursiveRef`s to it dynamic.
+    recursive_anchor: bool,
+}
+
+/// A rewrite of a schema object from one draft to the next.
+type Step =
+    fn(object: &mut Map<String, Value>, scope: Scope, pointer: &str, changes: &mut Vec<Change>);
+
+/// The drafts that can be upgraded, in order.
+const DRAFTS: &[Draft] = &[
+    Draft::Draft3,
+    Draft::Draft4,
+    Draft::Draft6,
+    Draft::Draft7,
+    Draft::Draft201909,
+    Draft::Draft202012,
+];
+
+/// The steps from each of the `DRAFTS` to the next.
+const STEPS: &[Step] = &[
+    draft3_to_draft4,
+    draft4_to_draft6,
+    draft6_to_draft7,
+    draft7_to_draft201909,
+    draft201909_to_draft202012,
+];
+
+fn draft_index(draft: Draft) -> Option<usize> {
+    DRAFTS.iter().position(|x| *x == draft)
+}
+
+/// Upgrade a schema from one draft to a newer one.
+///
+/// The schema is written in the draft `from`, or if it is `None`, in the draft
+/// given by its `$schema` entry, or otherwise Draft 7. Embedded schema
+/// resources that declare their own `$schema` are upgraded from that draft.
+/// Only the built-in drafts of JSON Schema, from Draft 3 to Draft 2020-12, can
+/// be upgraded.
+///
+/// Returns the upgraded schema, along with the list of changes made to it.
+///
+/// ## Example:
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
+/// # use serde_json::json;
+/// # use jsonschema_valid::schemas::Draft;
+/// let schema = json!({"id": "http://example.com/price.json", "exclusiveMinimum": true, "minimum": 0});
+/// let (upgraded, changes) = jsonschema_valid::upgrade(&schema, Some(Draft::Draft4), Draft::Draft7)?;
+/// assert_eq!(upgraded, json!({"$id": "http://example.com/price.json", "exclusiveMinimum": 0}));
+/// assert_eq!(changes.len(), 2);
+/// # Ok(()) }
+/// ```
+pub fn upgrade(
+    schema: &Value,
+    from: Option<Draft>,
+    to: Draft,
+) -> Result<(Value, Vec<Change>), ValidationError> {
+    let from = from.unwrap_or_else(|| schemas::draft_from_schema(schema).unwrap_or(Draft::Draft7));
+    match (draft_index(from), draft_index(to)) {
+        (Some(i), Some(j)) if i <= j => {
+            let mut schema = schema.clone();
+            let mut changes = Vec::new();
+            upgrade_schema(&mut schema, "", i, j, Scope::default(), &mut changes);
+            Ok((schema, changes))
+        }
+        _ => Err(ValidationError::new(
+            &format!("Can't upgrade a schema from {:?} to {:?}", from, to),
+            None,
+            None,
+        )),
+    }
+}
+
+/// Upgrade a schema, and then all of its subschemas, from the draft at index
+/// `from` of `DRAFTS` to the one at index `to`.
+fn upgrade_schema(
+    schema: &mut Value,
+    pointer: &str,
+    from: usize,
+    to: usize,
+    scope: Scope,
+    changes: &mut Vec<Change>,
+) {
+    let is_resource = pointer.is_empty()
+        || matches!(resolver::id_of(DRAFTS[from], schema), Some(id) if !id.starts_with('#'));
+    if let Value::Object(object) = schema {
+        let scope = if is_resource {
+            Scope {
+                recursive_anchor: object.get("$recursiveAnchor") == Some(&Value::Bool(true)),
+            }
+        } else {
+            scope
+        };
+        for step in &STEPS[from..to] {
+            step(object, scope, pointer, changes);
+        }
+        if from < to && object.contains_key("$schema") {
+            object.insert("$schema".to_string(), json!(DRAFTS[to].get_url()));
+            add_change(
+                changes,
+                pointer,
+                "$schema",
+                "Set to the URL of the new draft",
+            );
+        }
+
+        for (keyword, value) in object.iter_mut() {
+            let pointer = format!("{}/{}", pointer, escape(keyword));
+            match value {
+                Value::Array(subschemas) if resolver::takes_schema(keyword) => {
+                    for (index, subschema) in subschemas.iter_mut().enumerate() {
+                        upgrade_subschema(
+                            subschema,
+                            &format!("{}/{}", pointer, index),
+                            from,
+                            to,
+                            scope,
+                            changes,
+                        );
+                    }
+                }
+                subschema if resolver::takes_schema(keyword) => {
+                    upgrade_subschema(subschema, &pointer, from, to, scope, changes)
+                }
+                Value::Object(subschemas) if resolver::takes_named_schemas(keyword) => {
+                    for (name, subschema) in subschemas.iter_mut() {
+                        let pointer = format!("{}/{}", pointer, escape(name));
+                        upgrade_subschema(subschema, &pointer, from, to, scope, changes);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Upgrade a subschema, which may be an embedded resource written in another
+/// draft. Resources written in a draft that can't be upgraded to the target
+/// one are left as they are.
+fn upgrade_subschema(
+    schema: &mut Value,
+    pointer: &str,
+    from: usize,
+    to: usize,
+    scope: Scope,
+    changes: &mut Vec<Change>,
+) {
+    if let Some(from) = draft_index(resolver::draft_of(DRAFTS[from], schema)) {
+        if from <= to {
+            upgrade_schema(schema, pointer, from, to, scope, changes);
+        }
+    }
+}
+
+fn add_change(changes: &mut Vec<Change>, pointer: &str, keyword: &str, msg: &str) {
+    changes.push(Change {
+        pointer: format!("{}/{}", pointer, escape(keyword)),
+        msg: msg.to_string(),
+    });
+}
+
+/// Escape a reference token of a JSON pointer.
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn rename(
+    object: &mut Map<String, Value>,
+    pointer: &str,
+    changes: &mut Vec<Change>,
+    from: &str,
+    to: &str,
+) {
+    if object.contains_key(to) {
+        return;
+    }
+    if let Some(value) = object.remove(from) {
+        object.insert(to.to_string(), value);
+        add_change(changes, pointer, to, &format!("Renamed from {}", from));
+    }
+}
+
+/// Add schemas to an applicator keyword, such as `allOf`. If the keyword is
+/// already in use, the schemas are added under `allOf` instead.
+fn add_applicator(object: &mut Map<String, Value>, keyword: &str, value: Value) {
+    match (keyword, object.get_mut(keyword)) {
+        (_, None) => {
+            object.insert(keyword.to_string(), value);
+        }
+        ("allOf", Some(Value::Array(all_of))) => {
+            if let Value::Array(value) = value {
+                all_of.extend(value);
+            }
+        }
+        _ => add_applicator(object, "allOf", json!([{ keyword: value }])),
+    }
+}
+
+/// Turn a Draft 3 list of types, which may include schemas, into a schema.
+fn types_to_schema(types: &Value) -> Value {
+    let types: Vec<&Value> = match types {
+        Value::Array(types) => types.iter().collect(),
+        types => vec![types],
+    };
+    let mut schemas: Vec<Value> = Vec::new();
+    let mut names: Vec<&Value> = Vec::new();
+    for x in types {
+        match x {
+            Value::String(name) if name == "any" => return json!({}),
+            Value::String(_) => names.push(x),
+            _ => schemas.push(x.clone()),
+        }
+    }
+    match names.len() {
+        0 => {}
+        1 => schemas.insert(0, json!({ "type": names[0] })),
+        _ => schemas.insert(0, json!({ "type": names })),
+    }
+    if schemas.len() == 1 {
+        schemas.remove(0)
+    } else {
+        json!({ "anyOf": schemas })
+    }
+}
+
+/// Whether a reference token of a JSON pointer is an array index.
+fn is_index(token: Option<&str>) -> bool {
+    token.and_then(|x| x.parse::<usize>().ok()).is_some()
+}
+
+/// Rewrite the keywords of the JSON pointer in a `$ref`, following the
+/// renaming of keywords done by `rename`. The pointer may be into another
+/// document, which is taken to be upgraded along with this one.
+fn rename_in_ref(
+    object: &mut Map<String, Value>,
+    pointer: &str,
+    changes: &mut Vec<Change>,
+    rename: fn(&str, Option<&str>) -> Option<&'static str>,
+) {
+    let (document, fragment) = match object
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|x| x.split_once("#/"))
+    {
+        Some(split) => split,
+        None => return,
+    };
+    let document = document.to_string();
+    let mut segments: Vec<String> = fragment.split('/').map(String::from).collect();
+    let mut is_keyword = true;
+    let mut changed = false;
+    for i in 0..segments.len() {
+        if !is_keyword {
+            is_keyword = true;
+            continue;
+        }
+        let next = segments.get(i + 1).cloned();
+        let next = next.as_deref();
+        let keyword = segments[i].clone();
+        if let Some(renamed) = rename(&keyword, next) {
+            segments[i] = renamed.to_string();
+            changed = true;
+        }
+        is_keyword = match keyword.as_str() {
+            "$defs" | "definitions" | "dependencies" | "dependentSchemas" | "patternProperties"
+            | "properties" => false,
+            "allOf" | "anyOf" | "oneOf" | "items" | "prefixItems" | "extends" => !is_index(next),
+            _ => true,
+        };
+    }
+    if changed {
+        object.insert(
+            "$ref".to_string(),
+            json!(format!("{}#/{}", document, segments.join("/"))),
+        );
+        add_change(changes, pointer, "$ref", "Updated for the renamed keywords");
+    }
+}
+
+fn draft3_to_draft4(
+    object: &mut Map<String, Value>,
+    _scope: Scope,
+    pointer: &str,
+    changes: &mut Vec<Change>,
+) {
+    rename(object, pointer, changes, "divisibleBy", "multipleOf");
+
+    // Draft 3 marks a property as required within its own schema.
+    if let Some(Value::Bool(_)) = object.get("required") {
+        object.remove("required");
+        add_change(
+            changes,
+            pointer,
+            "required",
+            "Removed, as it only applies to properties",
+        );
+    }
+    let mut required = Vec::new();
+    if let Some(Value::Object(properties)) = object.get_mut("properties") {
+        for (name, property) in properties.iter_mut() {
+            if let Some(Value::Bool(is_required)) =
+                property.as_object_mut().and_then(|x| x.remove("required"))
+            {
+                let property_pointer = format!("{}/properties/{}", pointer, escape(name));
+                add_change(
+                    changes,
+                    &property_pointer,
+                    "required",
+                    "Moved to the parent's required",
+                );
+                if is_required {
+                    required.push(json!(name));
+                }
+            }
+        }
+    }
+    if !required.is_empty() {
+        object.insert("required".to_string(), Value::Array(required));
+        add_change(
+            changes,
+            pointer,
+            "required",
+            "Added the properties marked as required",
+        );
+    }
+
+    if let Some(types) = object.remove("type") {
+        let schema = types_to_schema(&types);
+        match schema.as_object() {
+            Some(x) if x.is_empty() => {
+                add_change(changes, pointer, "type", "Removed the \"any\" type");
+            }
+            Some(x) if x.len() == 1 && x.contains_key("type") => {
+                object.insert("type".to_string(), x["type"].clone());
+            }
+            Some(x) if x.len() == 1 && x.contains_key("anyOf") => {
+                add_applicator(object, "anyOf", x["anyOf"].clone());
+                add_change(
+                    changes,
+                    pointer,
+                    "type",
+                    "Replaced the schemas in type with anyOf",
+                );
+            }
+            _ => {
+                add_applicator(object, "allOf", json!([schema]));
+                add_change(
+                    changes,
+                    pointer,
+                    "type",
+                    "Replaced the schema in type with allOf",
+                );
+            }
+        }
+    }
+    if let Some(types) = object.remove("disallow") {
+        add_applicator(object, "not", types_to_schema(&types));
+        add_change(changes, pointer, "disallow", "Replaced with not");
+    }
+    if let Some(extends) = object.remove("extends") {
+        let extends = match extends {
+            Value::Array(_) => extends,
+            _ => json!([extends]),
+        };
+        add_applicator(object, "allOf", extends);
+        add_change(changes, pointer, "extends", "Replaced with allOf");
+    }
+
+    if let Some(Value::Object(dependencies)) = object.get_mut("dependencies") {
+        for (name, dependency) in dependencies.iter_mut() {
+            if let Value::String(_) = dependency {
+                *dependency = json!([dependency]);
+                let dependency_pointer = format!("{}/dependencies/{}", pointer, escape(name));
+                changes.push(Change {
+                    pointer: dependency_pointer,
+                    msg: "Replaced the property name with an array".to_string(),
+                });
+            }
+        }
+    }
+
+    let format = match object.get("format").and_then(Value::as_str) {
+        Some("host-name") => "hostname",
+        Some("ip-address") => "ipv4",
+        _ => return,
+    };
+    object.insert("format".to_string(), json!(format));
+    add_change(changes, pointer, "format", "Renamed the format");
+}
+
+fn draft4_to_draft6(
+    object: &mut Map<String, Value>,
+    _scope: Scope,
+    pointer: &str,
+    changes: &mut Vec<Change>,
+) {
+    if let Some(Value::String(_)) = object.get("id") {
+        rename(object, pointer, changes, "id", "$id");
+    }
+
+    // The exclusive bounds become numbers, rather than modifiers of the
+    // inclusive ones.
+    for (exclusive, inclusive) in &[
+        ("exclusiveMaximum", "maximum"),
+        ("exclusiveMinimum", "minimum"),
+    ] {
+        match object.get(*exclusive) {
+            Some(Value::Bool(true)) if object.contains_key(*inclusive) => {
+                let bound = object.remove(*inclusive).unwrap();
+                object.insert(exclusive.to_string(), bound);
+                add_change(
+                    changes,
+                    pointer,
+                    exclusive,
+                    &format!("Replaced with the value of {}", inclusive),
+                );
+            }
+            Some(Value::Bool(_)) => {
+                object.remove(*exclusive);
+                add_change(changes, pointer, exclusive, "Removed, as it has no effect");
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draft6_to_draft7(
+    _object: &mut Map<String, Value>,
+    _scope: Scope,
+    _pointer: &str,
+    _changes: &mut Vec<Change>,
+) {
+}
+
+fn draft7_to_draft201909(
+    object: &mut Map<String, Value>,
+    _scope: Scope,
+    pointer: &str,
+    changes: &mut Vec<Change>,
+) {
+    // The siblings of `$ref` were ignored, but are now evaluated.
+    if object.contains_key("$ref") {
+        let siblings: Vec<String> = object
+            .keys()
+            .filter(|k| *k != "$ref" && Draft::Draft7.get_validator(k).is_some())
+            .cloned()
+            .collect();
+        for sibling in siblings {
+            object.remove(&sibling);
+            add_change(
+                changes,
+                pointer,
+                &sibling,
+                "Removed, as it is ignored next to $ref",
+            );
+        }
+    }
+
+    // A plain-name fragment in `$id` becomes an `$anchor`, as ids can no
+    // longer have one.
+    let split = match object.get("$id").and_then(Value::as_str) {
+        Some(id) => match id.split_once('#') {
+            Some((id, anchor)) if !anchor.is_empty() && !object.contains_key("$anchor") => {
+                Some((id.to_string(), anchor.to_string()))
+            }
+            _ => None,
+        },
+        None => None,
+    };
+    if let Some((id, anchor)) = split {
+        if id.is_empty() {
+            object.remove("$id");
+            add_change(changes, pointer, "$id", "Replaced with $anchor");
+        } else {
+            object.insert("$id".to_string(), json!(id));
+            add_change(changes, pointer, "$id", "Moved the fragment to $anchor");
+        }
+        object.insert("$anchor".to_string(), json!(anchor));
+        add_change(changes, pointer, "$anchor", "Split from $id");
+    }
+
+    rename(object, pointer, changes, "definitions", "$defs");
+    rename_in_ref(object, pointer, changes, |keyword, _next| match keyword {
+        "definitions" => Some("$defs"),
+        _ => None,
+    });
+
+    if let Some(Value::Object(dependencies)) = object.remove("dependencies") {
+        let (required, schemas): (Map<String, Value>, Map<String, Value>) =
+            dependencies.into_iter().partition(|(_, x)| x.is_array());
+        if !required.is_empty() {
+            object.insert("dependentRequired".to_string(), Value::Object(required));
+            add_change(
+                changes,
+                pointer,
+                "dependentRequired",
+                "Split from dependencies",
+            );
+        }
+        if !schemas.is_empty() {
+            object.insert("dependentSchemas".to_string(), Value::Object(schemas));
+            add_change(
+                changes,
+                pointer,
+                "dependentSchemas",
+                "Split from dependencies",
+            );
+        }
+    }
+}
+
+fn draft201909_to_draft202012(
+    object: &mut Map<String, Value>,
+    scope: Scope,
+    pointer: &str,
+    changes: &mut Vec<Change>,
+) {
+    match object.get("items") {
+        Some(Value::Array(_)) => {
+            rename(object, pointer, changes, "items", "prefixItems");
+            rename(object, pointer, changes, "additionalItems", "items");
+        }
+        _ => {
+            if object.remove("additionalItems").is_some() {
+                add_change(
+                    changes,
+                    pointer,
+                    "additionalItems",
+                    "Removed, as it has no effect",
+                );
+            }
+        }
+    }
+    rename_in_ref(object, pointer, changes, |keyword, next| match keyword {
+        "items" if is_index(next) => Some("prefixItems"),
+        _ => None,
+    });
+
+    // `$recursiveAnchor` and `$recursiveRef` become a `$dynamicAnchor` and a
+    // `$dynamicRef` to it, under a fixed name. A `$recursiveRef` to a
+    // resource without the anchor is only a `$ref`.
+    match object.remove("$recursiveAnchor") {
+        Some(Value::Bool(true)) => {
+            object.insert("$dynamicAnchor".to_string(), json!("meta"));
+            add_change(
+                changes,
+                pointer,
+                "$dynamicAnchor",
+                "Replaced $recursiveAnchor",
+            );
+        }
+        Some(_) => add_change(
+            changes,
+            pointer,
+            "$recursiveAnchor",
+            "Removed, as it has no effect",
+        ),
+        None => {}
+    }
+    if let Some(Value::String(reference)) = object.remove("$recursiveRef") {
+        if reference == "#" && scope.recursive_anchor {
+            object.insert("$dynamicRef".to_string(), json!("#meta"));
+            add_change(changes, pointer, "$dynamicRef", "Replaced $recursiveRef");
+        } else if object.contains_key("$ref") {
+            add_applicator(object, "allOf", json!([{ "$ref": reference }]));
+            add_change(changes, pointer, "allOf", "Replaced $recursiveRef");
+        } else {
+            object.insert("$ref".to_string(), json!(reference));
+            add_change(changes, pointer, "$ref", "Replaced $recursiveRef");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_draft4() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-04/schema#",
+            "id": "http://example.com/root.json",
+            "properties": {
+                "price": { "$ref": "#/definitions/price", "type": "string" },
+                "tags": { "items": [{ "type": "string" }], "additionalItems": false }
+            },
+            "dependencies": { "a": ["b"], "c": { "required": ["d"] } },
+            "definitions": {
+                "price": { "minimum": 0, "exclusiveMinimum": true, "maximum": 10, "exclusiveMaximum": false }
+            }
+        });
+        let (upgraded, changes) = upgrade(&schema, None, Draft::Draft202012).unwrap();
+        assert_eq!(
+            upgraded,
+            json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "$id": "http://example.com/root.json",
+                "properties": {
+                    "price": { "$ref": "#/$defs/price" },
+                    "tags": { "prefixItems": [{ "type": "string" }], "items": false }
+                },
+                "dependentRequired": { "a": ["b"] },
+                "dependentSchemas": { "c": { "required": ["d"] } },
+                "$defs": {
+                    "price": { "exclusiveMinimum": 0, "maximum": 10 }
+                }
+            })
+        );
+        let pointers: Vec<&str> = changes.iter().map(|x| x.pointer.as_str()).collect();
+        assert!(pointers.contains(&"/$id"));
+        assert!(pointers.contains(&"/$defs"));
+        assert!(pointers.contains(&"/properties/price/type"));
+        assert!(pointers.contains(&"/properties/price/$ref"));
+        assert!(pointers.contains(&"/properties/tags/prefixItems"));
+        assert!(pointers.contains(&"/$defs/price/exclusiveMinimum"));
+        assert!(pointers.contains(&"/$defs/price/exclusiveMaximum"));
+
+        let cfg = crate::Config::from_schema(&upgraded, None).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!({"price": 0})).is_err());
+        assert!(cfg.validate(&json!({"price": 1})).is_ok());
+    }
+
+    #[test]
+    fn test_upgrade_draft3() {
+        let schema = json!({
+            "properties": {
+                "a": { "type": ["string", { "minimum": 0 }], "required": true },
+                "b": { "disallow": "any", "extends": { "title": "b" } }
+            }
+        });
+        let (upgraded, _) = upgrade(&schema, Some(Draft::Draft3), Draft::Draft4).unwrap();
+        assert_eq!(
+            upgraded,
+            json!({
+                "properties": {
+                    "a": { "anyOf": [{ "type": "string" }, { "minimum": 0 }] },
+                    "b": { "not": {}, "allOf": [{ "title": "b" }] }
+                },
+                "required": ["a"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_upgrade_anchors() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "properties": {
+                "a": { "$ref": "#foo" },
+                "b": { "$ref": "other.json#/definitions/x" }
+            },
+            "definitions": {
+                "foo": { "$id": "#foo", "type": "integer" },
+                "bar": { "$id": "other.json#bar" }
+            }
+        });
+        let (upgraded, _) = upgrade(&schema, None, Draft::Draft201909).unwrap();
+        assert_eq!(
+            upgraded,
+            json!({
+                "$schema": "https://json-schema.org/draft/2019-09/schema",
+                "properties": {
+                    "a": { "$ref": "#foo" },
+                    "b": { "$ref": "other.json#/$defs/x" }
+                },
+                "$defs": {
+                    "foo": { "$anchor": "foo", "type": "integer" },
+                    "bar": { "$id": "other.json", "$anchor": "bar" }
+                }
+            })
+        );
+        let cfg = crate::Config::from_schema(&upgraded, None).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!({"a": 1})).is_ok());
+        assert!(cfg.validate(&json!({"a": "1"})).is_err());
+    }
+
+    #[test]
+    fn test_upgrade_recursive_ref() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2019-09/schema",
+            "$recursiveAnchor": true,
+            "properties": {
+                "a": { "$recursiveRef": "#" },
+                "b": {
+                    "$id": "http://example.com/b.json",
+                    "properties": { "c": { "$recursiveRef": "#", "$ref": "#/$defs/d" } },
+                    "$defs": { "d": { "type": "object" } }
+                }
+            }
+        });
+        let (upgraded, _) = upgrade(&schema, None, Draft::Draft202012).unwrap();
+        assert_eq!(
+            upgraded,
+            json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "$dynamicAnchor": "meta",
+                "properties": {
+                    "a": { "$dynamicRef": "#meta" },
+                    "b": {
+                        "$id": "http://example.com/b.json",
+                        "properties": {
+                            "c": { "$ref": "#/$defs/d", "allOf": [{ "$ref": "#" }] }
+                        },
+                        "$defs": { "d": { "type": "object" } }
+                    }
+                }
+            })
+        );
+        let cfg = crate::Config::from_schema(&upgraded, None).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!({"a": {"b": {"c": {}}}})).is_ok());
+        assert!(cfg.validate(&json!({"b": {"c": 1}})).is_err());
+    }
+
+    #[test]
+    fn test_upgrade_downgrade() {
+        let schema = json!({});
+        assert!(upgrade(&schema, Some(Draft::Draft7), Draft::Draft4).is_err());
+        assert!(upgrade(&schema, Some(Draft::OpenApi30), Draft::Draft7).is_err());
+    }
+}
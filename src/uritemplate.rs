@@ -0,0 +1,421 @@
+//! Parsing and expanding [RFC 6570](https://www.rfc-editor.org/rfc/rfc6570)
+//! URI templates, up to level 4.
+
+use serde_json::Value;
+
+/// A parsed URI template.
+#[derive(Debug)]
+pub(crate) struct UriTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug)]
+enum Part {
+    Literal(String),
+    Expression(Operator, Vec<VarSpec>),
+}
+
+#[derive(Debug)]
+struct VarSpec {
+    name: String,
+    prefix: Option<usize>,
+    explode: bool,
+}
+
+/// The behaviour of an expression operator, from Appendix A of RFC 6570.
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    first: &'static str,
+    sep: &'static str,
+    named: bool,
+    ifemp: &'static str,
+    allow_reserved: bool,
+}
+
+impl Operator {
+    fn from_char(c: Option<char>) -> Option<Operator> {
+        let (first, sep, named, ifemp, allow_reserved) = match c {
+            None => ("", ",", false, "", false),
+            Some('+') => ("", ",", false, "", true),
+            Some('.') => (".", ".", false, "", false),
+            Some('/') => ("/", "/", false, "", false),
+            Some(';') => (";", ";", true, "", false),
+            Some('?') => ("?", "&", true, "=", false),
+            Some('&') => ("&", "&", true, "=", false),
+            Some('#') => ("#", ",", false, "", true),
+            _ => return None,
+        };
+        Some(Operator {
+            first,
+            sep,
+            named,
+            ifemp,
+            allow_reserved,
+        })
+    }
+}
+
+/// The value of a template variable. Undefined variables are skipped.
+enum TemplateValue {
+    String(String),
+    List(Vec<String>),
+    Assoc(Vec<(String, String)>),
+}
+
+impl TemplateValue {
+    /// Convert a JSON value to a template value. Strings are used as is, and
+    /// other scalars as their JSON text. `null`, as well as empty arrays and
+    /// objects, are undefined.
+    fn from_json(value: &Value) -> Option<TemplateValue> {
+        fn to_string(value: &Value) -> String {
+            match value {
+                Value::String(s) => s.clone(),
+                Value::Null => String::new(),
+                _ => value.to_string(),
+            }
+        }
+        match value {
+            Value::Null => None,
+            Value::Array(items) if items.is_empty() => None,
+            Value::Object(object) if object.is_empty() => None,
+            Value::Array(items) => Some(TemplateValue::List(items.iter().map(to_string).collect())),
+            Value::Object(object) => Some(TemplateValue::Assoc(
+                object
+                    .iter()
+                    .map(|(k, v)| (k.clone(), to_string(v)))
+                    .collect(),
+            )),
+            _ => Some(TemplateValue::String(to_string(value))),
+        }
+    }
+}
+
+fn is_unreserved(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-._~".contains(c)
+}
+
+fn is_reserved(c: char) -> bool {
+    ":/?#[]@!$&'()*+,;=".contains(c)
+}
+
+/// Percent-encode the characters of `text` that aren't allowed, keeping any
+/// existing percent-encoded triplets when reserved characters are allowed.
+fn encode(text: &str, allow_reserved: bool) -> String {
+    let mut out = String::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if is_unreserved(c) || (allow_reserved && is_reserved(c)) {
+            out.push(c);
+        } else if allow_reserved && c == '%' && is_pct_encoded(&text[i..]) {
+            out.push_str(&text[i..i + 3]);
+            chars.next();
+            chars.next();
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    out
+}
+
+fn is_pct_encoded(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 3
+        && bytes[0] == b'%'
+        && bytes[1].is_ascii_hexdigit()
+        && bytes[2].is_ascii_hexdigit()
+}
+
+fn parse_varspec(spec: &str) -> Result<VarSpec, String> {
+    let (name, prefix, explode) = if let Some(name) = spec.strip_suffix('*') {
+        (name, None, true)
+    } else if let Some((name, length)) = spec.split_once(':') {
+        let valid = !length.is_empty()
+            && length.len() <= 4
+            && !length.starts_with('0')
+            && length.chars().all(|c| c.is_ascii_digit());
+        if !valid {
+            return Err(format!("Invalid prefix length \"{}\"", length));
+        }
+        (name, Some(length.parse().unwrap()), false)
+    } else {
+        (spec, None, false)
+    };
+    // A varname is made of ALPHA, DIGIT, "_" and pct-encoded triplets, with
+    // single dots in between.
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.ends_with('.')
+        && !name.contains("..")
+        && name.char_indices().all(|(i, c)| {
+            c.is_ascii_alphanumeric()
+                || c == '_'
+                || c == '.'
+                || (c == '%' && is_pct_encoded(&name[i..]))
+                || (i >= 1 && is_pct_encoded(&name[i - 1..]))
+                || (i >= 2 && is_pct_encoded(&name[i - 2..]))
+        });
+    if !valid {
+        return Err(format!("Invalid variable name \"{}\"", name));
+    }
+    Ok(VarSpec {
+        name: name.to_string(),
+        prefix,
+        explode,
+    })
+}
+
+impl UriTemplate {
+    /// Parse a URI template.
+    pub fn parse(template: &str) -> Result<UriTemplate, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(i) if rest[i..].starts_with('}') => {
+                    return Err("Unmatched \"}\" in URI template".to_string())
+                }
+                Some(i) => {
+                    if i > 0 {
+                        parts.push(Part::Literal(rest[..i].to_string()));
+                    }
+                    let end = rest[i..]
+                        .find('}')
+                        .ok_or_else(|| "Unclosed \"{\" in URI template".to_string())?;
+                    let expression = &rest[i + 1..i + end];
+                    if expression.contains('{') {
+                        return Err("Nested \"{\" in URI template".to_string());
+                    }
+                    let mut chars = expression.chars();
+                    let (operator, varlist) = match chars.next() {
+                        Some(c) if "+#./;?&".contains(c) => (Some(c), chars.as_str()),
+                        Some(c) if "=,!@|".contains(c) => {
+                            return Err(format!("Reserved operator \"{}\" in URI template", c))
+                        }
+                        _ => (None, expression),
+                    };
+                    let varspecs = varlist
+                        .split(',')
+                        .map(parse_varspec)
+                        .collect::<Result<Vec<VarSpec>, String>>()?;
+                    parts.push(Part::Expression(
+                        Operator::from_char(operator).unwrap(),
+                        varspecs,
+                    ));
+                    rest = &rest[i + end + 1..];
+                }
+                None => {
+                    parts.push(Part::Literal(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+        Ok(UriTemplate { parts })
+    }
+
+    /// Expand the template, looking up the value of each variable with
+    /// `lookup`.
+    pub fn expand<F>(&self, lookup: F) -> String
+    where
+        F: Fn(&str) -> Option<Value>,
+    {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => out.push_str(&encode(literal, true)),
+                Part::Expression(op, varspecs) => {
+                    let mut first = true;
+                    for varspec in varspecs {
+                        let value = match lookup(&varspec.name)
+                            .as_ref()
+                            .and_then(TemplateValue::from_json)
+                        {
+                            Some(value) => value,
+                            None => continue,
+                        };
+                        out.push_str(if first { op.first } else { op.sep });
+                        first = false;
+                        expand_value(&mut out, *op, varspec, &value);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Append the name of a variable for a named operator, along with "=", or
+/// `ifemp` if the value is empty.
+fn push_name(out: &mut String, op: Operator, name: &str, empty: bool) {
+    out.push_str(name);
+    out.push_str(if empty { op.ifemp } else { "=" });
+}
+
+fn expand_value(out: &mut String, op: Operator, varspec: &VarSpec, value: &TemplateValue) {
+    let name = varspec.name.as_str();
+    match value {
+        TemplateValue::String(s) => {
+            if op.named {
+                push_name(out, op, name, s.is_empty());
+            }
+            let s = match varspec.prefix {
+                Some(length) => s.chars().take(length).collect(),
+                None => s.clone(),
+            };
+            out.push_str(&encode(&s, op.allow_reserved));
+        }
+        TemplateValue::List(items) if varspec.explode => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| {
+                    let mut out = String::new();
+                    if op.named {
+                        push_name(&mut out, op, name, item.is_empty());
+                    }
+                    out.push_str(&encode(item, op.allow_reserved));
+                    out
+                })
+                .collect();
+            out.push_str(&items.join(op.sep));
+        }
+        TemplateValue::List(items) => {
+            if op.named {
+                push_name(out, op, name, false);
+            }
+            let items: Vec<String> = items.iter().map(|x| encode(x, op.allow_reserved)).collect();
+            out.push_str(&items.join(","));
+        }
+        TemplateValue::Assoc(pairs) if varspec.explode => {
+            let pairs: Vec<String> = pairs
+                .iter()
+                .map(|(k, v)| {
+                    let mut out = encode(k, op.allow_reserved);
+                    out.push_str(if op.named && v.is_empty() {
+                        op.ifemp
+                    } else {
+                        "="
+                    });
+                    out.push_str(&encode(v, op.allow_reserved));
+                    out
+                })
+                .collect();
+            out.push_str(&pairs.join(op.sep));
+        }
+        TemplateValue::Assoc(pairs) => {
+            if op.named {
+                push_name(out, op, name, false);
+            }
+            let pairs: Vec<String> = pairs
+                .iter()
+                .flat_map(|(k, v)| vec![k, v])
+                .map(|x| encode(x, op.allow_reserved))
+                .collect();
+            out.push_str(&pairs.join(","));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expand() {
+        // The examples of section 3.2 of RFC 6570.
+        let variables = json!({
+            "count": ["one", "two", "three"],
+            "dom": ["example", "com"],
+            "dub": "me/too",
+            "hello": "Hello World!",
+            "half": "50%",
+            "var": "value",
+            "who": "fred",
+            "base": "http://example.com/home/",
+            "path": "/foo/bar",
+            "list": ["red", "green", "blue"],
+            "keys": {"comma": ",", "dot": ".", "semi": ";"},
+            "v": "6",
+            "x": "1024",
+            "y": "768",
+            "empty": "",
+            "empty_keys": {},
+            "undef": null
+        });
+        let expand = |template: &str| {
+            UriTemplate::parse(template)
+                .unwrap()
+                .expand(|name| variables.get(name).cloned())
+        };
+        let cases = [
+            ("{count}", "one,two,three"),
+            ("{count*}", "one,two,three"),
+            ("{/count*}", "/one/two/three"),
+            ("{;count*}", ";count=one;count=two;count=three"),
+            ("{?count*}", "?count=one&count=two&count=three"),
+            ("{&count*}", "&count=one&count=two&count=three"),
+            ("{var}", "value"),
+            ("{hello}", "Hello%20World%21"),
+            ("{half}", "50%25"),
+            ("O{empty}X", "OX"),
+            ("O{undef}X", "OX"),
+            ("{x,y}", "1024,768"),
+            ("{x,hello,y}", "1024,Hello%20World%21,768"),
+            ("?{x,empty}", "?1024,"),
+            ("?{x,undef}", "?1024"),
+            ("{var:3}", "val"),
+            ("{var:30}", "value"),
+            ("{list}", "red,green,blue"),
+            ("{keys}", "comma,%2C,dot,.,semi,%3B"),
+            ("{keys*}", "comma=%2C,dot=.,semi=%3B"),
+            ("{+var}", "value"),
+            ("{+hello}", "Hello%20World!"),
+            ("{+half}", "50%25"),
+            ("{base}index", "http%3A%2F%2Fexample.com%2Fhome%2Findex"),
+            ("{+base}index", "http://example.com/home/index"),
+            ("{+path:6}/here", "/foo/b/here"),
+            ("{+keys*}", "comma=,,dot=.,semi=;"),
+            ("{#var}", "#value"),
+            ("{#hello}", "#Hello%20World!"),
+            ("{#path:6}/here", "#/foo/b/here"),
+            ("X{.var:3}", "X.val"),
+            ("X{.list*}", "X.red.green.blue"),
+            ("{/var:1,var}", "/v/value"),
+            ("{/list*,path:4}", "/red/green/blue/%2Ffoo"),
+            ("{;x,y,empty}", ";x=1024;y=768;empty"),
+            ("{;list*}", ";list=red;list=green;list=blue"),
+            ("{;keys*}", ";comma=%2C;dot=.;semi=%3B"),
+            ("{?x,y,empty}", "?x=1024&y=768&empty="),
+            ("{?var:3}", "?var=val"),
+            ("{?list}", "?list=red,green,blue"),
+            ("{?keys*}", "?comma=%2C&dot=.&semi=%3B"),
+            ("?fixed=yes{&x}", "?fixed=yes&x=1024"),
+            ("{&var:3}", "&var=val"),
+            ("{?empty_keys}", ""),
+            ("{/who,dub}", "/fred/me%2Ftoo"),
+        ];
+        for (template, expected) in cases.iter() {
+            assert_eq!(&expand(template), expected, "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for template in &[
+            "{var",
+            "var}",
+            "{}",
+            "{=var}",
+            "{var:0}",
+            "{var:10000}",
+            "{a b}",
+            "{a..b}",
+        ] {
+            assert!(UriTemplate::parse(template).is_err(), "{}", template);
+        }
+        assert!(UriTemplate::parse("").is_ok());
+        assert!(UriTemplate::parse("/items/{id}{?fields*,q%20x}").is_ok());
+    }
+}
@@ -1,11 +1,16 @@
 #![allow(non_snake_case)]
 #![allow(clippy::too_many_arguments)]
 
+use std::collections::HashSet;
+
 use serde_json::{json, Map, Value, Value::Array, Value::Bool, Value::Object};
 
 use crate::config::Config;
-use crate::context::Context;
+use crate::context::{Context, Reference};
 use crate::error::{make_error, no_error, ErrorIterator, ValidationError};
+use crate::mongodb;
+use crate::resolver;
+use crate::schemas::Draft;
 use crate::unique;
 use crate::util;
 
@@ -40,8 +45,70 @@ pub type Validator<'a> = fn(
 
 /// The top-level validation function that performs all of the concrete
 /// validation functions at a given instance/schema pair.
-
 pub fn descend<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if enters_resource(cfg, schema, &ref_context) {
+        // An embedded schema resource changes the base URL used to resolve
+        // references, and possibly the draft, so it gets a context frame of
+        // its own.
+        let draft = resource_draft(cfg, schema, &ref_context);
+        let frame = ref_context.push(schema).with_draft(draft);
+        let base_url = resource_base_url(cfg, &frame);
+        let errors: Vec<ValidationError> = descend_in_scope(
+            cfg,
+            instance,
+            schema,
+            parent_schema,
+            frame.with_base_url(base_url.as_deref()),
+        )
+        .collect();
+        Box::new(errors.into_iter())
+    } else {
+        descend_in_scope(cfg, instance, schema, parent_schema, ref_context)
+    }
+}
+
+/// Whether `schema` is an embedded schema resource (one with its own id) that
+/// isn't already the innermost frame of the context.
+fn enters_resource(cfg: &Config, schema: &Value, ref_context: &Context) -> bool {
+    let draft = resolver::draft_of(ref_context.get_draft(cfg.draft), schema);
+    match resolver::id_of(draft, schema) {
+        Some(id) => !id.starts_with('#') && !std::ptr::eq(ref_context.get_resource(), schema),
+        None => false,
+    }
+}
+
+/// Get the base URL of the context frame of an embedded schema resource, to
+/// keep in the frame, so that the references within the resource are
+/// resolved against it without joining the ids of the frames below again.
+fn resource_base_url(cfg: &Config, frame: &Context) -> Option<String> {
+    cfg.get_resolver()
+        .base_url_of(cfg.draft, frame)
+        .ok()
+        .map(String::from)
+}
+
+/// Get the draft that a schema resource is evaluated with, if it isn't the
+/// one of the `Config`. A resource may declare its own draft with `$schema`,
+/// and otherwise keeps the draft of the context it is reached from.
+fn resource_draft(cfg: &Config, resource: &Value, ref_context: &Context) -> Option<Draft> {
+    if std::ptr::eq(resource, cfg.get_schema()) {
+        return None;
+    }
+    let draft = resolver::draft_of(ref_context.get_draft(cfg.draft), resource);
+    if draft == cfg.draft {
+        None
+    } else {
+        Some(draft)
+    }
+}
+
+fn descend_in_scope<'a>(
     cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
@@ -57,20 +124,34 @@ pub fn descend<'a>(
             }
         }
         Object(schema_object) => {
-            if let (Some(ref_), Some(validator)) =
-                (schema_object.get("$ref"), cfg.get_validator("$ref"))
-            {
+            // Resources declaring a draft of their own don't use the Config's
+            // vocabularies.
+            let get_validator = move |key: &'a str| match ref_context.draft {
+                Some(draft) => draft.get_validator(key),
+                None => cfg.get_validator(key),
+            };
+            let draft = ref_context.get_draft(cfg.draft);
+            if let (true, Some(ref_), Some(validator)) = (
+                cfg.ref_overrides_siblings(draft),
+                schema_object.get("$ref"),
+                get_validator("$ref"),
+            ) {
                 Box::new(validator(cfg, instance, ref_, Some(schema), ref_context))
             } else {
                 Box::new(
                     schema_object
                         .iter()
                         .flat_map(move |(k, v)| -> ErrorIterator<'a> {
-                            if let Some(validator) = cfg.get_validator(k) {
+                            if let Some(validator) = get_validator(k) {
                                 Box::new(
                                     validator(cfg, instance, v, Some(schema), ref_context)
                                         .map(move |err| err.schema_ctx(k.to_string())),
                                 )
+                            } else if let Some(validator) = draft.get_keyword_validator(k) {
+                                Box::new(
+                                    validator(cfg, instance, v, Some(schema))
+                                        .map(move |err| err.schema_ctx(k.to_string())),
+                                )
                             } else {
                                 no_error()
                             }
@@ -86,6 +167,19 @@ pub fn descend<'a>(
     }
 }
 
+/// Whether the instance is valid against the given schema.
+fn is_valid<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> bool {
+    descend(cfg, instance, schema, parent_schema, ref_context)
+        .next()
+        .is_none()
+}
+
 // The validation functions below all correspond to individual schema checks
 // defined in the JSON schema specification.
 
@@ -237,19 +331,17 @@ pub fn additionalProperties<'a>(
                         )
                     }));
                 }
-                Bool(bool) => {
-                    if !bool {
-                        let extra_string = util::format_list(&mut extras);
-                        if !extra_string.is_empty() {
-                            return make_error(
-                                format!(
-                                    "Additional properties are not allowed. Found {}.",
-                                    extra_string
-                                ),
-                                Some(instance),
-                                parent_schema,
-                            );
-                        }
+                Bool(false) => {
+                    let extra_string = util::format_list(&mut extras);
+                    if !extra_string.is_empty() {
+                        return make_error(
+                            format!(
+                                "Additional properties are not allowed. Found {}.",
+                                extra_string
+                            ),
+                            Some(instance),
+                            parent_schema,
+                        );
                     }
                 }
                 _ => {}
@@ -267,7 +359,7 @@ pub fn items<'a>(
     ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
     if let Array(instance) = instance {
-        let items = if cfg.get_draft_number() >= 6 {
+        let items = if ref_context.get_draft(cfg.draft).get_draft_number() >= 6 {
             util::bool_to_object_schema(schema)
         } else {
             schema
@@ -295,6 +387,65 @@ pub fn items<'a>(
     }
 }
 
+pub fn prefixItems<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Array(instance), Array(items)) = (instance, schema) {
+        Box::new(instance.iter().enumerate().zip(items.iter()).flat_map(
+            move |((index, item), subschema)| {
+                Box::new(
+                    descend(cfg, item, subschema, Some(schema), ref_context)
+                        .map(move |err| err.add_ctx(index.to_string(), index.to_string())),
+                )
+            },
+        ))
+    } else {
+        no_error()
+    }
+}
+
+/// From Draft 2020-12, `items` only takes a single schema, which applies to
+/// all of the items not covered by `prefixItems`.
+pub fn items_draft202012<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Array(instance_array) = instance {
+        let prefix_len = parent_schema
+            .and_then(|x| x.get("prefixItems"))
+            .and_then(Value::as_array)
+            .map_or(0, Vec::len);
+        match schema {
+            Object(_) => {
+                return Box::new(instance_array.iter().enumerate().skip(prefix_len).flat_map(
+                    move |(index, item)| {
+                        Box::new(
+                            descend(cfg, item, schema, parent_schema, ref_context)
+                                .map(move |err| err.instance_ctx(index.to_string())),
+                        )
+                    },
+                ))
+            }
+            Bool(false) if instance_array.len() > prefix_len => {
+                return make_error(
+                    "Additional items are not allowed.",
+                    Some(instance),
+                    parent_schema,
+                );
+            }
+            _ => {}
+        }
+    }
+    no_error()
+}
+
 pub fn additionalItems<'a>(
     cfg: &'a Config<'a>,
     instance: &'a Value,
@@ -320,14 +471,12 @@ pub fn additionalItems<'a>(
                             }),
                     )
                 }
-                Bool(b) => {
-                    if !b && instance_array.len() > items.len() {
-                        return make_error(
-                            "Additional items are not allowed.",
-                            Some(instance),
-                            Some(parent_schema),
-                        );
-                    }
+                Bool(false) if instance_array.len() > items.len() => {
+                    return make_error(
+                        "Additional items are not allowed.",
+                        Some(instance),
+                        Some(parent_schema),
+                    );
                 }
                 _ => {}
             }
@@ -375,6 +524,51 @@ pub fn contains<'a>(
     no_error()
 }
 
+pub fn contains_draft201909<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Array(instance_array) = instance {
+        let bound = |key| {
+            parent_schema
+                .and_then(|x| x.get(key))
+                .and_then(Value::as_f64)
+        };
+        let matches = instance_array
+            .iter()
+            .filter(|item| is_valid(cfg, item, schema, parent_schema, ref_context))
+            .count();
+        let min_contains = bound("minContains").unwrap_or(1.0);
+        if (matches as f64) < min_contains {
+            if matches == 0 {
+                return make_error(
+                    "No items in array valid under the given schema.",
+                    Some(instance),
+                    Some(schema),
+                );
+            }
+            return make_error(
+                format!("{} matching items < minContains {}", matches, min_contains),
+                Some(instance),
+                Some(schema),
+            );
+        }
+        if let Some(max_contains) = bound("maxContains") {
+            if (matches as f64) > max_contains {
+                return make_error(
+                    format!("{} matching items > maxContains {}", matches, max_contains),
+                    Some(instance),
+                    Some(schema),
+                );
+            }
+        }
+    }
+    no_error()
+}
+
 pub fn exclusiveMinimum<'a>(
     _cfg: &'a Config<'a>,
     instance: &'a Value,
@@ -522,13 +716,15 @@ pub fn multipleOf<'a>(
     _ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
     if let (Value::Number(instance_number), Value::Number(schema_number)) = (instance, schema) {
-        let failed = if schema_number.is_f64() {
-            let quotient = instance_number.as_f64().unwrap() / schema_number.as_f64().unwrap();
-            quotient.trunc() != quotient
-        } else if schema_number.is_u64() {
-            (instance_number.as_u64().unwrap() % schema_number.as_u64().unwrap()) != 0
+        let failed = if let (Some(x), Some(y)) = (instance_number.as_u64(), schema_number.as_u64())
+        {
+            (x % y) != 0
+        } else if let (Some(x), Some(y)) = (instance_number.as_i64(), schema_number.as_i64()) {
+            (x % y) != 0
         } else {
-            (instance_number.as_i64().unwrap() % schema_number.as_i64().unwrap()) != 0
+            let quotient = instance_number.as_f64().unwrap() / schema_number.as_f64().unwrap();
+            // A quotient that overflows can't be an integer either
+            !quotient.is_finite() || quotient.trunc() != quotient
         };
         if failed {
             return make_error(
@@ -549,7 +745,7 @@ pub fn minItems<'a>(
     _ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
     if let (Array(instance_array), Value::Number(schema_number)) = (instance, schema) {
-        if instance_array.len() < schema_number.as_u64().unwrap() as usize {
+        if (instance_array.len() as f64) < schema_number.as_f64().unwrap() {
             return make_error(
                 format!("{} < minItems {}", instance_array.len(), schema_number),
                 Some(instance),
@@ -568,7 +764,7 @@ pub fn maxItems<'a>(
     _ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
     if let (Array(instance_array), Value::Number(schema_number)) = (instance, schema) {
-        if instance_array.len() > schema_number.as_u64().unwrap() as usize {
+        if (instance_array.len() as f64) > schema_number.as_f64().unwrap() {
             return make_error(
                 format!("{} > maxItems {}", instance_array.len(), schema_number),
                 Some(instance),
@@ -618,10 +814,13 @@ pub fn format<'a>(
     instance: &'a Value,
     schema: &'a Value,
     _parent_schema: Option<&'a Value>,
-    _ref_context: Context<'a>,
+    ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
     if let (Value::String(instance_string), Value::String(schema_string)) = (instance, schema) {
-        if let Some(checker) = cfg.get_format_checker(schema_string) {
+        if let Some(checker) = ref_context
+            .get_draft(cfg.draft)
+            .get_format_checker(schema_string)
+        {
             if !checker(cfg, instance_string) {
                 return make_error("Invalid for format.", Some(instance), Some(schema));
             }
@@ -639,7 +838,7 @@ pub fn minLength<'a>(
 ) -> ErrorIterator<'a> {
     if let (Value::String(instance_string), Value::Number(schema_number)) = (instance, schema) {
         let count = instance_string.chars().count();
-        if count < schema_number.as_u64().unwrap() as usize {
+        if (count as f64) < schema_number.as_f64().unwrap() {
             return make_error(
                 format!("{} < minLength {}", count, schema_number),
                 Some(instance),
@@ -659,7 +858,7 @@ pub fn maxLength<'a>(
 ) -> ErrorIterator<'a> {
     if let (Value::String(instance_string), Value::Number(schema_number)) = (instance, schema) {
         let count = instance_string.chars().count();
-        if count > schema_number.as_u64().unwrap() as usize {
+        if (count as f64) > schema_number.as_f64().unwrap() {
             return make_error(
                 format!("{} < maxLength {}", count, schema_number),
                 Some(instance),
@@ -712,6 +911,71 @@ pub fn dependencies<'a>(
     }
 }
 
+pub fn dependentRequired<'a>(
+    _cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    _ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Object(instance_object), Object(schema_object)) = (instance, schema) {
+        Box::new(
+            schema_object
+                .iter()
+                .filter(move |(property, _dependency)| {
+                    instance_object.contains_key(property.as_str())
+                })
+                .flat_map(move |(property, dependency)| -> ErrorIterator<'a> {
+                    let missing_properties: Vec<&str> = util::iter_or_once(dependency)
+                        .filter_map(Value::as_str)
+                        .filter(|&x| !instance_object.contains_key(x))
+                        .collect();
+                    if missing_properties.is_empty() {
+                        return no_error();
+                    }
+                    Box::new(
+                        make_error(
+                            format!(
+                                "Required properties {} are missing, because \"{}\" is present",
+                                util::format_list(&mut missing_properties.iter().copied()),
+                                property
+                            ),
+                            Some(instance),
+                            Some(dependency),
+                        )
+                        .map(move |err| err.schema_ctx(property.clone())),
+                    )
+                }),
+        )
+    } else {
+        no_error()
+    }
+}
+
+pub fn dependentSchemas<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Object(instance_object), Object(schema_object)) = (instance, schema) {
+        Box::new(
+            schema_object
+                .iter()
+                .filter(move |(property, _dependency)| {
+                    instance_object.contains_key(property.as_str())
+                })
+                .flat_map(move |(property, dependency)| {
+                    descend(cfg, instance, dependency, Some(schema), ref_context)
+                        .map(move |err| err.schema_ctx(property.clone()))
+                }),
+        )
+    } else {
+        no_error()
+    }
+}
+
 pub fn enum_<'a>(
     _cfg: &'a Config<'a>,
     instance: &'a Value,
@@ -766,335 +1030,1403 @@ pub fn type_<'a>(
     no_error()
 }
 
-pub fn properties<'a>(
+/// In OpenAPI 3.0, `"nullable": true` adds `null` to the allowed types.
+pub fn type_openapi30<'a>(
     cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
-    _parent_schema: Option<&'a Value>,
+    parent_schema: Option<&'a Value>,
     ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let (Object(instance_object), Object(schema_object)) = (instance, schema) {
-        Box::new(schema_object.iter().flat_map(move |(property, subschema)| {
-            if let Some(property_value) = instance_object.get(property) {
-                Box::new(
-                    descend(cfg, property_value, subschema, Some(schema), ref_context)
-                        .map(move |err| err.add_ctx(property.clone(), property.clone())),
-                )
-            } else {
-                no_error()
-            }
-        }))
-    } else {
-        no_error()
+    if instance.is_null() && parent_schema.and_then(|x| x.get("nullable")) == Some(&Bool(true)) {
+        return no_error();
     }
+    type_(cfg, instance, schema, parent_schema, ref_context)
 }
 
-pub fn required<'a>(
+/// In MongoDB's `$jsonSchema`, the JSON types apply to the BSON types of the
+/// instance, so that an Extended JSON wrapper such as `{"$oid": "..."}` is not
+/// an object.
+pub fn type_mongodb<'a>(
     _cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
-    _parent_schema: Option<&'a Value>,
+    parent_schema: Option<&'a Value>,
     _ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let (Object(instance_object), Array(schema_array)) = (instance, schema) {
-        let missing_properties: Vec<&str> = schema_array
-            .iter()
-            .filter_map(Value::as_str)
-            .filter(|&x| !instance_object.contains_key(&x.to_string()))
-            .collect();
-
-        if !missing_properties.is_empty() {
-            return make_error(
-                format!(
-                    "Required properties {} are missing",
-                    util::format_list(&mut missing_properties.iter().copied())
-                ),
-                Some(instance),
-                Some(schema),
-            );
-        }
+    let matches = |typename: &Value| match typename.as_str() {
+        Some("boolean") => mongodb::has_bson_type(instance, "bool"),
+        Some(typename) => mongodb::has_bson_type(instance, typename),
+        None => true,
+    };
+    if !util::iter_or_once(schema).any(matches) {
+        return make_error("Invalid type.", Some(instance), parent_schema);
     }
     no_error()
 }
 
-pub fn minProperties<'a>(
+pub fn bsonType<'a>(
     _cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
-    _parent_schema: Option<&'a Value>,
+    parent_schema: Option<&'a Value>,
     _ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let (Object(instance_object), Value::Number(schema_number)) = (instance, schema) {
-        if instance_object.len() < schema_number.as_u64().unwrap() as usize {
-            return make_error(
-                format!(
-                    "{} < minProperties {}",
-                    instance_object.len(),
-                    schema_number
-                ),
-                Some(instance),
-                Some(schema),
-            );
-        }
+    let matches = |typename: &Value| match typename.as_str() {
+        Some(typename) => mongodb::has_bson_type(instance, typename),
+        None => true,
+    };
+    if !util::iter_or_once(schema).any(matches) {
+        return make_error(
+            format!("Invalid BSON type {}.", mongodb::bson_type(instance)),
+            Some(instance),
+            parent_schema,
+        );
     }
     no_error()
 }
 
-pub fn maxProperties<'a>(
-    _cfg: &'a Config<'a>,
+/// Validate the number held by an Extended JSON wrapper, such as
+/// `{"$numberLong": "..."}`, with one of the numeric validators.
+fn bson_number<'a>(
+    validator: for<'b> fn(
+        &'b Config<'b>,
+        &'b Value,
+        &'b Value,
+        Option<&'b Value>,
+        Context<'b>,
+    ) -> ErrorIterator<'b>,
+    cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
-    _parent_schema: Option<&'a Value>,
-    _ref_context: Context<'a>,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let (Object(instance_object), Value::Number(schema_number)) = (instance, schema) {
-        if instance_object.len() > schema_number.as_u64().unwrap() as usize {
-            return make_error(
-                format!(
-                    "{} > maxProperties {}",
-                    instance_object.len(),
-                    schema_number
-                ),
-                Some(instance),
-                Some(schema),
-            );
+    match mongodb::bson_number(instance) {
+        Some(number) => {
+            let errors: Vec<ValidationError> =
+                validator(cfg, &number, schema, parent_schema, ref_context).collect();
+            Box::new(errors.into_iter().map(move |mut error| {
+                error.instance = Some(instance.clone());
+                error
+            }))
         }
+        None => validator(cfg, instance, schema, parent_schema, ref_context),
     }
-    no_error()
 }
 
-pub fn allOf<'a>(
+pub fn minimum_mongodb<'a>(
     cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
-    _parent_schema: Option<&'a Value>,
+    parent_schema: Option<&'a Value>,
     ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let Array(schema_array) = schema {
-        Box::new(
-            schema_array
-                .iter()
-                .enumerate()
-                .flat_map(move |(index, subschema)| {
-                    let subschema0 = if cfg.get_draft_number() >= 6 {
-                        util::bool_to_object_schema(subschema)
-                    } else {
-                        subschema
-                    };
-                    Box::new(
-                        descend(cfg, instance, subschema0, Some(schema), ref_context)
-                            .map(move |err| err.schema_ctx(index.to_string())),
-                    )
-                }),
-        )
-    } else {
-        no_error()
-    }
+    bson_number(
+        minimum_draft4,
+        cfg,
+        instance,
+        schema,
+        parent_schema,
+        ref_context,
+    )
 }
 
-pub fn anyOf<'a>(
+pub fn maximum_mongodb<'a>(
     cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
-    _parent_schema: Option<&'a Value>,
+    parent_schema: Option<&'a Value>,
     ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let Array(schema_array) = schema {
-        for subschema in schema_array.iter() {
-            let subschema0 = if cfg.get_draft_number() >= 6 {
-                util::bool_to_object_schema(subschema)
-            } else {
-                subschema
-            };
-            if descend(cfg, instance, subschema0, Some(schema), ref_context)
-                .next()
-                .is_none()
-            {
-                return no_error();
-            }
+    bson_number(
+        maximum_draft4,
+        cfg,
+        instance,
+        schema,
+        parent_schema,
+        ref_context,
+    )
+}
+
+pub fn multipleOf_mongodb<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    bson_number(
+        multipleOf,
+        cfg,
+        instance,
+        schema,
+        parent_schema,
+        ref_context,
+    )
+}
+
+/// In a Kubernetes structural schema, an object only keeps the fields that its
+/// schema specifies, unless `x-kubernetes-preserve-unknown-fields` is set. The
+/// other fields are reported, as with strict field validation. The `metadata`
+/// of the custom resource itself is left to Kubernetes.
+pub fn type_kubernetes<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    let errors = type_openapi30(cfg, instance, schema, parent_schema, ref_context);
+    let (instance_object, parent) = match (instance, parent_schema) {
+        (Object(instance_object), Some(Object(parent))) if schema == "object" => {
+            (instance_object, parent)
         }
-        return make_error("anyOf failed", Some(instance), Some(schema));
+        _ => return errors,
+    };
+    if parent.contains_key("additionalProperties")
+        || parent.contains_key("patternProperties")
+        || parent.get("x-kubernetes-preserve-unknown-fields") == Some(&Bool(true))
+        || parent_schema.map(|x| x as *const Value)
+            == cfg
+                .get_schema()
+                .pointer("/properties/metadata")
+                .map(|x| x as *const Value)
+    {
+        return errors;
+    }
+    let properties = parent.get("properties").and_then(Value::as_object);
+    let embedded = parent.get("x-kubernetes-embedded-resource") == Some(&Bool(true));
+    let mut unknown = instance_object
+        .keys()
+        .filter(|k| properties.and_then(|x| x.get(*k)).is_none())
+        .filter(|k| !(embedded && ["apiVersion", "kind", "metadata"].contains(&k.as_str())))
+        .map(String::as_str)
+        .peekable();
+    if unknown.peek().is_none() {
+        return errors;
+    }
+    Box::new(errors.chain(make_error(
+        format!(
+            "Unknown fields are not allowed. Found {}.",
+            util::format_list(&mut unknown)
+        ),
+        Some(instance),
+        parent_schema,
+    )))
+}
+
+/// A Kubernetes `x-kubernetes-int-or-string` value is either an integer or a
+/// string.
+pub fn xKubernetesIntOrString<'a>(
+    _cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    _ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    let nullable = parent_schema.and_then(|x| x.get("nullable")) == Some(&Bool(true));
+    if schema == &Bool(true)
+        && !(instance.is_i64()
+            || instance.is_u64()
+            || instance.is_string()
+            || (nullable && instance.is_null()))
+    {
+        return make_error("Must be an integer or a string.", Some(instance), None);
     }
     no_error()
 }
 
-pub fn oneOf<'a>(
-    cfg: &'a Config<'a>,
+/// A Kubernetes `x-kubernetes-embedded-resource` is an object with the
+/// `apiVersion` and `kind` of a resource, and optionally its `metadata`.
+pub fn xKubernetesEmbeddedResource<'a>(
+    _cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
     _parent_schema: Option<&'a Value>,
-    ref_context: Context<'a>,
+    _ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let Array(schema_array) = schema {
-        let mut oneOf = schema_array.iter().enumerate();
-        let mut found_one = false;
-        for (_, subschema) in oneOf.by_ref() {
-            let subschema0 = if cfg.get_draft_number() >= 6 {
-                util::bool_to_object_schema(subschema)
-            } else {
-                subschema
-            };
-            if descend(cfg, instance, subschema0, Some(schema), ref_context)
-                .next()
-                .is_none()
-            {
-                found_one = true;
-                break;
+    if let (Object(instance_object), Bool(true)) = (instance, schema) {
+        let mut errors = Vec::new();
+        for field in &["apiVersion", "kind"] {
+            if !matches!(instance_object.get(*field), Some(Value::String(_))) {
+                errors.push(ValidationError::new(
+                    &format!("Embedded resource must have a string \"{}\"", field),
+                    Some(instance),
+                    None,
+                ));
             }
         }
-
-        if !found_one {
-            return make_error("nothing matched in oneOf", Some(instance), Some(schema));
+        if matches!(instance_object.get("metadata"), Some(x) if !x.is_object()) {
+            errors.push(
+                ValidationError::new(
+                    "Embedded resource metadata must be an object",
+                    Some(&instance_object["metadata"]),
+                    None,
+                )
+                .instance_ctx("metadata".to_string()),
+            );
         }
+        return Box::new(errors.into_iter());
+    }
+    no_error()
+}
 
-        let mut found_more = false;
-        for (_, subschema) in oneOf.by_ref() {
-            let subschema0 = if cfg.get_draft_number() >= 6 {
-                util::bool_to_object_schema(subschema)
+/// A Kubernetes `x-kubernetes-list-type` of `set` has unique items, and one of
+/// `map` has unique values for the fields in `x-kubernetes-list-map-keys`.
+pub fn xKubernetesListType<'a>(
+    _cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    _ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    let items = match instance {
+        Array(items) => items,
+        _ => return no_error(),
+    };
+    match schema.as_str() {
+        Some("set") if !unique::has_unique_elements(&mut items.iter()) => {
+            make_error("Items of a set are not unique", Some(instance), None)
+        }
+        Some("map") => {
+            let keys: Vec<&str> = parent_schema
+                .and_then(|x| x.get("x-kubernetes-list-map-keys"))
+                .and_then(Value::as_array)
+                .map(|x| x.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let entries: Vec<Value> = items
+                .iter()
+                .map(|item| {
+                    Array(
+                        keys.iter()
+                            .map(|key| item.get(key).cloned().unwrap_or(Value::Null))
+                            .collect(),
+                    )
+                })
+                .collect();
+            if unique::has_unique_elements(&mut entries.iter()) {
+                no_error()
             } else {
-                subschema
-            };
-            if descend(cfg, instance, subschema0, Some(schema), ref_context)
-                .next()
-                .is_none()
-            {
-                found_more = true;
-                break;
+                make_error(
+                    format!(
+                        "Items of a map are not unique for the keys {}",
+                        util::format_list(&mut keys.into_iter())
+                    ),
+                    Some(instance),
+                    None,
+                )
             }
         }
+        _ => no_error(),
+    }
+}
 
-        if found_more {
-            return make_error(
-                "More than one matched in oneOf",
-                Some(instance),
-                Some(schema),
-            );
-        }
+/// In Draft 3, `type` may also contain schemas, which the instance is matched
+/// against like any of the simple types.
+pub fn type_draft3<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if !util::iter_or_once(schema).any(|x| match x {
+        Object(_) => is_valid(cfg, instance, x, parent_schema, ref_context),
+        _ => single_type(instance, x),
+    }) {
+        return make_error("Invalid type.", Some(instance), parent_schema);
     }
     no_error()
 }
 
-pub fn not<'a>(
+pub fn disallow<'a>(
     cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
     parent_schema: Option<&'a Value>,
     ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if descend(cfg, instance, schema, parent_schema, ref_context)
-        .next()
-        .is_none()
-    {
-        make_error("not", Some(instance), Some(schema))
-    } else {
-        no_error()
+    if util::iter_or_once(schema).any(|x| match x {
+        Object(_) => is_valid(cfg, instance, x, parent_schema, ref_context),
+        _ => single_type(instance, x),
+    }) {
+        return make_error("Disallowed type.", Some(instance), parent_schema);
     }
+    no_error()
 }
 
-pub fn ref_<'a>(
+pub fn properties<'a>(
     cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
     _parent_schema: Option<&'a Value>,
     ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if let Value::String(sref) = schema {
-        struct RefIter {
-            collected_errors: Vec<ValidationError>,
-            error_i: usize,
-        }
-
-        impl Iterator for RefIter {
-            type Item = ValidationError;
-
-            fn next(&mut self) -> Option<Self::Item> {
-                if self.error_i < self.collected_errors.len() {
-                    self.error_i += 1;
-                    Some(self.collected_errors[self.error_i - 1].clone())
-                } else {
-                    None
-                }
+    if let (Object(instance_object), Object(schema_object)) = (instance, schema) {
+        Box::new(schema_object.iter().flat_map(move |(property, subschema)| {
+            if let Some(property_value) = instance_object.get(property) {
+                Box::new(
+                    descend(cfg, property_value, subschema, Some(schema), ref_context)
+                        .map(move |err| err.add_ctx(property.clone(), property.clone())),
+                )
+            } else {
+                no_error()
             }
-        }
+        }))
+    } else {
+        no_error()
+    }
+}
 
-        match cfg
-            .get_resolver()
-            .resolve_fragment(cfg.draft, sref, &ref_context, cfg.get_schema())
-        {
-            Ok((scope, resolved)) => {
-                let scope_schema = json!({"$id": scope.to_string()});
-                return Box::new(RefIter {
-                    collected_errors: descend(
-                        cfg,
-                        instance,
-                        resolved,
-                        Some(schema),
-                        ref_context.push(&scope_schema),
+/// In Draft 3, whether a property is required is specified by a boolean
+/// `required` within the property's own schema.
+pub fn properties_draft3<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Object(instance_object), Object(schema_object)) = (instance, schema) {
+        Box::new(schema_object.iter().flat_map(move |(property, subschema)| {
+            if let Some(property_value) = instance_object.get(property) {
+                Box::new(
+                    descend(cfg, property_value, subschema, Some(schema), ref_context)
+                        .map(move |err| err.add_ctx(property.clone(), property.clone())),
+                )
+            } else if subschema.get("required") == Some(&Bool(true)) {
+                Box::new(
+                    make_error(
+                        format!("Required property \"{}\" is missing", property),
+                        Some(instance),
+                        Some(subschema),
                     )
-                    .collect(),
-                    error_i: 0,
-                });
-            }
-            Err(_err) => {
-                return make_error(
-                    format!("Couldn't resolve reference {}", sref),
-                    Some(instance),
-                    None,
+                    .map(move |err| {
+                        err.schema_ctx("required".to_string())
+                            .schema_ctx(property.clone())
+                    }),
                 )
+            } else {
+                no_error()
             }
+        }))
+    } else {
+        no_error()
+    }
+}
+
+pub fn required<'a>(
+    _cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    _ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Object(instance_object), Array(schema_array)) = (instance, schema) {
+        let missing_properties: Vec<&str> = schema_array
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|&x| !instance_object.contains_key(&x.to_string()))
+            .collect();
+
+        if !missing_properties.is_empty() {
+            return make_error(
+                format!(
+                    "Required properties {} are missing",
+                    util::format_list(&mut missing_properties.iter().copied())
+                ),
+                Some(instance),
+                Some(schema),
+            );
         }
     }
     no_error()
 }
 
-pub fn if_<'a>(
-    cfg: &'a Config<'a>,
+pub fn minProperties<'a>(
+    _cfg: &'a Config<'a>,
     instance: &'a Value,
     schema: &'a Value,
-    parent_schema: Option<&'a Value>,
-    ref_context: Context<'a>,
+    _parent_schema: Option<&'a Value>,
+    _ref_context: Context<'a>,
 ) -> ErrorIterator<'a> {
-    if descend(cfg, instance, schema, parent_schema, ref_context)
-        .next()
-        .is_none()
-    {
-        if let Some(then) = parent_schema.and_then(|x| x.get("then")) {
-            if then.is_object() {
-                return Box::new(
-                    descend(cfg, instance, then, Some(schema), ref_context)
-                        .map(move |err| err.schema_ctx("then".to_string())),
-                );
-            }
-        }
-    } else if let Some(else_) = parent_schema.and_then(|x| x.get("else")) {
-        if else_.is_object() {
-            return Box::new(
-                descend(cfg, instance, else_, Some(schema), ref_context)
-                    .map(move |err| err.schema_ctx("else".to_string())),
+    if let (Object(instance_object), Value::Number(schema_number)) = (instance, schema) {
+        if (instance_object.len() as f64) < schema_number.as_f64().unwrap() {
+            return make_error(
+                format!(
+                    "{} < minProperties {}",
+                    instance_object.len(),
+                    schema_number
+                ),
+                Some(instance),
+                Some(schema),
             );
         }
     }
     no_error()
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{schemas, Config};
-    use serde_json::json;
-
-    #[test]
-    fn test_additional_properties_errors() {
-        let schema = json!({
-            "properties": { "foo": { "type": "integer" } },
-            "additionalProperties": false
-        });
-        let instance = json!({
-            "foo": 42,
-            "bar": "additional",
-            "baz": "another additional"
-        });
-        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft6)).unwrap();
-        let validation = cfg.validate(&instance);
+pub fn maxProperties<'a>(
+    _cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    _ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Object(instance_object), Value::Number(schema_number)) = (instance, schema) {
+        if (instance_object.len() as f64) > schema_number.as_f64().unwrap() {
+            return make_error(
+                format!(
+                    "{} > maxProperties {}",
+                    instance_object.len(),
+                    schema_number
+                ),
+                Some(instance),
+                Some(schema),
+            );
+        }
+    }
+    no_error()
+}
+
+pub fn allOf<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Array(schema_array) = schema {
+        Box::new(
+            schema_array
+                .iter()
+                .enumerate()
+                .flat_map(move |(index, subschema)| {
+                    let subschema0 = if ref_context.get_draft(cfg.draft).get_draft_number() >= 6 {
+                        util::bool_to_object_schema(subschema)
+                    } else {
+                        subschema
+                    };
+                    Box::new(
+                        descend(cfg, instance, subschema0, Some(schema), ref_context)
+                            .map(move |err| err.schema_ctx(index.to_string())),
+                    )
+                }),
+        )
+    } else {
+        no_error()
+    }
+}
+
+pub fn extends<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    match schema {
+        Object(_) => descend(cfg, instance, schema, Some(schema), ref_context),
+        Array(schema_array) => Box::new(schema_array.iter().enumerate().flat_map(
+            move |(index, subschema)| {
+                descend(cfg, instance, subschema, Some(schema), ref_context)
+                    .map(move |err| err.schema_ctx(index.to_string()))
+            },
+        )),
+        _ => no_error(),
+    }
+}
+
+pub fn anyOf<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Array(schema_array) = schema {
+        for subschema in schema_array.iter() {
+            let subschema0 = if ref_context.get_draft(cfg.draft).get_draft_number() >= 6 {
+                util::bool_to_object_schema(subschema)
+            } else {
+                subschema
+            };
+            if descend(cfg, instance, subschema0, Some(schema), ref_context)
+                .next()
+                .is_none()
+            {
+                return no_error();
+            }
+        }
+        return make_error("anyOf failed", Some(instance), Some(schema));
+    }
+    no_error()
+}
+
+pub fn oneOf<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Array(schema_array) = schema {
+        let mut oneOf = schema_array.iter().enumerate();
+        let mut found_one = false;
+        for (_, subschema) in oneOf.by_ref() {
+            let subschema0 = if ref_context.get_draft(cfg.draft).get_draft_number() >= 6 {
+                util::bool_to_object_schema(subschema)
+            } else {
+                subschema
+            };
+            if descend(cfg, instance, subschema0, Some(schema), ref_context)
+                .next()
+                .is_none()
+            {
+                found_one = true;
+                break;
+            }
+        }
+
+        if !found_one {
+            return make_error("nothing matched in oneOf", Some(instance), Some(schema));
+        }
+
+        let mut found_more = false;
+        for (_, subschema) in oneOf.by_ref() {
+            let subschema0 = if ref_context.get_draft(cfg.draft).get_draft_number() >= 6 {
+                util::bool_to_object_schema(subschema)
+            } else {
+                subschema
+            };
+            if descend(cfg, instance, subschema0, Some(schema), ref_context)
+                .next()
+                .is_none()
+            {
+                found_more = true;
+                break;
+            }
+        }
+
+        if found_more {
+            return make_error(
+                "More than one matched in oneOf",
+                Some(instance),
+                Some(schema),
+            );
+        }
+    }
+    no_error()
+}
+
+/// Validate the instance against the branch of `oneOf` or `anyOf` selected by
+/// an OpenAPI 3.0 `discriminator`. The value of the discriminating property is
+/// looked up in the `mapping`, which gives either the name of a schema in
+/// `#/components/schemas` or a reference, and is otherwise taken to be the
+/// name of a schema. The selected schema must be one that a branch refers to.
+///
+/// Returns `None` if none of the branches is a reference, as only references
+/// can be selected, so that the branches are evaluated as usual.
+fn discriminate<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    branches: &'a Value,
+    parent_schema: &'a Value,
+    discriminator: &'a Value,
+    ref_context: Context<'a>,
+) -> Option<ErrorIterator<'a>> {
+    let resolver = cfg.get_resolver();
+    let resolve = |reference: &str| {
+        resolver
+            .resolve_fragment(cfg.draft, reference, &ref_context, cfg.get_schema())
+            .ok()
+            .map(|(_, _, x)| x)
+    };
+    let references: Vec<(usize, &'a Value, &'a Value)> = match branches {
+        Array(branches) => branches
+            .iter()
+            .enumerate()
+            .filter_map(|(i, branch)| {
+                let reference = branch.get("$ref").and_then(Value::as_str)?;
+                Some((i, branch, resolve(reference)?))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    if references.is_empty() {
+        return None;
+    }
+
+    let property = discriminator.get("propertyName").and_then(Value::as_str)?;
+    let value = match instance.get(property).and_then(Value::as_str) {
+        Some(value) => value,
+        None => {
+            return Some(make_error(
+                format!("Discriminator property \"{}\" is missing", property),
+                Some(instance),
+                Some(discriminator),
+            ))
+        }
+    };
+    let by_name = |name: &str| {
+        if name.contains('/') || name.contains('#') {
+            None
+        } else {
+            resolve(&format!("#/components/schemas/{}", name))
+        }
+    };
+    let mapped = discriminator
+        .get("mapping")
+        .and_then(|x| x.get(value))
+        .and_then(Value::as_str);
+    let target = match mapped {
+        Some(mapped) => by_name(mapped).or_else(|| resolve(mapped)),
+        None => by_name(value),
+    };
+    let selected = target.and_then(|target| {
+        references
+            .iter()
+            .find(|(_, _, resolved)| std::ptr::eq(*resolved, target))
+    });
+    match (selected, mapped) {
+        (Some((i, branch, _)), _) => {
+            let i = i.to_string();
+            Some(Box::new(
+                descend(cfg, instance, branch, Some(parent_schema), ref_context)
+                    .map(move |err| err.schema_ctx(i.clone())),
+            ))
+        }
+        (None, Some(mapped)) => Some(make_error(
+            format!(
+                "Discriminator value \"{}\" maps to {}, which isn't one of the choices",
+                value, mapped
+            ),
+            Some(instance),
+            Some(discriminator),
+        )),
+        (None, None) => Some(make_error(
+            format!(
+                "Discriminator value \"{}\" is not in the mapping, or the name of one of the choices",
+                value
+            ),
+            Some(instance),
+            Some(discriminator),
+        )),
+    }
+}
+
+/// In OpenAPI 3.0, a `discriminator` alongside `anyOf` selects the schema to
+/// validate against, rather than trying each one in turn.
+pub fn anyOf_openapi30<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Some(parent), true) = (parent_schema, instance.is_object()) {
+        if let Some(discriminator) = parent.get("discriminator") {
+            if let Some(errors) =
+                discriminate(cfg, instance, schema, parent, discriminator, ref_context)
+            {
+                return errors;
+            }
+        }
+    }
+    anyOf(cfg, instance, schema, parent_schema, ref_context)
+}
+
+/// In OpenAPI 3.0, a `discriminator` alongside `oneOf` selects the schema to
+/// validate against, rather than trying each one in turn.
+pub fn oneOf_openapi30<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Some(parent), true) = (parent_schema, instance.is_object()) {
+        if let Some(discriminator) = parent.get("discriminator") {
+            if let Some(errors) =
+                discriminate(cfg, instance, schema, parent, discriminator, ref_context)
+            {
+                return errors;
+            }
+        }
+    }
+    oneOf(cfg, instance, schema, parent_schema, ref_context)
+}
+
+pub fn not<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if descend(cfg, instance, schema, parent_schema, ref_context)
+        .next()
+        .is_none()
+    {
+        make_error("not", Some(instance), Some(schema))
+    } else {
+        no_error()
+    }
+}
+
+/// Validate the instance against the schema resolved from a reference. `scope`
+/// is the base URL of the resolved schema and `resource` is the schema
+/// resource that contains it.
+fn descend_reference<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    reference: &'a str,
+    resolved: Result<(url::Url, &'a Value, &'a Value), ValidationError>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    match resolved {
+        Ok((scope, resource, resolved)) => {
+            if let Some(mut chain) = ref_context.reference_chain(resolved, instance) {
+                chain.push(reference);
+                return Box::new(std::iter::once(reference_cycle_error(
+                    &chain,
+                    Some(instance),
+                    Some(schema),
+                )));
+            }
+            let scope_schema = json!({"$id": scope.to_string()});
+            let draft = resource_draft(cfg, resource, &ref_context);
+            let errors: Vec<ValidationError> = descend(
+                cfg,
+                instance,
+                resolved,
+                Some(schema),
+                ref_context
+                    .push_resource(&scope_schema, resource)
+                    .with_draft(draft)
+                    .with_reference(Reference {
+                        reference,
+                        schema: resolved,
+                        instance,
+                    }),
+            )
+            .collect();
+            Box::new(errors.into_iter())
+        }
+        Err(err) => Box::new(std::iter::once(ValidationError {
+            policy_violation: err.policy_violation,
+            ..ValidationError::new(
+                &format!("Couldn't resolve reference {}: {}", reference, err.msg),
+                Some(instance),
+                None,
+            )
+        })),
+    }
+}
+
+/// Make the error for a chain of references that applies a schema to the
+/// same instance again, which would go on forever.
+fn reference_cycle_error(
+    chain: &[&str],
+    instance: Option<&Value>,
+    schema: Option<&Value>,
+) -> ValidationError {
+    ValidationError::new(
+        &format!("Infinite reference cycle: {}", chain.join(" -> ")),
+        instance,
+        schema,
+    )
+}
+
+pub fn ref_<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Value::String(sref) = schema {
+        let resolved =
+            cfg.get_resolver()
+                .resolve_fragment(cfg.draft, sref, &ref_context, cfg.get_schema());
+        return descend_reference(cfg, instance, schema, sref, resolved, ref_context);
+    }
+    no_error()
+}
+
+fn has_recursive_anchor(schema: &Value) -> bool {
+    schema.get("$recursiveAnchor") == Some(&Bool(true))
+}
+
+/// Resolve a `$recursiveRef`. If the initially resolved schema has
+/// `"$recursiveAnchor": true`, the reference is instead resolved to the
+/// outermost schema resource in the dynamic scope that also has it, as long
+/// as there are no resources without it in between.
+fn resolve_recursive_ref<'a>(
+    cfg: &'a Config<'a>,
+    reference: &str,
+    ref_context: &Context<'a>,
+) -> Result<(url::Url, &'a Value, &'a Value), ValidationError> {
+    let resolver = cfg.get_resolver();
+    let mut resolved =
+        resolver.resolve_fragment(cfg.draft, reference, ref_context, cfg.get_schema())?;
+    if has_recursive_anchor(resolved.2) {
+        for (url, resource) in resolver.dynamic_scope(cfg.draft, ref_context)? {
+            if !has_recursive_anchor(resource) {
+                break;
+            }
+            resolved = (url, resource, resource);
+        }
+    }
+    Ok(resolved)
+}
+
+pub fn recursiveRef<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Value::String(sref) = schema {
+        let resolved = resolve_recursive_ref(cfg, sref, &ref_context);
+        return descend_reference(cfg, instance, schema, sref, resolved, ref_context);
+    }
+    no_error()
+}
+
+/// Resolve a `$dynamicRef`. If the initially resolved schema has a
+/// `$dynamicAnchor` matching the reference's fragment, the reference is instead
+/// resolved to the outermost schema resource in the dynamic scope that has a
+/// `$dynamicAnchor` of the same name.
+fn resolve_dynamic_ref<'a>(
+    cfg: &'a Config<'a>,
+    reference: &str,
+    ref_context: &Context<'a>,
+) -> Result<(url::Url, &'a Value, &'a Value), ValidationError> {
+    let resolver = cfg.get_resolver();
+    let resolved =
+        resolver.resolve_fragment(cfg.draft, reference, ref_context, cfg.get_schema())?;
+    let url = resolver.join_url(cfg.draft, reference, ref_context)?;
+    let anchor = match url.fragment() {
+        Some(anchor) if resolver::dynamic_anchor_of(cfg.draft, resolved.2) == Some(anchor) => {
+            anchor
+        }
+        _ => return Ok(resolved),
+    };
+    for (mut url, _resource) in resolver
+        .dynamic_scope(cfg.draft, ref_context)?
+        .into_iter()
+        .rev()
+    {
+        url.set_fragment(Some(anchor));
+        if let Ok(candidate) =
+            resolver.resolve_fragment(cfg.draft, url.as_str(), ref_context, cfg.get_schema())
+        {
+            if resolver::dynamic_anchor_of(cfg.draft, candidate.2) == Some(anchor) {
+                return Ok(candidate);
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+pub fn dynamicRef<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    _parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let Value::String(sref) = schema {
+        let resolved = resolve_dynamic_ref(cfg, sref, &ref_context);
+        return descend_reference(cfg, instance, schema, sref, resolved, ref_context);
+    }
+    no_error()
+}
+
+/// Check that no subschema of the `Config`'s schema applies itself to the same
+/// instance again through its references, with no keyword such as
+/// `properties` moving on to a part of the instance in between, as
+/// validating against it would never end.
+///
+/// Only `$ref` is followed, as `$recursiveRef` and `$dynamicRef` depend on
+/// the dynamic scope, and references that can't be resolved without
+/// retrieving a document are skipped. The cycles that this misses are
+/// reported when validating instead.
+pub(crate) fn check_reference_cycles(cfg: &Config) -> Result<(), ValidationError> {
+    check_subschema_cycles(
+        cfg,
+        cfg.get_schema(),
+        &mut Vec::new(),
+        Context::new_from(cfg.get_schema()),
+        &mut HashSet::new(),
+    )
+}
+
+/// Check for reference cycles starting from `schema`, at `path` in the
+/// `Config`'s schema, and from each of its subschemas. The schemas that are
+/// known not to start a cycle are kept in `done`.
+fn check_subschema_cycles<'a>(
+    cfg: &'a Config<'a>,
+    schema: &'a Value,
+    path: &mut Vec<String>,
+    ref_context: Context<'a>,
+    done: &mut HashSet<*const Value>,
+) -> Result<(), ValidationError> {
+    if let Object(schema_object) = schema {
+        if enters_resource(cfg, schema, &ref_context) {
+            let draft = resource_draft(cfg, schema, &ref_context);
+            let frame = ref_context.push(schema).with_draft(draft);
+            let base_url = resource_base_url(cfg, &frame);
+            return check_subschema_cycles(
+                cfg,
+                schema,
+                path,
+                frame.with_base_url(base_url.as_deref()),
+                done,
+            );
+        }
+        if let Err(chain) = find_reference_cycle(cfg, schema, ref_context, &mut Vec::new(), done) {
+            let chain: Vec<&str> = chain.iter().map(String::as_str).collect();
+            return Err(ValidationError {
+                schema_path: path.iter().rev().cloned().collect(),
+                ..reference_cycle_error(&chain, None, Some(schema))
+            });
+        }
+        for (subpath, subschema) in resolver::subschemas_with_paths(schema_object) {
+            let len = path.len();
+            path.extend(subpath);
+            check_subschema_cycles(cfg, subschema, path, ref_context, done)?;
+            path.truncate(len);
+        }
+    }
+    Ok(())
+}
+
+/// Follow the references and the other keywords that apply subschemas to the
+/// same instance as `schema`, looking for a path back to one of the schemas
+/// in `chain`, which holds the schemas followed to reach `schema`, each with
+/// the reference followed from it, if any. On finding one, get the references
+/// that make up the cycle.
+fn find_reference_cycle<'a>(
+    cfg: &'a Config<'a>,
+    schema: &'a Value,
+    ref_context: Context<'a>,
+    chain: &mut Vec<(*const Value, Option<String>)>,
+    done: &mut HashSet<*const Value>,
+) -> Result<(), Vec<String>> {
+    let schema_object = match schema {
+        Object(schema_object) if !done.contains(&(schema as *const Value)) => schema_object,
+        _ => return Ok(()),
+    };
+    if let Some(start) = chain.iter().position(|(x, _)| std::ptr::eq(*x, schema)) {
+        return Err(chain[start..]
+            .iter()
+            .filter_map(|(_, x)| x.clone())
+            .collect());
+    }
+    if enters_resource(cfg, schema, &ref_context) {
+        let draft = resource_draft(cfg, schema, &ref_context);
+        let frame = ref_context.push(schema).with_draft(draft);
+        let base_url = resource_base_url(cfg, &frame);
+        return find_reference_cycle(
+            cfg,
+            schema,
+            frame.with_base_url(base_url.as_deref()),
+            chain,
+            done,
+        );
+    }
+    let get_validator = |key: &str| match ref_context.draft {
+        Some(draft) => draft.get_validator(key).is_some(),
+        None => cfg.get_validator(key).is_some(),
+    };
+    let draft = ref_context.get_draft(cfg.draft);
+    let only_ref = cfg.ref_overrides_siblings(draft) && schema_object.contains_key("$ref");
+
+    let mut subschemas: Vec<&Value> = Vec::new();
+    for (keyword, value) in schema_object {
+        if (only_ref && keyword != "$ref") || !get_validator(keyword) {
+            continue;
+        }
+        match (keyword.as_str(), value) {
+            ("$ref", Value::String(reference)) => {
+                let resolved = cfg.get_resolver().resolve_known_fragment(
+                    cfg.draft,
+                    reference,
+                    &ref_context,
+                    cfg.get_schema(),
+                );
+                let (scope, resource, resolved) = match resolved {
+                    Some(resolved) => resolved,
+                    None => continue,
+                };
+                let scope_schema = json!({"$id": scope.to_string()});
+                let draft = resource_draft(cfg, resource, &ref_context);
+                chain.push((schema, Some(reference.clone())));
+                let result = find_reference_cycle(
+                    cfg,
+                    resolved,
+                    ref_context
+                        .push_resource(&scope_schema, resource)
+                        .with_draft(draft),
+                    chain,
+                    done,
+                );
+                chain.pop();
+                result?;
+            }
+            ("allOf" | "anyOf" | "oneOf" | "extends", Array(items)) => subschemas.extend(items),
+            ("not" | "extends", _) => subschemas.push(value),
+            ("if", _) => subschemas.extend(
+                ["then", "else"]
+                    .iter()
+                    .filter_map(|x| schema_object.get(*x))
+                    .chain(std::iter::once(value)),
+            ),
+            ("dependentSchemas" | "dependencies", Object(dependencies)) => {
+                subschemas.extend(dependencies.values().filter(|x| !x.is_array()))
+            }
+            _ => {}
+        }
+    }
+    for subschema in subschemas {
+        chain.push((schema, None));
+        let result = find_reference_cycle(cfg, subschema, ref_context, chain, done);
+        chain.pop();
+        result?;
+    }
+    done.insert(schema);
+    Ok(())
+}
+
+pub fn if_<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if descend(cfg, instance, schema, parent_schema, ref_context)
+        .next()
+        .is_none()
+    {
+        if let Some(then) = parent_schema.and_then(|x| x.get("then")) {
+            if then.is_object() || then.is_boolean() {
+                return Box::new(
+                    descend(cfg, instance, then, Some(schema), ref_context)
+                        .map(move |err| err.schema_ctx("then".to_string())),
+                );
+            }
+        }
+    } else if let Some(else_) = parent_schema.and_then(|x| x.get("else")) {
+        if else_.is_object() || else_.is_boolean() {
+            return Box::new(
+                descend(cfg, instance, else_, Some(schema), ref_context)
+                    .map(move |err| err.schema_ctx("else".to_string())),
+            );
+        }
+    }
+    no_error()
+}
+
+pub(crate) type EvaluatingSchemaVisitor<'v> =
+    dyn for<'b> FnMut(&'b Config<'b>, &'b Map<String, Value>, Context<'b>) + 'v;
+
+/// Visit the schema objects that contribute annotations to the evaluation of
+/// `instance` against `schema`: the schema itself, and all of the in-place
+/// applicators (such as `allOf` or `$ref`) that successfully apply to the
+/// instance. This is used to find the parts of the instance that
+/// `unevaluatedProperties` and `unevaluatedItems` should apply to, and the
+/// links of an instance.
+pub(crate) fn visit_evaluating_schemas<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    ref_context: Context<'a>,
+    visit: &mut EvaluatingSchemaVisitor<'_>,
+) {
+    if let Object(schema_object) = schema {
+        if enters_resource(cfg, schema, &ref_context) {
+            let draft = resource_draft(cfg, schema, &ref_context);
+            let frame = ref_context.push(schema).with_draft(draft);
+            let base_url = resource_base_url(cfg, &frame);
+            return visit_evaluating_schemas(
+                cfg,
+                instance,
+                schema,
+                frame.with_base_url(base_url.as_deref()),
+                visit,
+            );
+        }
+
+        visit(cfg, schema_object, ref_context);
+
+        for (keyword, value) in schema_object {
+            match (keyword.as_str(), value) {
+                ("$ref", Value::String(reference))
+                | ("$recursiveRef", Value::String(reference))
+                | ("$dynamicRef", Value::String(reference)) => {
+                    let resolved = match keyword.as_str() {
+                        "$recursiveRef" => resolve_recursive_ref(cfg, reference, &ref_context),
+                        "$dynamicRef" => resolve_dynamic_ref(cfg, reference, &ref_context),
+                        _ => cfg.get_resolver().resolve_fragment(
+                            cfg.draft,
+                            reference,
+                            &ref_context,
+                            cfg.get_schema(),
+                        ),
+                    };
+                    if let Ok((scope, resource, resolved)) = resolved {
+                        // A cycle is reported by validating, and only has to
+                        // be stopped here.
+                        if ref_context.reference_chain(resolved, instance).is_some() {
+                            continue;
+                        }
+                        let scope_schema = json!({"$id": scope.to_string()});
+                        let draft = resource_draft(cfg, resource, &ref_context);
+                        visit_evaluating_schemas(
+                            cfg,
+                            instance,
+                            resolved,
+                            ref_context
+                                .push_resource(&scope_schema, resource)
+                                .with_draft(draft)
+                                .with_reference(Reference {
+                                    reference,
+                                    schema: resolved,
+                                    instance,
+                                }),
+                            visit,
+                        );
+                    }
+                }
+                ("allOf", Array(subschemas))
+                | ("anyOf", Array(subschemas))
+                | ("oneOf", Array(subschemas)) => {
+                    for subschema in subschemas {
+                        if is_valid(cfg, instance, subschema, Some(schema), ref_context) {
+                            visit_evaluating_schemas(cfg, instance, subschema, ref_context, visit);
+                        }
+                    }
+                }
+                ("dependentSchemas", Object(dependencies)) => {
+                    if let Object(instance_object) = instance {
+                        for (property, dependency) in dependencies {
+                            if instance_object.contains_key(property) {
+                                visit_evaluating_schemas(
+                                    cfg,
+                                    instance,
+                                    dependency,
+                                    ref_context,
+                                    visit,
+                                );
+                            }
+                        }
+                    }
+                }
+                ("if", _) => {
+                    let branch = if is_valid(cfg, instance, value, Some(schema), ref_context) {
+                        visit_evaluating_schemas(cfg, instance, value, ref_context, visit);
+                        schema_object.get("then")
+                    } else {
+                        schema_object.get("else")
+                    };
+                    if let Some(branch) = branch {
+                        visit_evaluating_schemas(cfg, instance, branch, ref_context, visit);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn unevaluatedProperties<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Object(instance_object), Some(parent_schema)) = (instance, parent_schema) {
+        let mut evaluated: HashSet<&str> = HashSet::new();
+        visit_evaluating_schemas(
+            cfg,
+            instance,
+            parent_schema,
+            ref_context,
+            &mut |cfg, schema_object, ref_context| {
+                for (keyword, value) in schema_object {
+                    match (keyword.as_str(), value) {
+                        ("properties", Object(properties)) => evaluated.extend(
+                            instance_object
+                                .keys()
+                                .filter(|&k| properties.contains_key(k))
+                                .map(String::as_str),
+                        ),
+                        ("patternProperties", Object(patterns)) => {
+                            let regexes: Vec<regex::Regex> = patterns
+                                .keys()
+                                .filter_map(|k| regex::Regex::new(k).ok())
+                                .collect();
+                            evaluated.extend(
+                                instance_object
+                                    .keys()
+                                    .filter(|&k| regexes.iter().any(|re| re.is_match(k)))
+                                    .map(String::as_str),
+                            )
+                        }
+                        ("additionalProperties", _) | ("unevaluatedProperties", _) => evaluated
+                            .extend(
+                                instance_object
+                                    .iter()
+                                    .filter(|&(_, v)| is_valid(cfg, v, value, None, ref_context))
+                                    .map(|(k, _)| k.as_str()),
+                            ),
+                        _ => {}
+                    }
+                }
+            },
+        );
+
+        let mut unevaluated = instance_object
+            .keys()
+            .filter(move |&k| !evaluated.contains(k.as_str()))
+            .map(String::as_str);
+
+        match schema {
+            Bool(false) => {
+                let unevaluated_string = util::format_list(&mut unevaluated);
+                if !unevaluated_string.is_empty() {
+                    return make_error(
+                        format!(
+                            "Unevaluated properties are not allowed. Found {}.",
+                            unevaluated_string
+                        ),
+                        Some(instance),
+                        Some(parent_schema),
+                    );
+                }
+            }
+            Object(_) => {
+                return Box::new(unevaluated.flat_map(move |property| {
+                    descend(
+                        cfg,
+                        instance.get(property).unwrap(),
+                        schema,
+                        Some(parent_schema),
+                        ref_context,
+                    )
+                    .map(move |err| err.instance_ctx(property.to_string()))
+                }));
+            }
+            _ => {}
+        }
+    }
+    no_error()
+}
+
+pub fn unevaluatedItems<'a>(
+    cfg: &'a Config<'a>,
+    instance: &'a Value,
+    schema: &'a Value,
+    parent_schema: Option<&'a Value>,
+    ref_context: Context<'a>,
+) -> ErrorIterator<'a> {
+    if let (Array(instance_array), Some(parent_schema)) = (instance, parent_schema) {
+        let mut evaluated: HashSet<usize> = HashSet::new();
+        visit_evaluating_schemas(
+            cfg,
+            instance,
+            parent_schema,
+            ref_context,
+            &mut |cfg, schema_object, ref_context| {
+                for (keyword, value) in schema_object {
+                    match (keyword.as_str(), value) {
+                        ("items", Array(items)) | ("prefixItems", Array(items)) => {
+                            evaluated.extend(0..items.len())
+                        }
+                        ("items", _) => evaluated.extend(0..instance_array.len()),
+                        ("additionalItems", _) => {
+                            if let Some(Array(_)) = schema_object.get("items") {
+                                evaluated.extend(0..instance_array.len())
+                            }
+                        }
+                        // Only from Draft 2020-12 do the items matching
+                        // `contains` count as evaluated.
+                        ("contains", _)
+                            if ref_context.get_draft(cfg.draft).get_draft_number() >= 9 =>
+                        {
+                            evaluated.extend(
+                                instance_array
+                                    .iter()
+                                    .enumerate()
+                                    .filter(|&(_, item)| {
+                                        is_valid(cfg, item, value, None, ref_context)
+                                    })
+                                    .map(|(index, _)| index),
+                            )
+                        }
+                        ("unevaluatedItems", _) => evaluated.extend(
+                            instance_array
+                                .iter()
+                                .enumerate()
+                                .filter(|&(_, item)| is_valid(cfg, item, value, None, ref_context))
+                                .map(|(index, _)| index),
+                        ),
+                        _ => {}
+                    }
+                }
+            },
+        );
+
+        let mut unevaluated = instance_array
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| !evaluated.contains(index));
+
+        match schema {
+            Bool(false) if unevaluated.next().is_some() => {
+                return make_error(
+                    "Unevaluated items are not allowed.",
+                    Some(instance),
+                    Some(parent_schema),
+                );
+            }
+            Object(_) => {
+                return Box::new(unevaluated.flat_map(move |(index, item)| {
+                    descend(cfg, item, schema, Some(parent_schema), ref_context)
+                        .map(move |err| err.instance_ctx(index.to_string()))
+                }));
+            }
+            _ => {}
+        }
+    }
+    no_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{schemas, Config, ValidationError};
+    use serde_json::json;
+
+    #[test]
+    fn test_additional_properties_errors() {
+        let schema = json!({
+            "properties": { "foo": { "type": "integer" } },
+            "additionalProperties": false
+        });
+        let instance = json!({
+            "foo": 42,
+            "bar": "additional",
+            "baz": "another additional"
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft6)).unwrap();
+        let validation = cfg.validate(&instance);
 
         if let Err(errors) = validation {
             for error in errors {
@@ -1111,4 +2443,762 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_unevaluated_properties_through_ref() {
+        let schema = json!({
+            "$defs": { "foo": { "properties": { "foo": { "type": "string" } } } },
+            "$ref": "#/$defs/foo",
+            "properties": { "bar": { "type": "string" } },
+            "unevaluatedProperties": false
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft201909)).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!({"foo": "a", "bar": "b"})).is_ok());
+
+        let errors: Vec<_> = cfg
+            .validate(&json!({"foo": "a", "bar": "b", "baz": "c"}))
+            .unwrap_err()
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert!(format!("{}", errors[0])
+            .contains("Unevaluated properties are not allowed. Found \"baz\"."));
+    }
+
+    #[test]
+    fn test_recursive_ref() {
+        let schema = json!({
+            "$id": "http://example.com/strict-tree.json",
+            "$recursiveAnchor": true,
+            "$ref": "tree.json",
+            "$defs": {
+                "tree": {
+                    "$id": "tree.json",
+                    "$recursiveAnchor": true,
+                    "type": "object",
+                    "properties": {
+                        "children": { "type": "array", "items": { "$recursiveRef": "#" } }
+                    }
+                }
+            },
+            "unevaluatedProperties": false
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft201909)).unwrap();
+        assert!(cfg
+            .validate(&json!({"children": [{"children": []}]}))
+            .is_ok());
+        assert!(cfg.validate(&json!({"children": [{"daat": 1}]})).is_err());
+    }
+
+    #[test]
+    fn test_dynamic_ref() {
+        let schema = json!({
+            "$id": "https://example.com/root",
+            "$ref": "list",
+            "$defs": {
+                "foo": { "$dynamicAnchor": "items", "type": "string" },
+                "list": {
+                    "$id": "list",
+                    "type": "array",
+                    "items": { "$dynamicRef": "#items" },
+                    "$defs": { "items": { "$dynamicAnchor": "items" } }
+                }
+            }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft202012)).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!(["foo", "bar"])).is_ok());
+
+        let errors: Vec<_> = cfg.validate(&json!(["foo", 42])).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].instance_path, vec!("1"));
+    }
+
+    #[test]
+    fn test_reference_cycles() {
+        let message = |schema: serde_json::Value| match Config::from_schema(&schema, None) {
+            Ok(_) => None,
+            Err(err) => Some((err.msg, err.schema_path)),
+        };
+        assert_eq!(
+            message(json!({"$ref": "#"})),
+            Some(("Infinite reference cycle: #".to_string(), vec![]))
+        );
+        assert_eq!(
+            message(json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "properties": {"a": {"$ref": "#/$defs/a"}},
+                "$defs": {
+                    "a": {"anyOf": [{"$ref": "#/$defs/b"}]},
+                    "b": {"not": {"$ref": "#/$defs/a"}}
+                }
+            })),
+            Some((
+                "Infinite reference cycle: #/$defs/b -> #/$defs/a".to_string(),
+                vec!["a".to_string(), "$defs".to_string()]
+            ))
+        );
+        // Each reference here moves on to a part of the instance.
+        let tree = json!({
+            "properties": {"children": {"items": {"$ref": "#"}}},
+            "definitions": {"unused": {"$ref": "#/definitions/other"}, "other": {}}
+        });
+        let cfg = Config::from_schema(&tree, None).unwrap();
+        assert!(cfg
+            .validate(&json!({"children": [{"children": [{}]}]}))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_reference_cycle_guard() {
+        // A cycle through a retrieved document is only found when validating.
+        let schema = json!({"properties": {"a": {"$ref": "https://example.com/a.json"}}});
+        let cfg = Config::from_schema(&schema, None)
+            .unwrap()
+            .with_retriever(|_: &str| {
+                Ok(json!({"$ref": "#/definitions/b", "definitions": {"b": {"$ref": "#"}}}))
+            });
+        let errors: Vec<_> = cfg.validate(&json!({"a": 1})).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].msg,
+            "Infinite reference cycle: #/definitions/b -> #"
+        );
+        assert_eq!(errors[0].instance_path, vec!["a"]);
+        assert!(cfg.validate(&json!({"b": 1})).is_ok());
+
+        // So is one through a dynamic reference.
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$dynamicAnchor": "node",
+            "allOf": [{"$dynamicRef": "#node"}]
+        });
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        let errors: Vec<_> = cfg.validate(&json!(1)).unwrap_err().collect();
+        assert_eq!(errors[0].msg, "Infinite reference cycle: #node");
+    }
+
+    #[test]
+    fn test_ref_siblings() {
+        let schema = json!({
+            "definitions": { "name": { "type": "string" } },
+            "properties": {
+                "name": { "$ref": "#/definitions/name", "maxLength": 3 }
+            }
+        });
+        let instance = json!({ "name": 42 });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft7)).unwrap();
+        let errors: Vec<_> = cfg.validate(&instance).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].schema_path, vec!["type", "name", "properties"]);
+        assert!(cfg.validate(&json!({ "name": "long" })).is_ok());
+
+        let cfg = cfg.with_ref_siblings(true);
+        let errors: Vec<_> = cfg.validate(&instance).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].schema_path,
+            vec!["type", "$ref", "name", "properties"]
+        );
+        let errors: Vec<_> = cfg
+            .validate(&json!({ "name": "long" }))
+            .unwrap_err()
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].schema_path,
+            vec!["maxLength", "name", "properties"]
+        );
+
+        // Later drafts always evaluate the siblings.
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft201909)).unwrap();
+        assert!(cfg.validate(&json!({ "name": "long" })).is_err());
+    }
+
+    #[test]
+    fn test_retriever() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let schema = json!({
+            "$id": "http://example.com/root.json",
+            "properties": {
+                "a": { "$ref": "remote.json#/$defs/positive" },
+                "b": { "$ref": "http://example.com/remote.json" },
+                "c": { "$ref": "missing.json" }
+            }
+        });
+        let calls = AtomicUsize::new(0);
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft202012))
+            .unwrap()
+            .with_retriever(|uri: &str| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                match uri {
+                    "http://example.com/remote.json" => Ok(json!({
+                        "$defs": { "positive": { "exclusiveMinimum": 0 } },
+                        "$ref": "#/$defs/positive"
+                    })),
+                    _ => Err(ValidationError::new("Not found", None, None)),
+                }
+            });
+        assert!(cfg.validate(&json!({ "a": 1, "b": 1 })).is_ok());
+        let errors: Vec<_> = cfg
+            .validate(&json!({ "a": -1, "b": -1 }))
+            .unwrap_err()
+            .collect();
+        assert_eq!(errors.len(), 2);
+
+        let errors: Vec<_> = cfg.validate(&json!({ "c": 1 })).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .msg
+            .contains("Can't retrieve url http://example.com/missing.json: Not found"));
+        drop(cfg);
+        // Each document is retrieved once; only the missing one is asked for again.
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_draft3_required() {
+        let schema = json!({
+            "properties": {
+                "foo": { "type": "integer", "required": true },
+                "bar": { "type": "string" }
+            }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft3)).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!({"foo": 1})).is_ok());
+
+        let errors: Vec<_> = cfg.validate(&json!({"bar": "x"})).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].schema_path, vec!("required", "foo", "properties"));
+        assert!(format!("{}", errors[0]).contains("Required property \"foo\" is missing"));
+    }
+
+    #[test]
+    fn test_openapi30_nullable() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "foo": { "type": "integer", "nullable": true } }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::OpenApi30)).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!({"foo": null})).is_ok());
+        assert!(cfg.validate(&json!({"foo": "bar"})).is_err());
+        assert!(cfg.validate(&json!(null)).is_err());
+    }
+
+    #[test]
+    fn test_openapi30_discriminator() {
+        let schema = json!({
+            "oneOf": [
+                { "$ref": "#/components/schemas/Cat" },
+                { "$ref": "#/components/schemas/Dog" },
+                { "$ref": "https://example.com/wolf.json" }
+            ],
+            "discriminator": {
+                "propertyName": "petType",
+                "mapping": {
+                    "dog": "Dog",
+                    "wolf": "https://example.com/wolf.json",
+                    "fish": "#/components/schemas/Fish"
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Cat": { "required": ["meow"] },
+                    "Dog": { "required": ["bark"] },
+                    "Fish": {}
+                }
+            }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::OpenApi30))
+            .unwrap()
+            .with_retriever(|_: &str| Ok(json!({ "required": ["howl"] })));
+        assert!(cfg
+            .validate(&json!({"petType": "Cat", "meow": true}))
+            .is_ok());
+        assert!(cfg
+            .validate(&json!({"petType": "dog", "bark": true}))
+            .is_ok());
+        assert!(cfg
+            .validate(&json!({"petType": "wolf", "howl": true}))
+            .is_ok());
+
+        let errors: Vec<_> = cfg
+            .validate(&json!({"petType": "Cat", "bark": true}))
+            .unwrap_err()
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].schema_path, vec!("required", "0", "oneOf"));
+
+        // Only the schemas of the branches can be selected.
+        let messages = |instance| -> Vec<String> {
+            cfg.validate(&instance)
+                .unwrap_err()
+                .map(|err| err.msg)
+                .collect()
+        };
+        assert_eq!(
+            messages(json!({"petType": "Fish"})),
+            vec!["Discriminator value \"Fish\" is not in the mapping, or the name of one of the choices"]
+        );
+        assert_eq!(
+            messages(json!({"petType": "fish"})),
+            vec!["Discriminator value \"fish\" maps to #/components/schemas/Fish, which isn't one of the choices"]
+        );
+        assert_eq!(
+            messages(json!({"bark": true})),
+            vec!["Discriminator property \"petType\" is missing"]
+        );
+
+        // Without references to select, the branches are evaluated as usual.
+        let schema = json!({
+            "oneOf": [{ "required": ["meow"] }, { "required": ["bark"] }],
+            "discriminator": { "propertyName": "petType" }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::OpenApi30)).unwrap();
+        assert!(cfg.validate(&json!({"bark": true})).is_ok());
+    }
+
+    #[test]
+    fn test_openapi30_unsupported_keywords() {
+        let schema = json!({
+            "type": "object",
+            "patternProperties": { "^a": { "type": "string" } }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::OpenApi30)).unwrap();
+        assert!(cfg.validate_schema().is_err());
+
+        let schema = json!({ "type": "string", "x-internal": true, "example": "foo" });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::OpenApi30)).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+    }
+
+    #[test]
+    fn test_kubernetes() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "metadata": { "type": "object" },
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "port": { "x-kubernetes-int-or-string": true },
+                        "hosts": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "x-kubernetes-list-type": "set"
+                        },
+                        "ports": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string" },
+                                    "protocol": { "type": "string" }
+                                }
+                            },
+                            "x-kubernetes-list-type": "map",
+                            "x-kubernetes-list-map-keys": ["name", "protocol"]
+                        },
+                        "template": {
+                            "type": "object",
+                            "x-kubernetes-embedded-resource": true,
+                            "x-kubernetes-preserve-unknown-fields": true
+                        }
+                    }
+                }
+            }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Kubernetes)).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(crate::check_structural(&schema).is_ok());
+        assert!(cfg
+            .validate(&json!({
+                "metadata": { "name": "x", "labels": {} },
+                "spec": {
+                    "port": "http",
+                    "hosts": ["a", "b"],
+                    "ports": [
+                        { "name": "a", "protocol": "TCP" },
+                        { "name": "a", "protocol": "UDP" }
+                    ],
+                    "template": { "apiVersion": "v1", "kind": "Pod", "spec": {} }
+                }
+            }))
+            .is_ok());
+
+        let errors: Vec<_> = cfg
+            .validate(&json!({
+                "spec": {
+                    "port": 1.5,
+                    "hosts": ["a", "a"],
+                    "ports": [{ "name": "a" }, { "name": "a" }],
+                    "template": { "kind": "Pod" },
+                    "replicas": 1
+                }
+            }))
+            .unwrap_err()
+            .collect();
+        let messages: Vec<String> = errors.iter().map(|x| x.msg.clone()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Items of a set are not unique",
+                "Must be an integer or a string.",
+                "Items of a map are not unique for the keys \"name\", \"protocol\"",
+                "Embedded resource must have a string \"apiVersion\"",
+                "Unknown fields are not allowed. Found \"replicas\".",
+            ]
+        );
+
+        let schema = json!({ "type": "object", "x-kubernetes-list-type": "bogus" });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Kubernetes)).unwrap();
+        assert!(cfg.validate_schema().is_err());
+    }
+
+    #[test]
+    fn test_mongodb() {
+        let schema = json!({
+            "bsonType": "object",
+            "required": ["_id", "name", "created", "stock"],
+            "properties": {
+                "_id": { "bsonType": "objectId" },
+                "name": { "type": "string" },
+                "created": { "bsonType": "date" },
+                "stock": { "bsonType": ["int", "long"], "minimum": 0 },
+                "price": { "bsonType": "decimal", "maximum": 100 },
+                "tags": { "type": "array", "items": { "bsonType": "string" } }
+            }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::MongoDb)).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg
+            .validate(&json!({
+                "_id": { "$oid": "5f1d7a3c9b1e8a2f4c6d8e0a" },
+                "name": "widget",
+                "created": { "$date": "2024-01-01T00:00:00Z" },
+                "stock": { "$numberLong": "9000000000" },
+                "price": { "$numberDecimal": "9.99" },
+                "tags": ["a"]
+            }))
+            .is_ok());
+
+        let errors: Vec<_> = cfg
+            .validate(&json!({
+                "_id": "5f1d7a3c9b1e8a2f4c6d8e0a",
+                "name": { "$oid": "5f1d7a3c9b1e8a2f4c6d8e0a" },
+                "created": "2024-01-01T00:00:00Z",
+                "stock": { "$numberInt": "-1" },
+                "price": 9.99
+            }))
+            .unwrap_err()
+            .collect();
+        let messages: Vec<String> = errors.iter().map(|x| x.msg.clone()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Invalid BSON type string.",
+                "Invalid BSON type string.",
+                "Invalid type.",
+                "Invalid BSON type double.",
+                "-1 <= minimum 0",
+            ]
+        );
+        assert_eq!(errors[4].instance, Some(json!({ "$numberInt": "-1" })));
+
+        for schema in &[
+            json!({ "$ref": "#/definitions/x" }),
+            json!({ "type": "integer" }),
+            json!({ "format": "date-time" }),
+            json!({ "bsonType": "uuid" }),
+        ] {
+            let cfg = Config::from_schema(schema, Some(schemas::Draft::MongoDb)).unwrap();
+            assert!(cfg.validate_schema().is_err());
+        }
+    }
+
+    fn positive<'a>(
+        _cfg: &'a Config<'a>,
+        instance: &'a serde_json::Value,
+        schema: &'a serde_json::Value,
+        _parent_schema: Option<&'a serde_json::Value>,
+    ) -> crate::ErrorIterator<'a> {
+        match (instance.as_f64(), schema.as_bool()) {
+            (Some(n), Some(true)) if n <= 0.0 => Box::new(std::iter::once(
+                crate::ValidationError::new("Not positive.", Some(instance), Some(schema)),
+            )),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn shout(_cfg: &Config, value: &str) -> bool {
+        value.chars().all(|c| !c.is_lowercase())
+    }
+
+    #[test]
+    fn test_custom_dialect() {
+        let draft = schemas::Dialect::new(
+            "https://example.com/custom/schema#",
+            schemas::Draft::Draft202012,
+            json!({
+                "$ref": "https://json-schema.org/draft/2020-12/schema",
+                "properties": { "positive": { "type": "boolean" } }
+            }),
+        )
+        .with_keyword("positive", positive)
+        .with_draft_keyword("format", schemas::Draft::Draft7)
+        .with_format("shout", shout)
+        .without_keyword("minLength")
+        .register();
+        assert_eq!(
+            schemas::draft_from_url("https://example.com/custom/schema"),
+            Some(draft)
+        );
+
+        let schema = json!({
+            "$schema": "https://example.com/custom/schema",
+            "properties": {
+                "n": { "positive": true },
+                "s": { "format": "shout", "minLength": 10 }
+            }
+        });
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        assert_eq!(cfg.get_draft_number(), 9);
+        assert!(cfg.validate_schema().is_ok());
+        assert!(cfg.validate(&json!({"n": 1, "s": "HEY"})).is_ok());
+
+        let errors: Vec<_> = cfg
+            .validate(&json!({"n": -1, "s": "hey"}))
+            .unwrap_err()
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .any(|e| e.msg == "Not positive."
+                && e.schema_path == vec!["positive", "n", "properties"]));
+        assert!(errors.iter().any(|e| e.msg == "Invalid for format."));
+
+        let schema = json!({
+            "$schema": "https://example.com/custom/schema",
+            "positive": "yes"
+        });
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        assert!(cfg.validate_schema().is_err());
+    }
+
+    #[test]
+    fn test_vocabulary() {
+        let schema = json!({
+            "$schema": "https://example.com/meta/no-validation",
+            "$defs": {
+                "meta": {
+                    "$id": "https://example.com/meta/no-validation",
+                    "$vocabulary": {
+                        "https://json-schema.org/draft/2020-12/vocab/applicator": true,
+                        "https://example.com/vocab/optional": false
+                    }
+                }
+            },
+            "properties": { "n": { "type": "integer" }, "m": false }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft202012)).unwrap();
+        assert_eq!(
+            cfg.get_vocabularies(),
+            vec![
+                "https://json-schema.org/draft/2020-12/vocab/core",
+                "https://json-schema.org/draft/2020-12/vocab/applicator"
+            ]
+        );
+        assert!(cfg.validate(&json!({"n": "not validated"})).is_ok());
+        assert!(cfg.validate(&json!({"m": 0})).is_err());
+
+        let schema = json!({
+            "$schema": "https://example.com/meta/unknown",
+            "$defs": {
+                "meta": {
+                    "$id": "https://example.com/meta/unknown",
+                    "$vocabulary": { "https://example.com/vocab/unknown": true }
+                }
+            }
+        });
+        let error = Config::from_schema(&schema, Some(schemas::Draft::Draft202012))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.msg,
+            "Unknown required vocabulary https://example.com/vocab/unknown"
+        );
+
+        // A remote metaschema is retrieved once there is a retriever.
+        let schema = json!({
+            "$schema": "https://example.com/meta/remote",
+            "properties": { "n": { "type": "integer" } }
+        });
+        let metaschema = |vocabulary: &str| {
+            json!({
+                "$vocabulary": {
+                    "https://json-schema.org/draft/2020-12/vocab/core": true,
+                    vocabulary: true
+                }
+            })
+        };
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft202012)).unwrap();
+        assert!(cfg.validate(&json!({"n": "not validated"})).is_err());
+        let cfg = cfg.with_retriever(move |_: &str| {
+            Ok(metaschema(
+                "https://json-schema.org/draft/2020-12/vocab/applicator",
+            ))
+        });
+        assert!(cfg.validate(&json!({"n": "not validated"})).is_ok());
+
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft202012))
+            .unwrap()
+            .with_retriever(move |_: &str| Ok(metaschema("https://example.com/vocab/unknown")));
+        let errors: Vec<ValidationError> = cfg.validate(&json!({})).unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].msg,
+            "Unknown required vocabulary https://example.com/vocab/unknown"
+        );
+        assert!(cfg.validate_schema().is_err());
+    }
+
+    #[test]
+    fn test_vocabulary_format_assertion() {
+        schemas::Dialect::new(
+            "https://example.com/format-assertion/schema",
+            schemas::Draft::Draft202012,
+            json!({
+                "$vocabulary": {
+                    "https://json-schema.org/draft/2020-12/vocab/core": true,
+                    "https://json-schema.org/draft/2020-12/vocab/format-assertion": true
+                }
+            }),
+        )
+        .register();
+        let schema = json!({
+            "$schema": "https://example.com/format-assertion/schema",
+            "format": "ipv4"
+        });
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        assert!(cfg.validate(&json!("127.0.0.1")).is_ok());
+        assert!(cfg.validate(&json!("localhost")).is_err());
+    }
+
+    #[test]
+    fn test_schema_url_spellings() {
+        for url in &[
+            "http://json-schema.org/draft-04/schema#",
+            "https://json-schema.org/draft-04/schema",
+        ] {
+            assert_eq!(
+                schemas::draft_from_url(url),
+                Some(schemas::Draft::Draft4),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(
+            schemas::draft_from_url("http://json-schema.org/draft/2020-12/schema#"),
+            Some(schemas::Draft::Draft202012)
+        );
+        assert_eq!(
+            schemas::draft_from_url("http://json-schema.org/draft-05/schema#"),
+            None
+        );
+        for draft in &[
+            schemas::Draft::OpenApi30,
+            schemas::Draft::Kubernetes,
+            schemas::Draft::MongoDb,
+        ] {
+            assert_eq!(schemas::draft_from_url(draft.get_url()), Some(*draft));
+        }
+        // The official OpenAPI 3.0 document schema isn't taken to be the
+        // Schema Object metaschema, so that references to it are retrieved.
+        let url = "https://spec.openapis.org/oas/3.0/schema/2021-09-28";
+        assert_eq!(schemas::draft_from_url(url), None);
+        assert!(schemas::metaschema_from_url(url).is_none());
+    }
+
+    #[test]
+    fn test_strict_schema_detection() {
+        let schema = json!({"$schema": "https://json-schema.org/draft-06/schema#"});
+        let cfg = Config::from_schema_strict(&schema, None).unwrap();
+        assert_eq!(cfg.get_draft_number(), 6);
+
+        let schema = json!({});
+        let cfg = Config::from_schema_strict(&schema, None).unwrap();
+        assert_eq!(cfg.get_draft_number(), 7);
+
+        let schema = json!({"$schema": "http://example.com/unknown"});
+        assert!(Config::from_schema(&schema, None).is_ok());
+        let error = Config::from_schema_strict(&schema, None).err().unwrap();
+        assert_eq!(error.msg, "Unknown $schema \"http://example.com/unknown\"");
+        assert_eq!(error.schema_path, vec!["$schema"]);
+        assert!(Config::from_schema_strict(&schema, Some(schemas::Draft::Draft4)).is_ok());
+    }
+
+    #[test]
+    fn test_embedded_resource_draft() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "properties": {
+                "vendor": { "$ref": "http://example.com/vendor.json" },
+                "local": { "minimum": 0, "exclusiveMinimum": 0 }
+            },
+            "definitions": {
+                "vendor": {
+                    "$schema": "http://json-schema.org/draft-04/schema#",
+                    "id": "http://example.com/vendor.json",
+                    "properties": {
+                        "price": { "$ref": "#/definitions/price" }
+                    },
+                    "definitions": {
+                        "price": { "minimum": 0, "exclusiveMinimum": true }
+                    }
+                }
+            }
+        });
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        assert!(cfg.validate(&json!({"vendor": {"price": 1}})).is_ok());
+        assert!(cfg.validate(&json!({"vendor": {"price": 0}})).is_err());
+        assert!(cfg.validate(&json!({"local": 0})).is_err());
+        assert!(cfg.validate(&json!({"local": 1})).is_ok());
+
+        // The embedded resource is also used as a Draft 4 schema when it is
+        // reached directly, rather than through a reference.
+        let schema = json!({
+            "items": {
+                "$schema": "http://json-schema.org/draft-04/schema#",
+                "id": "http://example.com/item.json",
+                "minimum": 0,
+                "exclusiveMinimum": true
+            }
+        });
+        let cfg = Config::from_schema(&schema, Some(schemas::Draft::Draft7)).unwrap();
+        assert!(cfg.validate(&json!([1])).is_ok());
+        assert!(cfg.validate(&json!([0])).is_err());
+    }
+
+    #[test]
+    fn test_strict_ignored_keywords() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-04/schema#",
+            "properties": { "a": { "const": 1 } }
+        });
+        let cfg = Config::from_schema(&schema, None).unwrap();
+        assert!(cfg.validate_schema().is_ok());
+        assert_eq!(cfg.get_ignored_keywords().from_other_drafts.len(), 1);
+
+        let cfg = Config::from_schema_strict(&schema, None).unwrap();
+        let errors: Vec<_> = cfg.validate_schema().unwrap_err().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].msg,
+            "Keyword \"const\" is ignored by this draft, but known to Draft6, Draft7, Draft201909, Draft202012"
+        );
+        assert_eq!(errors[0].schema_path, vec!["const", "a", "properties"]);
+    }
 }